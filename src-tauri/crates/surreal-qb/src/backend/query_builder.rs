@@ -120,6 +120,10 @@ pub trait QueryBuilder: QuotedBuilder + EscapeBuilder + TableRefBuilder + OperLe
         }
 
         self.prepare_select_limit_offset(select, sql);
+
+        if !select.fetches.is_empty() {
+            write!(sql, " FETCH {}", select.fetches.join(", ")).unwrap();
+        }
     }
 
     // Translate the LIMIT and OFFSET expression in [`SelectStatement`]
@@ -557,6 +561,7 @@ pub trait QueryBuilder: QuotedBuilder + EscapeBuilder + TableRefBuilder + OperLe
     fn prepare_keyword(&self, keyword: &Keyword, sql: &mut dyn SurrelaQLWriter) {
         match keyword {
             Keyword::Null => write!(sql, "NULL").unwrap(),
+            Keyword::None => write!(sql, "NONE").unwrap(),
             Keyword::CurrentDate => write!(sql, "CURRENT_DATE").unwrap(),
             Keyword::CurrentTime => write!(sql, "CURRENT_TIME").unwrap(),
             Keyword::CurrentTimestamp => write!(sql, "CURRENT_TIMESTAMP").unwrap(),
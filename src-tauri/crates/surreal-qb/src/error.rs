@@ -31,7 +31,9 @@ pub enum Error {
 	Surreal(SurrealError),
 	IntoSurreal(IntoSurrealError),
 
-	ListLimitOverMax { max: i64, actial: i64 }
+	ListLimitOverMax { max: i64, actial: i64 },
+	ListExpandInvalid(String),
+	ListFieldsInvalid(String),
 }
 
 impl From<SurrealError> for Error {
@@ -60,6 +62,8 @@ impl std::fmt::Display for Error {
 				fmt,
 				"Columns and values length mismatch: {col_len} != {val_len}"
 			),
+			Self::ListExpandInvalid(field) => write!(fmt, "Invalid field to expand: '{field}'"),
+			Self::ListFieldsInvalid(field) => write!(fmt, "Invalid field to select: '{field}'"),
 			_ => write!(fmt, "{self:?}"),
 		}
 	}
@@ -212,6 +212,16 @@ impl Expr {
         self.binary(BinaryOper::Is, Keyword::Null)
     }
 
+    /// Express a `IS NONE` expression (SurrealDB specific, matches missing fields too).
+    pub fn is_none(self) -> SimpleExpr {
+        self.binary(BinaryOper::Is, Keyword::None)
+    }
+
+    /// Express a `IS NOT NONE` expression (SurrealDB specific).
+    pub fn is_not_none(self) -> SimpleExpr {
+        self.binary(BinaryOper::IsNot, Keyword::None)
+    }
+
     /// Express a `IS` expression.
     pub fn is<V>(self, v: V) -> SimpleExpr
         where
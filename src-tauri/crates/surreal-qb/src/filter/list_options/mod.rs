@@ -13,6 +13,23 @@ pub struct ListOptions {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
     pub order_bys: Option<OrderBys>,
+    /// Record link fields resolved to their records in the same query (`FETCH`)
+    pub expand: Option<Vec<String>>,
+    /// Fields selected instead of `*`. The `id` and the ordered fields are always selected.
+    pub fields: Option<Vec<String>>,
+    /// Opaque keyset cursor of the page to list, resolved by the caller into a condition.
+    /// The `offset` is ignored with a cursor.
+    pub cursor: Option<String>,
+    /// Count the records matching the filters, see `build_count_query_with_cond`
+    pub total: Option<bool>,
+}
+
+/// Field (or `a.b` field path) names only, they are written as is in the query
+fn is_field_path(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
 }
 
 impl From<OrderBys> for ListOptions {
@@ -63,12 +80,22 @@ pub fn finalize_list_options(list_options: Option<ListOptions>) -> Result<ListOp
         } else {
             list_options.limit = Some(LIST_LIMIT_DEFAULT);
         }
+        if let Some(field) = list_options.expand.iter().flatten().find(|field| !is_field_path(field)) {
+            return Err(Error::ListExpandInvalid(field.clone()));
+        }
+        if let Some(field) = list_options.fields.iter().flatten().find(|field| !is_field_path(field)) {
+            return Err(Error::ListFieldsInvalid(field.clone()));
+        }
         Ok(list_options)
     } else {
         Ok(ListOptions {
             limit: Some(LIST_LIMIT_DEFAULT),
             offset: None,
-            order_bys: Some("id".into())
+            order_bys: Some("id".into()),
+            expand: None,
+            fields: None,
+            cursor: None,
+            total: None,
         })
     }
 }
@@ -90,15 +117,65 @@ mod surralql {
                 select_query.limit(as_positive_u64(limit)); // Note: Negative == 0
             }
 
-            if let Some(offset) = self.offset {
+            if let Some(offset) = self.offset.filter(|_| self.cursor.is_none()) {
                 select_query.offset(as_positive_u64(offset)); // Note: Negative == 0
             }
 
+            let mut order_cols = vec![];
             if let Some(order_bys) = self.order_bys {
                 for (col, order) in order_bys.into_surrealql_order_iter() {
+                    order_cols.push(col.clone());
                     select_query.order_by(col, order);
                 }
             }
+
+            if let Some(fields) = self.fields {
+                // Note: the records are ordered after the projection, the ordered fields must be selected
+                let mut cols = vec!["id".to_string()];
+                for col in fields.into_iter().chain(order_cols) {
+                    if !cols.contains(&col) {
+                        cols.push(col);
+                    }
+                }
+                select_query.clear_selects().columns(cols);
+            }
+
+            for field in self.expand.into_iter().flatten() {
+                select_query.fetch(field);
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_query::build_select_query;
+    use crate::filter::FilterGroups;
+
+    fn expand(fields: &[&str]) -> ListOptions {
+        ListOptions {
+            expand: Some(fields.iter().map(|field| field.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_finalize_expand() {
+        assert!(finalize_list_options(Some(expand(&["tags", "folder.parent"]))).is_ok());
+        assert!(matches!(
+            finalize_list_options(Some(expand(&["tags; DELETE picture"]))),
+            Err(Error::ListExpandInvalid(_))
+        ));
+        assert!(finalize_list_options(Some(expand(&[""]))).is_err());
+    }
+
+    #[test]
+    fn test_expand_query() {
+        let (sql, _) = build_select_query::<FilterGroups>("picture".to_string(), None, expand(&["tags", "categories"]));
+        assert_eq!(sql, "SELECT * FROM type::table($tb) FETCH tags, categories");
+
+        let (sql, _) = build_select_query::<FilterGroups>("picture".to_string(), None, ListOptions::default());
+        assert!(!sql.contains("FETCH"));
+    }
+}
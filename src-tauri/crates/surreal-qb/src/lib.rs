@@ -25,10 +25,17 @@ pub use analyzer::*;
 pub mod build_query {
     use surrealdb::sql::Object;
     use crate::filter::{FilterGroups, ListOptions};
-    use crate::{Condition, Query, SurrealQueryBuilder};
+    use crate::{Condition, Query, SimpleExpr, SurrealQueryBuilder};
 
     pub fn build_select_query<F>(table: String, filters: Option<F>, list_options: ListOptions) -> (String, Object)
         where F: Into<FilterGroups>,
+    {
+        build_select_query_with_cond(table, filters, None, list_options)
+    }
+
+    /// Same as `build_select_query`, but `and_cond` is ANDed with the filters
+    pub fn build_select_query_with_cond<F>(table: String, filters: Option<F>, and_cond: Option<SimpleExpr>, list_options: ListOptions) -> (String, Object)
+        where F: Into<FilterGroups>,
     {
         let mut query = Query::select();
         query.from(table).column("*".to_string());
@@ -38,6 +45,7 @@ pub mod build_query {
             println!("{cond:?}");
             query.cond_where(cond);
         }
+        query.and_where_option(and_cond);
         list_options.apply_to_surreal_query(&mut query);
         // let (sql, object) = query.build(SurrealQueryBuilder);
         // println!("build_select_query SQL: {sql:#?}");
@@ -45,4 +53,16 @@ pub mod build_query {
         // (sql, object)
         query.build(SurrealQueryBuilder)
     }
+
+    /// The number of records matching the filters and `and_cond`, as `total` (no row when none match)
+    pub fn build_count_query_with_cond<F>(table: String, filters: Option<F>, and_cond: Option<SimpleExpr>) -> (String, Object)
+        where F: Into<FilterGroups>,
+    {
+        let list_options = ListOptions {
+            fields: Some(vec!["id".to_string()]),
+            ..Default::default()
+        };
+        let (sql, object) = build_select_query_with_cond(table, filters, and_cond, list_options);
+        (format!("SELECT count() AS total FROM ({sql}) GROUP ALL"), object)
+    }
 }
@@ -27,6 +27,7 @@ pub struct SelectStatement {
     pub(crate) orders: Vec<OrderExpr>,
     pub(crate) limit: Option<Value>,
     pub(crate) offset: Option<Value>,
+    pub(crate) fetches: Vec<String>,
 }
 
 impl<T> From<T> for SelectExpr
@@ -57,6 +58,7 @@ impl SelectStatement {
             orders: std::mem::take(&mut self.orders),
             limit: self.limit.take(),
             offset: self.offset.take(),
+            fetches: std::mem::take(&mut self.fetches),
         }
     }
 
@@ -231,6 +233,13 @@ impl SelectStatement {
         self.offset = None;
         self
     }
+
+    /// Resolve a record link field to its record (`FETCH`).
+    /// Note: the field name is written as is.
+    pub fn fetch(&mut self, field: String) -> &mut Self {
+        self.fetches.push(field);
+        self
+    }
 }
 
 #[inherent]
@@ -308,3 +317,23 @@ impl ConditionalStatement for SelectStatement {
     pub fn and_where_option(&mut self, other: Option<SimpleExpr>) -> &mut Self;
     pub fn and_where(&mut self, other: SimpleExpr) -> &mut Self;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Query, SurrealQueryBuilder};
+
+    #[test]
+    fn test_fetch() {
+        let mut query = Query::select();
+        query.from("picture".to_string()).column("*".to_string());
+        assert!(!query.build(SurrealQueryBuilder).0.contains("FETCH"));
+
+        query.limit(10).fetch("tags".to_string()).fetch("categories".to_string());
+        let (sql, _) = query.build(SurrealQueryBuilder);
+        assert_eq!(sql, "SELECT * FROM type::table($tb) LIMIT $w1 FETCH tags, categories");
+
+        let taken = query.take();
+        assert!(taken.build(SurrealQueryBuilder).0.ends_with(" FETCH tags, categories"));
+        assert!(!query.build(SurrealQueryBuilder).0.contains("FETCH"));
+    }
+}
@@ -439,6 +439,7 @@ pub struct Alias(String);
 #[derive(Debug, Clone, PartialEq)]
 pub enum Keyword {
     Null,
+    None,
     CurrentDate,
     CurrentTime,
     CurrentTimestamp,
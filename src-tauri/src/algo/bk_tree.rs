@@ -0,0 +1,127 @@
+//! BK-tree over 64-bit hashes with the Hamming distance, for the "near" lookups of perceptual hashes.
+//!
+//! A child is stored under its distance to its parent, so a query only visits the children whose
+//! distance is within `max_distance` of its own distance to the node (triangle inequality).
+
+use std::collections::HashMap;
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+struct Node<T> {
+    hash: u64,
+    /// The values of the same hash
+    values: Vec<T>,
+    children: HashMap<u32, usize>,
+}
+
+pub struct BkTree<T> {
+    nodes: Vec<Node<T>>,
+}
+
+impl<T> Default for BkTree<T> {
+    fn default() -> Self {
+        Self { nodes: Vec::new() }
+    }
+}
+
+impl<T> BkTree<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, hash: u64, value: T) {
+        let new_node = |value| Node {
+            hash,
+            values: vec![value],
+            children: HashMap::new(),
+        };
+        if self.nodes.is_empty() {
+            self.nodes.push(new_node(value));
+            return;
+        }
+
+        let mut index = 0;
+        loop {
+            let distance = hamming_distance(self.nodes[index].hash, hash);
+            if distance == 0 {
+                self.nodes[index].values.push(value);
+                return;
+            }
+            match self.nodes[index].children.get(&distance) {
+                Some(&child) => index = child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(new_node(value));
+                    self.nodes[index].children.insert(distance, child);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Remove the values of the hash matching the predicate.
+    /// Note: The node stays in the tree (it routes to its children), without values it's never found.
+    pub fn remove(&mut self, hash: u64, mut matches: impl FnMut(&T) -> bool) {
+        let mut index = 0;
+        while index < self.nodes.len() {
+            let distance = hamming_distance(self.nodes[index].hash, hash);
+            if distance == 0 {
+                self.nodes[index].values.retain(|value| !matches(value));
+                return;
+            }
+            match self.nodes[index].children.get(&distance) {
+                Some(&child) => index = child,
+                None => return,
+            }
+        }
+    }
+
+    /// The values whose hash is within `max_distance` of `hash`, with their distance
+    pub fn find(&self, hash: u64, max_distance: u32) -> Vec<(&T, u32)> {
+        let mut found = Vec::new();
+        let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let distance = hamming_distance(node.hash, hash);
+            if distance <= max_distance {
+                found.extend(node.values.iter().map(|value| (value, distance)));
+            }
+
+            let range = distance.saturating_sub(max_distance)..=distance + max_distance;
+            stack.extend(node.children.iter().filter(|(d, _)| range.contains(d)).map(|(_, &child)| child));
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bk_tree_find() {
+        let mut tree = BkTree::new();
+        for (value, hash) in [("a", 0b0000u64), ("b", 0b0001), ("c", 0b0011), ("d", 0b1111), ("e", 0b0001)] {
+            tree.insert(hash, value);
+        }
+
+        let mut found: Vec<(&str, u32)> = tree.find(0b0000, 1).into_iter().map(|(v, d)| (*v, d)).collect();
+        found.sort();
+        assert_eq!(found, vec![("a", 0), ("b", 1), ("e", 1)]);
+
+        assert_eq!(tree.find(0b1111, 0).len(), 1);
+        assert_eq!(tree.find(0b0000, 64).len(), 5);
+        assert!(BkTree::<()>::new().find(0, 64).is_empty());
+
+        tree.remove(0b0001, |value| *value == "b");
+        let found: Vec<&str> = tree.find(0b0000, 1).into_iter().map(|(v, _)| *v).collect();
+        assert!(!found.contains(&"b") && found.contains(&"e"));
+        tree.remove(0b0000, |_| true);
+        assert_eq!(tree.find(0b0011, 0).len(), 1);
+        assert!(tree.find(0b0000, 0).is_empty());
+    }
+}
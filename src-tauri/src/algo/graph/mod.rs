@@ -3,6 +3,8 @@ use std::hash::Hash;
 
 mod topological_sort;
 
+pub use topological_sort::topological_sort;
+
 pub struct Graph<VId, E = (), V = ()> {
     vertices: HashMap<VId, V>,
     adjacency: HashMap<VId, Vec<(VId, E)>>
@@ -58,7 +58,31 @@ impl<T> State<T>
     }
 }
 
-pub fn topological_sort<T, Id>(deps: T) -> Result<Vec<Id>, Box<dyn std::error::Error>>
+/// Edges `(dependency, dependent)`, e.g. `(parent, child)` for a tree
+impl<T> From<&[(T, T)]> for State<T>
+    where T: Eq + Hash + Copy
+{
+    fn from(edges: &[(T, T)]) -> Self {
+        let mut depends_on = GraphStruct::new();
+        let mut dependents = GraphStruct::new();
+        let mut nodes = HashSet::new();
+
+        for &(dependency, dependent) in edges {
+            add_edge(&mut depends_on, dependent, dependency);
+            add_edge(&mut dependents, dependency, dependent);
+            nodes.insert(dependency);
+            nodes.insert(dependent);
+        }
+
+        let no_deps = nodes.into_iter().filter(|node| !depends_on.contains_key(node)).collect();
+
+        State { depends_on, dependents, no_deps }
+    }
+}
+
+/// Returns the nodes sorted so every node comes after its dependencies.
+/// Fails with the unresolved nodes (the nodes of a cycle, or depending on one).
+pub fn topological_sort<T, Id>(deps: T) -> Result<Vec<Id>, Vec<Id>>
     where Id: Eq + Hash + Copy,
           State<Id>: From<T>
 {
@@ -77,6 +101,32 @@ pub fn topological_sort<T, Id>(deps: T) -> Result<Vec<Id>, Box<dyn std::error::E
 
     match state.is_resolved() {
         true => Ok(res),
-        false => Err(Box::from("The graph topology cannot be constructed")),
+        false => Err(state.unresolved().copied().collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_a_tree() {
+        let edges = [("root", "a"), ("a", "b"), ("root", "c")];
+        let sorted = topological_sort(&edges[..]).unwrap();
+
+        let position = |node| sorted.iter().position(|n| *n == node).unwrap();
+        assert_eq!(sorted.len(), 4);
+        assert!(position("root") < position("a"));
+        assert!(position("a") < position("b"));
+        assert!(position("root") < position("c"));
+    }
+
+    #[test]
+    fn reports_the_cycles() {
+        let edges = [("root", "a"), ("a", "b"), ("b", "c"), ("c", "a"), ("c", "d")];
+        let mut unresolved = topological_sort(&edges[..]).unwrap_err();
+        unresolved.sort();
+
+        assert_eq!(unresolved, vec!["a", "b", "c", "d"]);
     }
 }
@@ -1,5 +1,7 @@
 mod algo;
 mod matrix;
-mod graph;
+pub mod bk_tree;
+pub mod graph;
+pub mod lru;
 
 pub use self::algo::*;
\ No newline at end of file
@@ -1,5 +1,6 @@
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
+use crate::event::HubSubscriptionsMux;
 use crate::fs::{FsState, FsStateMux};
 use crate::model::{ModelStore, ModelStoreState};
 use crate::settings::{AppSettings, AppSettingsState};
@@ -11,6 +12,7 @@ pub struct ApplicationContext {
     pub settings: AppSettingsState,
     pub store: ModelStoreState,
     pub fs_state: FsStateMux,
+    pub subscriptions: HubSubscriptionsMux,
 }
 
 impl ApplicationContext {
@@ -22,7 +24,8 @@ impl ApplicationContext {
         Self {
             settings,
             store,
-            fs_state
+            fs_state,
+            subscriptions: HubSubscriptionsMux::default(),
         }
     }
 
@@ -24,3 +24,13 @@ pub enum Error {
     #[error("{0}")]
     Other(String)
 }
+
+impl Error {
+    /// Stable code of the error kinds the client has to handle (sent with the `IpcError`)
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            Error::Model(err) => err.code(),
+            _ => None,
+        }
+    }
+}
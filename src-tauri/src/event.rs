@@ -1,9 +1,12 @@
 //! Event layer of the backend.
 //!
 //! Right now, very simple, just a HubEvent data type.
+//! The windows receive the "Live" hub events only for the topics they subscribed to (see `HubSubscriptions`).
 //!
 
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use ts_gen::TS;
 
 #[derive(Serialize, TS, Clone)]
@@ -14,3 +17,34 @@ pub struct HubEvent<D: Serialize + Clone> {
     pub label: Option<String>,
     pub data: Option<D>,
 }
+
+/// Labels of the windows subscribed to each topic
+#[derive(Default)]
+pub struct HubSubscriptions {
+    topics: HashMap<String, HashSet<String>>,
+}
+
+pub type HubSubscriptionsMux = Arc<Mutex<HubSubscriptions>>;
+
+impl HubSubscriptions {
+    /// Returns false if the window was already subscribed
+    pub fn subscribe(&mut self, topic: &str, window: &str) -> bool {
+        self.topics.entry(topic.to_string()).or_default().insert(window.to_string())
+    }
+
+    /// Returns false if the window wasn't subscribed
+    pub fn unsubscribe(&mut self, topic: &str, window: &str) -> bool {
+        self.topics.get_mut(topic).map_or(false, |windows| windows.remove(window))
+    }
+
+    /// Drop all the subscriptions of a closed window
+    pub fn remove_window(&mut self, window: &str) {
+        self.topics.values_mut().for_each(|windows| {
+            windows.remove(window);
+        });
+    }
+
+    pub fn windows(&self, topic: &str) -> Vec<String> {
+        self.topics.get(topic).map(|windows| windows.iter().cloned().collect()).unwrap_or_default()
+    }
+}
@@ -25,6 +25,25 @@ pub fn get_user_path() -> PathBuf {
     PathBuf::from(format!("{}/userdata", path_to_string(&get_app_data_path())))
 }
 
+/// Directory of the picture files owned by the vault (e.g. imported from a vault archive)
+pub fn get_pictures_path() -> PathBuf {
+    PathBuf::from(format!("{}/pictures", path_to_string(&get_user_path())))
+}
+
+/// Directory of the media library, the picture files stored by content (see `media`)
+pub fn get_media_path() -> PathBuf {
+    PathBuf::from(format!("{}/media", path_to_string(&get_user_path())))
+}
+
+pub fn get_thumbnails_path() -> PathBuf {
+    PathBuf::from(format!("{}/thumbnails", path_to_string(&get_media_path())))
+}
+
+/// Cached renders of the edited pictures (see `model::picture_edit`)
+pub fn get_renders_path() -> PathBuf {
+    PathBuf::from(format!("{}/renders", path_to_string(&get_media_path())))
+}
+
 pub fn get_settings_path() -> PathBuf {
     PathBuf::from(format!(
         "{}/app_settings.json",
@@ -33,6 +52,24 @@ pub fn get_settings_path() -> PathBuf {
 }
 //#endregion -------- /Paths --------
 
+/// Walk the directory, the directories are listed before their content
+pub fn list_files_recursively(path: &str, include_dirs: bool) -> Vec<String> {
+    let mut result: Vec<String> = vec![];
+
+    for entry in WalkDir::new(path)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let file_path = entry.path().display().to_string();
+        if !include_dirs && entry.file_type().is_dir() {
+            continue;
+        }
+        result.push(file_path);
+    }
+    result
+}
+
 //#region -------- Main --------
 pub fn init_workspace() {
     if !get_app_data_path().exists() {
@@ -111,6 +148,7 @@ pub fn read_file(filename: String) -> IpcResponse<String> {
         Err(err) => IpcResponse {
             error: Some(IpcError {
                 message: format!("{err}"),
+                code: None,
             }),
             result: None,
         },
@@ -119,19 +157,6 @@ pub fn read_file(filename: String) -> IpcResponse<String> {
 
 #[tauri::command]
 pub fn get_files_recursively(path: String, include_dirs: bool) -> IpcResponse<Vec<String>> {
-    let mut result: Vec<String> = vec![];
-
-    for entry in WalkDir::new(path)
-        .follow_links(true)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let file_path = entry.path().display().to_string();
-        if !include_dirs && entry.file_type().is_dir() {
-            continue;
-        }
-        result.push(file_path);
-    }
-    Ok(result).into()
+    Ok(list_files_recursively(&path, include_dirs)).into()
 }
 //endregion -------- /Commands --------
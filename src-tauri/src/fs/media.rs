@@ -0,0 +1,228 @@
+//! Media library: the picture files owned by the vault, stored by content.
+//!
+//! Every imported file is copied to `get_media_path()` as `<sha256>.<ext>`, so identical files are stored once
+//! and a picture doesn't break when the user moves the original file.
+//! The thumbnails are PNG files of `get_thumbnails_path()`, named `<sha256>_<size>.png`.
+//!
+//! Notes:
+//!     - The extension comes from the content (not from the file name), unknown formats are rejected.
+//!     - The files are deleted when the last record with the content is purged from the trash (`remove_media`),
+//!       never when a record is trashed.
+//!     - `dhash` is a perceptual hash: close pictures (resized, recompressed...) have close hashes.
+
+use super::fs::{get_media_path, get_renders_path, get_thumbnails_path};
+use super::utils::path_to_string;
+use crate::{Error, Result};
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine as _;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// Max width/height of the generated thumbnails
+pub const THUMBNAIL_SIZES: [u32; 3] = [128, 256, 512];
+
+/// A file of the media library
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaFile {
+    /// SHA-256 of the content, lowercase hex
+    pub hash: String,
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    pub dhash: u64,
+    /// EXIF fields by tag name (e.g. `DateTimeOriginal`), empty if the file has none
+    pub exif: BTreeMap<String, String>,
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn media_file_path(hash: &str, extension: &str) -> PathBuf {
+    get_media_path().join(format!("{hash}.{extension}"))
+}
+
+pub fn thumbnail_path(hash: &str, size: u32) -> PathBuf {
+    get_thumbnails_path().join(format!("{hash}_{size}.png"))
+}
+
+/// The stored file of the content hash, whatever its extension
+pub fn find_media_file(hash: &str) -> Option<PathBuf> {
+    let entries = fs::read_dir(get_media_path()).ok()?;
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.is_file() && path.file_stem().map_or(false, |stem| stem == hash))
+}
+
+/// The file is the stored file, a thumbnail or a render of the content hash
+fn is_media_file_of(file_name: &str, hash: &str) -> bool {
+    file_name
+        .strip_prefix(hash)
+        .map_or(false, |rest| rest.starts_with('.') || rest.starts_with('_'))
+}
+
+/// Remove the stored file of the content hash, with its thumbnails and renders.
+/// Note: The caller checks that no record has the content anymore, the failures are only logged.
+pub fn remove_media(hash: &str) {
+    for dir in [get_media_path(), get_thumbnails_path(), get_renders_path()] {
+        let entries = fs::read_dir(dir).into_iter().flatten().filter_map(|entry| entry.ok());
+        for entry in entries.filter(|entry| is_media_file_of(&entry.file_name().to_string_lossy(), hash)) {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            if let Err(ex) = fs::remove_file(&path) {
+                warn!("Failed to remove the media file {}: {ex}", path.display());
+            }
+        }
+    }
+}
+
+/// Difference hash: the 64 horizontal gradients (darker/lighter) of a 9x8 grayscale version of the picture
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+pub fn dhash_file<P: AsRef<Path>>(path: P) -> Result<u64> {
+    let image = image::open(path.as_ref()).map_err(|ex| Error::Other(format!("{}: {ex}", path.as_ref().display())))?;
+    Ok(dhash(&image))
+}
+
+pub fn dhash_to_string(dhash: u64) -> String {
+    format!("{dhash:016x}")
+}
+
+pub fn dhash_from_string(dhash: &str) -> Option<u64> {
+    u64::from_str_radix(dhash, 16).ok()
+}
+
+fn read_exif(data: &[u8]) -> BTreeMap<String, String> {
+    let Ok(exif) = exif::Reader::new().read_from_container(&mut Cursor::new(data)) else {
+        return BTreeMap::new();
+    };
+
+    exif.fields()
+        .filter(|field| field.ifd_num == exif::In::PRIMARY)
+        .map(|field| (field.tag.to_string(), field.display_value().with_unit(&exif).to_string()))
+        .collect()
+}
+
+/// Copy the content to the media library (if it's not there yet) and generate the missing thumbnails
+pub fn store_media(data: &[u8]) -> Result<MediaFile> {
+    let format = image::guess_format(data).map_err(|ex| Error::Other(format!("Unsupported picture: {ex}")))?;
+    let extension = format.extensions_str().first().copied().unwrap_or("bin");
+    let image = image::load_from_memory_with_format(data, format)
+        .map_err(|ex| Error::Other(format!("Invalid picture: {ex}")))?;
+
+    let hash = sha256_hex(data);
+    let path = media_file_path(&hash, extension);
+    if !path.is_file() {
+        fs::create_dir_all(get_media_path())?;
+        fs::write(&path, data)?;
+    }
+
+    fs::create_dir_all(get_thumbnails_path())?;
+    for size in THUMBNAIL_SIZES {
+        let thumbnail = thumbnail_path(&hash, size);
+        if !thumbnail.is_file() {
+            image
+                .thumbnail(size, size)
+                .save_with_format(&thumbnail, ImageFormat::Png)
+                .map_err(|ex| Error::Other(format!("{}: {ex}", thumbnail.display())))?;
+        }
+    }
+
+    let (width, height) = image.dimensions();
+    Ok(MediaFile {
+        hash,
+        path: path_to_string(&path),
+        width,
+        height,
+        dhash: dhash(&image),
+        exif: read_exif(data),
+    })
+}
+
+pub fn import_media_file<P: AsRef<Path>>(path: P) -> Result<MediaFile> {
+    let data = fs::read(path.as_ref())?;
+    store_media(&data)
+}
+
+/// Decode a base64 data URL (e.g. pasted from the clipboard), padded or not
+pub fn decode_data_url(data_url: &str) -> Result<Vec<u8>> {
+    let invalid = || Error::Other("Invalid data URL, a base64 `data:image/...` URL is expected".to_string());
+
+    let (header, data) = data_url.strip_prefix("data:").and_then(|url| url.split_once(',')).ok_or_else(invalid)?;
+    if !header.starts_with("image/") || !header.ends_with(";base64") {
+        return Err(invalid());
+    }
+
+    let engine = GeneralPurpose::new(
+        &alphabet::STANDARD,
+        GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+    );
+    engine.decode(data.trim()).map_err(|ex| Error::Other(format!("Invalid data URL: {ex}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::img_to_data_url;
+
+    #[test]
+    fn test_decode_data_url() {
+        let data = b"\x89PNG\r\n\x1a\n-- some bytes";
+        assert_eq!(decode_data_url(&img_to_data_url(data, "png")).unwrap(), data);
+        assert!(decode_data_url("data:text/plain;base64,aGVsbG8=").is_err());
+        assert!(decode_data_url("/home/user/picture.png").is_err());
+    }
+
+    #[test]
+    fn test_dhash() {
+        let gradient = |rising: bool| {
+            DynamicImage::ImageLuma8(image::GrayImage::from_fn(90, 80, |x, _| {
+                image::Luma([if rising { x as u8 * 2 } else { 255 - x as u8 * 2 }])
+            }))
+        };
+
+        assert_eq!(dhash(&gradient(true)), u64::MAX);
+        assert_eq!(dhash(&gradient(false)), 0);
+        assert_eq!(dhash_from_string(&dhash_to_string(0x0f00_ff00_0000_00aa)), Some(0x0f00_ff00_0000_00aa));
+    }
+
+    #[test]
+    fn test_is_media_file_of() {
+        assert!(is_media_file_of("abc.png", "abc"));
+        assert!(is_media_file_of("abc_256.png", "abc"));
+        assert!(is_media_file_of("abc_0123456789abcdef_128.png", "abc"));
+        assert!(!is_media_file_of("abcd.png", "abc"));
+        assert!(!is_media_file_of("xabc.png", "abc"));
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
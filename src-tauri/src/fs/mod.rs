@@ -1,5 +1,6 @@
 mod fs;
 mod utils;
+pub mod media;
 pub mod scope;
 
 pub use fs::*;
@@ -9,11 +10,18 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use crate::utils::LabelValue;
+use notify_debouncer_full::notify::RecommendedWatcher;
+use notify_debouncer_full::{Debouncer, FileIdMap};
 
 pub const IMAGE_EXTENSIONS: [&str; 10] = ["bmp", "gif", "ico", "jpeg", "jpg", "png", "svg", "tga", "tiff", "webp"];
 pub const DOCUMENTS_EXTENSIONS: [&str; 1] = ["jdoc"];
 
+pub type PictureWatcher = Debouncer<RecommendedWatcher, FileIdMap>;
+
 #[derive(Default)]
-pub struct FsState {}
+pub struct FsState {
+    /// Watcher of the `PicturesSettings::watched_dirs`, dropped to stop watching
+    pub picture_watcher: Option<PictureWatcher>,
+}
 
 pub type FsStateMux = Arc<Mutex<FsState>>;
@@ -15,6 +15,42 @@ pub fn string_to_path(path: &String) -> PathBuf {
     PathBuf::from(path)
 }
 
+/// Path under `dir` that doesn't exist yet, based on the file name (e.g. `name (1).ext`)
+pub fn free_file_path(dir: &Path, file_name: &str) -> PathBuf {
+    let path = dir.join(file_name);
+    if !path.exists() {
+        return path;
+    }
+
+    let (stem, ext) = match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{ext}")),
+        _ => (file_name, String::new()),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{stem} ({n}){ext}")))
+        .find(|path| !path.exists())
+        .unwrap_or(path)
+}
+
+/// Replace the characters that are not allowed in file names on any platform
+pub fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .collect();
+    let sanitized = sanitized.trim().trim_end_matches('.').trim();
+
+    if sanitized.is_empty() {
+        "untitled".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
 pub fn img_to_data_url<T, S>(input: T, extension: S) -> String
     where T: AsRef<[u8]>,
           S: Into<String> + Display,
@@ -52,6 +88,7 @@ pub fn is_exists(path: String) -> IpcResponse<bool> {
         Err(err) => IpcResponse {
             error: Some(IpcError {
                 message: format!("{err}"),
+                code: None,
             }),
             result: None,
         },
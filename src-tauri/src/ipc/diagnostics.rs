@@ -0,0 +1,14 @@
+use super::{into_response, IpcResponse};
+use crate::model::{EntityCacheBmc, EntityCacheStats};
+use crate::Error;
+use crate::model::Error as ModelError;
+use tauri::{command, AppHandle, Wry};
+use crate::model::ctx::Ctx;
+
+#[command]
+pub fn get_entity_cache_stats(app: AppHandle<Wry>) -> IpcResponse<Vec<EntityCacheStats>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(EntityCacheBmc::stats(ctx)),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
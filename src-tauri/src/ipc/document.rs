@@ -1,7 +1,8 @@
 use serde_json::Value;
 use surreal_qb::filter::ListOptions;
 use super::{CreateParams, DeleteParams, GetParams, into_response, IpcResponse, ListParams, UpdateParams};
-use crate::model::{Document, DocumentBmc, DocumentForCreate, DocumentForUpdate, DocumentsFolderBmc, DocumentsFolderTree};
+use crate::model::{Document, DocumentBmc, DocumentBody, DocumentType, DocumentForCreate, DocumentForUpdate, DocumentsBulkUpdate, DocumentSummary, DocumentsFolderBmc, DocumentsFolderTree, DocumentLinkBmc, DocumentRevision, DocumentRevisionBmc, LinkedDocument, OutgoingLinks, Page, SearchHit};
+use crate::utils::LineChange;
 use crate::Error;
 use crate::model::Error as ModelError;
 use tauri::{command, AppHandle, Wry};
@@ -31,6 +32,14 @@ pub async fn update_document(app: AppHandle<Wry>, params: UpdateParams<DocumentF
     }
 }
 
+#[command]
+pub async fn bulk_update_documents(app: AppHandle<Wry>, ids: Vec<String>, data: DocumentsBulkUpdate) -> IpcResponse<Vec<Document>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(DocumentBmc::bulk_update(ctx, ids, data).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
 #[command]
 pub async fn delete_document(app: AppHandle<Wry>, params: DeleteParams) -> IpcResponse<Document> {
     match Ctx::from_app(app) {
@@ -40,7 +49,7 @@ pub async fn delete_document(app: AppHandle<Wry>, params: DeleteParams) -> IpcRe
 }
 
 #[command]
-pub async fn list_documents(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Vec<Document>> {
+pub async fn list_documents(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Page<Document>> {
     match Ctx::from_app(app) {
         Ok(ctx) => {
             match filter.map(serde_json::from_value).transpose() {
@@ -54,6 +63,19 @@ pub async fn list_documents(app: AppHandle<Wry>, filter: Option<Value>, list_opt
     }
 }
 
+#[command]
+pub async fn list_document_summaries(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Page<DocumentSummary>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => {
+            match filter.map(serde_json::from_value).transpose() {
+                Ok(filter) => into_response(DocumentBmc::list_summaries(ctx, filter, list_options).await),
+                Err(err) => Err(Error::JsonSerde(err)).into(),
+            }
+        },
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
 #[command]
 pub async fn create_untitled_document(app: AppHandle<Wry>) -> IpcResponse<Document> {
     match Ctx::from_app(app) {
@@ -61,3 +83,78 @@ pub async fn create_untitled_document(app: AppHandle<Wry>) -> IpcResponse<Docume
         Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
     }
 }
+
+#[command]
+pub async fn search_documents(app: AppHandle<Wry>, query: String, limit: Option<i64>) -> IpcResponse<Vec<SearchHit>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(DocumentBmc::search(ctx, query.as_str(), limit).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn get_backlinks(app: AppHandle<Wry>, id: String) -> IpcResponse<Vec<LinkedDocument>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(DocumentLinkBmc::get_backlinks(ctx, id.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn get_outgoing_links(app: AppHandle<Wry>, id: String) -> IpcResponse<OutgoingLinks> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(DocumentLinkBmc::get_outgoing_links(ctx, id.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn list_document_revisions(app: AppHandle<Wry>, id: String) -> IpcResponse<Vec<DocumentRevision>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(DocumentRevisionBmc::list(ctx, id.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn get_document_revision(app: AppHandle<Wry>, params: GetParams) -> IpcResponse<DocumentRevision> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(DocumentRevisionBmc::get(ctx, &params.id).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn diff_document_revisions(app: AppHandle<Wry>, from_id: String, to_id: Option<String>) -> IpcResponse<Vec<LineChange>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => {
+            let to_id = to_id.as_ref().map(String::as_str);
+            into_response(DocumentRevisionBmc::diff(ctx, from_id.as_str(), to_id).await)
+        },
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn restore_document_revision(app: AppHandle<Wry>, id: String) -> IpcResponse<Document> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(DocumentRevisionBmc::restore(ctx, id.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn get_document_body(app: AppHandle<Wry>, id: String) -> IpcResponse<DocumentBody> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(DocumentBmc::get_body(ctx, id.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn convert_document(app: AppHandle<Wry>, id: String, to: DocumentType) -> IpcResponse<Document> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(DocumentBmc::convert(ctx, id.as_str(), to).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
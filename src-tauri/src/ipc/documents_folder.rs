@@ -3,7 +3,7 @@ use surreal_qb::filter::ListOptions;
 use tauri::{AppHandle, command, Wry};
 
 use crate::Error;
-use crate::model::{DocumentsFolder, DocumentsFolderBmc, DocumentsFolderForCreate, DocumentsFolderForUpdate, DocumentsFolderTree};
+use crate::model::{DocumentsFolder, DocumentsFolderBmc, DocumentsFolderChild, DocumentsFolderForCreate, DocumentsFolderForUpdate, DocumentsFolderTree, MarkdownBmc, MarkdownExportReport, MarkdownImportReport, Page, TreeDelta};
 use crate::model::ctx::Ctx;
 use crate::model::Error as ModelError;
 
@@ -42,7 +42,7 @@ pub async fn delete_documents_folder(app: AppHandle<Wry>, params: DeleteParams)
 }
 
 #[command]
-pub async fn list_documents_folders(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Vec<DocumentsFolder>> {
+pub async fn list_documents_folders(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Page<DocumentsFolder>> {
     match Ctx::from_app(app) {
         Ok(ctx) => {
             match filter.map(serde_json::from_value).transpose() {
@@ -65,7 +65,7 @@ pub async fn create_unnamed_folder(app: AppHandle<Wry>) -> IpcResponse<Documents
 }
 
 #[command]
-pub async fn add_folder_or_document(app: AppHandle<Wry>, id: String, sub_id: String) -> IpcResponse<DocumentsFolderTree> {
+pub async fn add_folder_or_document(app: AppHandle<Wry>, id: String, sub_id: String) -> IpcResponse<TreeDelta<DocumentsFolderChild>> {
     match Ctx::from_app(app) {
         Ok(ctx) => into_response(DocumentsFolderBmc::attach_folder_or_document(ctx, id.as_str(), sub_id.as_str()).await),
         Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
@@ -73,7 +73,7 @@ pub async fn add_folder_or_document(app: AppHandle<Wry>, id: String, sub_id: Str
 }
 
 #[command]
-pub async fn remove_folder_or_document(app: AppHandle<Wry>, id: String, sub_id: String) -> IpcResponse<DocumentsFolderTree> {
+pub async fn remove_folder_or_document(app: AppHandle<Wry>, id: String, sub_id: String) -> IpcResponse<TreeDelta<DocumentsFolderChild>> {
     match Ctx::from_app(app) {
         Ok(ctx) => into_response(DocumentsFolderBmc::detach_folder_or_document(ctx, id.as_str(), sub_id.as_str()).await),
         Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
@@ -81,7 +81,7 @@ pub async fn remove_folder_or_document(app: AppHandle<Wry>, id: String, sub_id:
 }
 
 #[command]
-pub async fn move_folder_or_document(app: AppHandle<Wry>, id: String, from_id: Option<String>, to_id: Option<String>) -> IpcResponse<DocumentsFolderTree> {
+pub async fn move_folder_or_document(app: AppHandle<Wry>, id: String, from_id: Option<String>, to_id: Option<String>) -> IpcResponse<TreeDelta<DocumentsFolderChild>> {
     match Ctx::from_app(app) {
         Ok(ctx) => {
             let from_id = from_id.as_ref().map(String::as_str);
@@ -93,9 +93,36 @@ pub async fn move_folder_or_document(app: AppHandle<Wry>, id: String, from_id: O
 }
 
 #[command]
-pub async fn list_folders_tree(app: AppHandle<Wry>) -> IpcResponse<DocumentsFolderTree> {
+pub async fn bulk_move_folder_or_document(app: AppHandle<Wry>, ids: Vec<String>, to_id: Option<String>) -> IpcResponse<Vec<TreeDelta<DocumentsFolderChild>>> {
     match Ctx::from_app(app) {
+        Ok(ctx) => {
+            let to_id = to_id.as_ref().map(String::as_str);
+            into_response(DocumentsFolderBmc::bulk_move_folder_or_document(ctx, ids, to_id).await)
+        },
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn list_folders_tree(app: AppHandle<Wry>, summaries: Option<bool>) -> IpcResponse<DocumentsFolderTree> {
+    match Ctx::from_app(app) {
+        Ok(ctx) if summaries.unwrap_or(false) => into_response(DocumentsFolderBmc::list_summary_tree(ctx).await),
         Ok(ctx) => into_response(DocumentsFolderBmc::list_tree(ctx).await),
         Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
     }
-}
\ No newline at end of file
+}
+#[command]
+pub async fn import_markdown_folder(app: AppHandle<Wry>, path: String) -> IpcResponse<MarkdownImportReport> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(MarkdownBmc::import_folder(ctx, path.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn export_markdown_folder(app: AppHandle<Wry>, path: String) -> IpcResponse<MarkdownExportReport> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(MarkdownBmc::export_folder(ctx, path.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
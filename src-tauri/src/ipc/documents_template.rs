@@ -2,10 +2,12 @@ use super::{into_response, CreateParams, DeleteParams, GetParams, IpcResponse, U
 use crate::model::ctx::Ctx;
 use crate::model::Error as ModelError;
 use crate::model::{
-    DocumentsTemplate, DocumentsTemplateBmc, DocumentsTemplateForCreate, DocumentsTemplateForUpdate,
+    Document, DocumentsTemplate, DocumentsTemplateBmc, DocumentsTemplateForCreate,
+    DocumentsTemplateForUpdate, Page, TemplateMigrationReport,
 };
 use crate::Error;
 use serde_json::Value;
+use std::collections::BTreeMap;
 use surreal_qb::filter::ListOptions;
 use tauri::{command, AppHandle, Wry};
 
@@ -58,7 +60,7 @@ pub async fn list_documents_templates(
     app: AppHandle<Wry>,
     filter: Option<Value>,
     list_options: Option<ListOptions>,
-) -> IpcResponse<Vec<DocumentsTemplate>> {
+) -> IpcResponse<Page<DocumentsTemplate>> {
     match Ctx::from_app(app) {
         Ok(ctx) => match filter.map(serde_json::from_value).transpose() {
             Ok(filter) => {
@@ -69,3 +71,30 @@ pub async fn list_documents_templates(
         Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
     }
 }
+
+#[command]
+pub async fn create_document_from_template(
+    app: AppHandle<Wry>,
+    id: String,
+    title: String,
+    folder: Option<String>,
+    values: BTreeMap<String, Value>,
+) -> IpcResponse<Document> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(
+            DocumentsTemplateBmc::create_document(ctx, id.as_str(), title, folder, values).await,
+        ),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn migrate_template_instances(
+    app: AppHandle<Wry>,
+    id: String,
+) -> IpcResponse<TemplateMigrationReport> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(DocumentsTemplateBmc::migrate_instances(ctx, id.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
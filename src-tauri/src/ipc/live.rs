@@ -0,0 +1,23 @@
+use super::{into_response, IpcResponse};
+use crate::model::ctx::Ctx;
+use crate::model::Error as ModelError;
+use crate::model::LiveBmc;
+use crate::Error;
+use tauri::{command, AppHandle, Window, Wry};
+
+/// Receive the "Live" hub events of the topic (a table name) in the calling window
+#[command]
+pub async fn subscribe_topic(app: AppHandle<Wry>, window: Window<Wry>, topic: String) -> IpcResponse<bool> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(LiveBmc::subscribe(ctx, window.label(), topic.as_str())),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn unsubscribe_topic(app: AppHandle<Wry>, window: Window<Wry>, topic: String) -> IpcResponse<bool> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(LiveBmc::unsubscribe(ctx, window.label(), topic.as_str())),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
@@ -0,0 +1,14 @@
+use super::{into_response, IpcResponse};
+use crate::model::{MigrationBmc, MigrationReport};
+use crate::Error;
+use crate::model::Error as ModelError;
+use tauri::{command, AppHandle, Wry};
+use crate::model::ctx::Ctx;
+
+#[command]
+pub async fn run_migrations(app: AppHandle<Wry>, dry_run: bool) -> IpcResponse<MigrationReport> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(MigrationBmc::migrate(ctx, dry_run).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
@@ -1,25 +1,37 @@
+mod diagnostics;
 mod document;
 mod documents_folder;
 mod documents_template;
+mod live;
+mod migration;
 mod params;
 mod picture;
 mod response;
 mod settings;
 mod tags_and_categories;
+mod trash;
+mod tree_repair;
+mod vault;
 
 use crate::prelude::f;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 // --- re-exports
+pub use diagnostics::*;
 pub use document::*;
 pub use documents_folder::*;
 pub use documents_template::*;
+pub use live::*;
+pub use migration::*;
 pub use params::*;
 pub use picture::*;
 pub use response::*;
 pub use settings::*;
 pub use tags_and_categories::*;
+pub use trash::*;
+pub use tree_repair::*;
+pub use vault::*;
 pub(crate) fn into_response<D>(result: crate::model::Result<D>) -> IpcResponse<D>
 where
     D: Serialize,
@@ -32,6 +44,7 @@ where
         Err(err) => IpcResponse {
             error: Some(IpcError {
                 message: f!("{err}"),
+                code: err.code().map(String::from),
             }),
             result: None,
         },
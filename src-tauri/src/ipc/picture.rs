@@ -1,18 +1,14 @@
 //! Tauri IPC commands to bridge Project Frontend Model Controller to Backend Model Controller
 //!
 
-use std::collections::HashMap;
 use serde_json::Value;
 use surreal_qb::filter::ListOptions;
-use super::{CreateParams, DeleteParams, GetParams, into_response, IpcResponse, ListParams, UpdateParams};
+use super::{CreateParams, DeleteParams, GetParams, into_response, IpcResponse, UpdateParams};
 use crate::model::ctx::Ctx;
-use crate::model::{Document, Picture, PictureBmc, PictureForCreate, PictureForUpdate, vmap};
+use crate::model::{DuplicatePictureGroup, Page, Picture, PictureBmc, PictureEdits, PictureForCreate, PictureForUpdate, PictureSummary, RelinkReport, SearchHit, SimilarPicture};
 use crate::Error;
 use crate::model::Error as ModelError;
 use tauri::{command, AppHandle, Wry};
-use walkdir::WalkDir;
-use crate::fs::{IMAGE_EXTENSIONS, read_file_as_data_url};
-use crate::error::Result as MainResult;
 
 #[command]
 pub async fn get_picture(app: AppHandle<Wry>, params: GetParams) -> IpcResponse<Picture> {
@@ -47,7 +43,7 @@ pub async fn delete_picture(app: AppHandle<Wry>, params: DeleteParams) -> IpcRes
 }
 
 #[command]
-pub async fn list_pictures(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Vec<Picture>> {
+pub async fn list_pictures(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Page<Picture>> {
     match Ctx::from_app(app) {
         Ok(ctx) => {
             match filter.map(serde_json::from_value).transpose() {
@@ -59,6 +55,19 @@ pub async fn list_pictures(app: AppHandle<Wry>, filter: Option<Value>, list_opti
     }
 }
 
+#[command]
+pub async fn list_picture_summaries(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Page<PictureSummary>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => {
+            match filter.map(serde_json::from_value).transpose() {
+                Ok(filter) => into_response(PictureBmc::list_summaries(ctx, filter, list_options).await),
+                Err(err) => Err(Error::JsonSerde(err)).into(),
+            }
+        },
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
 #[command]
 pub async fn get_document_used_in(app: AppHandle<Wry>, id: String) -> IpcResponse<Vec<String>> {
     match Ctx::from_app(app) {
@@ -76,7 +85,7 @@ pub async fn get_picture_with_url(app: AppHandle<Wry>, id: String) -> IpcRespons
 }
 
 #[command]
-pub async fn list_pictures_with_urls(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Vec<Picture>> {
+pub async fn list_pictures_with_urls(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Page<Picture>> {
     match Ctx::from_app(app) {
         Ok(ctx) => {
             match filter.map(serde_json::from_value).transpose() {
@@ -88,33 +97,58 @@ pub async fn list_pictures_with_urls(app: AppHandle<Wry>, filter: Option<Value>,
     }
 }
 
-// TODO: get tags & categories
-// #[command]
-// pub async fn collect_pictures_from_disk(app: AppHandle<Wry>, path: String) -> IpcResponse<Vec<Picture>> {
-//     let mut paths = Vec::<String>::new();
-//
-//     WalkDir::new(path)
-//         .into_iter()
-//         .filter_map(Result::ok) // TODO: handle entries that could not be read
-//         .for_each(|entry| {
-//             let file_path = entry.path().to_string_lossy().to_string();
-//
-//             if entry.file_type().is_file() {
-//                 let extension = entry.path().extension().unwrap().to_str().unwrap();
-//
-//                 if IMAGE_EXTENSIONS.contains(&extension) {
-//                     if (!paths.contains(&file_path)) {
-//                         paths.push(file_path);
-//                     }
-//                 }
-//             }
-//         });
-//
-//     let sql = "BEGIN; FOR $path IN $data { CREATE picture SET data_url = $path; }; SELECT * FROM picture; COMMIT;";
-//     let vars = vmap!("data".into() => paths.into());
-//
-//     match Ctx::from_app(app) {
-//         Ok(ctx) => into_response(PictureBmc::custom_multi_query(ctx, sql, Some(vars.into())).await),
-//         Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
-//     }
-// }
+#[command]
+pub async fn search_pictures(app: AppHandle<Wry>, query: String, limit: Option<i64>) -> IpcResponse<Vec<SearchHit>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(PictureBmc::search(ctx, query.as_str(), limit).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn import_picture_data_url(app: AppHandle<Wry>, data_url: String, name: Option<String>) -> IpcResponse<Picture> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(PictureBmc::import_data_url(ctx, &data_url, name).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn edit_picture(app: AppHandle<Wry>, id: String, edits: PictureEdits) -> IpcResponse<Picture> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(PictureBmc::edit(ctx, &id, edits).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn reset_picture_edits(app: AppHandle<Wry>, id: String) -> IpcResponse<Picture> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(PictureBmc::reset_edits(ctx, &id).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn find_similar_pictures(app: AppHandle<Wry>, id: String, max_distance: u32) -> IpcResponse<Vec<SimilarPicture>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(PictureBmc::find_similar(ctx, &id, max_distance).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn find_duplicate_picture_groups(app: AppHandle<Wry>, max_distance: Option<u32>) -> IpcResponse<Vec<DuplicatePictureGroup>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(PictureBmc::find_duplicate_groups(ctx, max_distance).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn relink_missing_pictures(app: AppHandle<Wry>, search_dirs: Vec<String>) -> IpcResponse<RelinkReport> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(PictureBmc::relink_missing_pictures(ctx, search_dirs).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
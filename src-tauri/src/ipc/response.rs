@@ -1,12 +1,17 @@
 use crate::prelude::f;
 use crate::Result;
 use serde::Serialize;
+use serde_with_macros::skip_serializing_none;
 use ts_gen::TS;
 
+#[skip_serializing_none]
 #[derive(TS, Serialize)]
 #[ts(export)]
 pub struct IpcError {
     pub message: String,
+    /// Set for the errors the client has to handle, e.g. `TitleConflict`
+    #[ts(optional)]
+    pub code: Option<String>,
 }
 
 #[derive(TS, Serialize)]
@@ -41,6 +46,7 @@ where
             Err(err) => IpcResponse {
                 error: Some(IpcError {
                     message: f!("{err}"),
+                    code: err.code().map(String::from),
                 }),
                 result: None,
             },
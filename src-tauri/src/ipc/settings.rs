@@ -1,6 +1,9 @@
 use serde_diff::{Apply, Diff};
+use tauri::{AppHandle, Wry};
 use crate::{AppSettings, Error, settings};
 use crate::ipc::IpcResponse;
+use crate::model::ctx::Ctx;
+use crate::model::{EntityCacheBmc, Error as ModelError, PictureWatchBmc};
 use crate::utils::apply_diff;
 
 #[tauri::command]
@@ -10,8 +13,10 @@ pub fn get_settings() -> IpcResponse<AppSettings> {
 }
 
 #[tauri::command]
-pub fn change_settings(new_settings: String) -> IpcResponse<()> {
+pub fn change_settings(app: AppHandle<Wry>, new_settings: String) -> IpcResponse<()> {
     let mut old = AppSettings::deserialize().unwrap_or_default();
+    let old_pictures = old.pictures.clone();
+    let old_cache = old.cache.clone();
 
     let new =  AppSettings::from(new_settings);
 
@@ -19,6 +24,24 @@ pub fn change_settings(new_settings: String) -> IpcResponse<()> {
     match apply_diff(&mut old, &new) {
         Ok(()) => {
             old.serialize();
+            if old.pictures != old_pictures {
+                let started = match Ctx::from_app(app.clone()) {
+                    Ok(ctx) => PictureWatchBmc::start(ctx),
+                    Err(_) => Err(ModelError::CtxFail),
+                };
+                if let Err(err) = started {
+                    return Err(Error::Model(err)).into();
+                }
+            }
+            if old.cache != old_cache {
+                let resized = match Ctx::from_app(app) {
+                    Ok(ctx) => EntityCacheBmc::resize(ctx),
+                    Err(_) => Err(ModelError::CtxFail),
+                };
+                if let Err(err) = resized {
+                    return Err(Error::Model(err)).into();
+                }
+            }
             Ok(()).into()
         },
         Err(err) => Err(Error::Other(err.to_string())).into()
@@ -1,7 +1,7 @@
 use serde_json::Value;
 use surreal_qb::filter::ListOptions;
 use super::{CreateParams, DeleteParams, GetParams, into_response, IpcResponse, ListParams, UpdateParams};
-use crate::model::{Category, CategoryBmc, CategoryForCreate, CategoryForUpdate, CategoriesTree, Tag, TagBmc, TagForCreate, TagForUpdate};
+use crate::model::{Category, CategoryBmc, CategoryForCreate, CategoryForUpdate, CategoriesTree, CategoryNode, Page, Tag, TagBmc, TagForCreate, TagForUpdate, TagWithUsage, TagsTree, TreeDelta};
 use crate::Error;
 use crate::model::Error as ModelError;
 use tauri::{command, AppHandle, Wry};
@@ -41,12 +41,12 @@ pub async fn delete_category(app: AppHandle<Wry>, params: DeleteParams) -> IpcRe
 }
 
 #[command]
-pub async fn list_categories(app: AppHandle<Wry>, filter: Option<Value>) -> IpcResponse<Vec<Category>> {
+pub async fn list_categories(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Page<Category>> {
     match Ctx::from_app(app) {
         Ok(ctx) => {
             match filter.map(serde_json::from_value).transpose() {
                 Ok(filter) => {
-                    into_response(CategoryBmc::list(ctx, filter).await)
+                    into_response(CategoryBmc::list(ctx, filter, list_options).await)
                 },
                 Err(err) => Err(Error::JsonSerde(err)).into(),
             }
@@ -64,7 +64,7 @@ pub async fn create_new_category(app: AppHandle<Wry>) -> IpcResponse<Category> {
 }
 
 #[command]
-pub async fn attach_subcategory(app: AppHandle<Wry>, id: String, sub_id: String) -> IpcResponse<CategoriesTree> {
+pub async fn attach_subcategory(app: AppHandle<Wry>, id: String, sub_id: String) -> IpcResponse<TreeDelta<CategoryNode>> {
     match Ctx::from_app(app) {
         Ok(ctx) => into_response(CategoryBmc::attach_subcategory(ctx, id.as_str(), sub_id.as_str()).await),
         Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
@@ -72,7 +72,7 @@ pub async fn attach_subcategory(app: AppHandle<Wry>, id: String, sub_id: String)
 }
 
 #[command]
-pub async fn detach_subcategory(app: AppHandle<Wry>, id: String, sub_id: String) -> IpcResponse<CategoriesTree> {
+pub async fn detach_subcategory(app: AppHandle<Wry>, id: String, sub_id: String) -> IpcResponse<TreeDelta<CategoryNode>> {
     match Ctx::from_app(app) {
         Ok(ctx) => into_response(CategoryBmc::detach_subcategory(ctx, id.as_str(), sub_id.as_str()).await),
         Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
@@ -80,7 +80,7 @@ pub async fn detach_subcategory(app: AppHandle<Wry>, id: String, sub_id: String)
 }
 
 #[command]
-pub async fn reattach_subcategory(app: AppHandle<Wry>, id: String, from_id: Option<String>, to_id: Option<String>) -> IpcResponse<CategoriesTree> {
+pub async fn reattach_subcategory(app: AppHandle<Wry>, id: String, from_id: Option<String>, to_id: Option<String>) -> IpcResponse<TreeDelta<CategoryNode>> {
     match Ctx::from_app(app) {
         Ok(ctx) => {
             let from_id = from_id.as_ref().map(String::as_str);
@@ -134,12 +134,12 @@ pub async fn delete_tag(app: AppHandle<Wry>, params: DeleteParams) -> IpcRespons
 }
 
 #[command]
-pub async fn list_tags(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Vec<Tag>> {
+pub async fn list_tags(app: AppHandle<Wry>, filter: Option<Value>, list_options: Option<ListOptions>) -> IpcResponse<Page<TagWithUsage>> {
     match Ctx::from_app(app) {
         Ok(ctx) => {
             match filter.map(serde_json::from_value).transpose() {
                 Ok(filter) => {
-                    into_response(TagBmc::list(ctx, filter, list_options).await)
+                    into_response(TagBmc::list_with_usage(ctx, filter, list_options).await)
                 },
                 Err(err) => Err(Error::JsonSerde(err)).into(),
             }
@@ -147,4 +147,48 @@ pub async fn list_tags(app: AppHandle<Wry>, filter: Option<Value>, list_options:
         Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
     }
 }
+
+#[command]
+pub async fn create_tag_path(app: AppHandle<Wry>, path: String) -> IpcResponse<Tag> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(TagBmc::create_path(ctx, path.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn reattach_subtag(app: AppHandle<Wry>, id: String, from_id: Option<String>, to_id: Option<String>) -> IpcResponse<TagsTree> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => {
+            let from_id = from_id.as_ref().map(String::as_str);
+            let to_id = to_id.as_ref().map(String::as_str);
+            into_response(TagBmc::reattach_subtag(ctx, id.as_str(), from_id, to_id).await)
+        },
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn list_tags_tree(app: AppHandle<Wry>) -> IpcResponse<TagsTree> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(TagBmc::list_tree(ctx).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn merge_tags(app: AppHandle<Wry>, ids: Vec<String>, into_id: String) -> IpcResponse<Tag> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(TagBmc::merge(ctx, ids, into_id.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn rename_tag(app: AppHandle<Wry>, id: String, name: String) -> IpcResponse<Tag> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(TagBmc::rename(ctx, id.as_str(), name.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
 //#endregion -------------------------------- Tags --------------------------------
@@ -0,0 +1,38 @@
+use super::{into_response, IpcResponse};
+use crate::model::{TrashBmc, TrashItem};
+use crate::Error;
+use crate::model::Error as ModelError;
+use tauri::{command, AppHandle, Wry};
+use crate::model::ctx::Ctx;
+
+#[command]
+pub async fn bulk_delete(app: AppHandle<Wry>, ids: Vec<String>) -> IpcResponse<Vec<String>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(TrashBmc::trash_many(ctx, ids).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn list_trash(app: AppHandle<Wry>) -> IpcResponse<Vec<TrashItem>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(TrashBmc::list(ctx).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn restore_from_trash(app: AppHandle<Wry>, id: String) -> IpcResponse<TrashItem> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(TrashBmc::restore(ctx, id.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn empty_trash(app: AppHandle<Wry>) -> IpcResponse<Vec<TrashItem>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(TrashBmc::empty(ctx).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
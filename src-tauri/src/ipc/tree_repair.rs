@@ -0,0 +1,14 @@
+use super::{into_response, IpcResponse};
+use crate::model::{TreeRepair, TreeRepairBmc};
+use crate::Error;
+use crate::model::Error as ModelError;
+use tauri::{command, AppHandle, Wry};
+use crate::model::ctx::Ctx;
+
+#[command]
+pub async fn repair_trees(app: AppHandle<Wry>, dry_run: bool) -> IpcResponse<Vec<TreeRepair>> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(TreeRepairBmc::repair(ctx, dry_run).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
@@ -0,0 +1,22 @@
+use super::{into_response, IpcResponse};
+use crate::model::{VaultBmc, VaultImportMode, VaultImportReport, VaultManifest};
+use crate::Error;
+use crate::model::Error as ModelError;
+use tauri::{command, AppHandle, Wry};
+use crate::model::ctx::Ctx;
+
+#[command]
+pub async fn export_vault(app: AppHandle<Wry>, path: String) -> IpcResponse<VaultManifest> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(VaultBmc::export(ctx, path.as_str()).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
+
+#[command]
+pub async fn import_vault(app: AppHandle<Wry>, path: String, mode: VaultImportMode) -> IpcResponse<VaultImportReport> {
+    match Ctx::from_app(app) {
+        Ok(ctx) => into_response(VaultBmc::import(ctx, path.as_str(), mode).await),
+        Err(_) => Err(Error::Model(ModelError::CtxFail)).into(),
+    }
+}
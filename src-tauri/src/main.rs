@@ -13,7 +13,7 @@ use std::sync::Arc;
 
 use serde::Serialize;
 // Manager is used by .get_window
-use tauri::{self, Manager};
+use tauri::{self, AppHandle, Manager, WindowEvent, Wry};
 use tauri_plugin_store;
 use tauri_plugin_window_state;
 use ts_gen::TS;
@@ -25,7 +25,7 @@ use tauri_plugins::window_state_manager_plugin::{
     AppHandleExt, Builder as WindowPluginBuilder, StateFlags,
 };
 
-use crate::context::ApplicationContext;
+use crate::context::{get_context, ApplicationContext};
 use crate::tray::{create_tray, create_tray_event, setup_tray_state};
 
 mod algo;
@@ -38,6 +38,7 @@ mod logger;
 mod macros;
 mod model;
 mod prelude;
+mod protocol;
 mod settings;
 mod tauri_plugins;
 mod tray;
@@ -50,6 +51,53 @@ struct SingleInstancePayload {
     cwd: String,
 }
 
+/// Push the store changes to the subscribed windows, in the background
+fn start_live_queries(app: AppHandle<Wry>) {
+    match model::ctx::Ctx::from_app(app) {
+        Ok(ctx) => model::LiveBmc::start(ctx),
+        Err(err) => error!("Failed to start the live queries: {err}"),
+    }
+}
+
+/// Import the watched picture folders and keep them in sync, in the background
+fn watch_picture_folders(app: AppHandle<Wry>) {
+    let started = match model::ctx::Ctx::from_app(app) {
+        Ok(ctx) => model::PictureWatchBmc::start(ctx),
+        Err(_) => Err(model::Error::CtxFail),
+    };
+    if let Err(err) = started {
+        error!("Failed to watch the picture folders: {err}");
+    }
+}
+
+/// Delete the records trashed for longer than the `TrashSettings`, in the background
+fn purge_expired_trash(app: AppHandle<Wry>) {
+    tauri::async_runtime::spawn(async move {
+        let purged = match model::ctx::Ctx::from_app(app) {
+            Ok(ctx) => model::TrashBmc::purge_expired(ctx).await,
+            Err(_) => Err(model::Error::CtxFail),
+        };
+        if let Err(err) = purged {
+            error!("Failed to purge the expired trash: {err}");
+        }
+    });
+}
+
+/// Import the pictures created before the media library to it, in the background
+fn import_legacy_pictures(app: AppHandle<Wry>) {
+    tauri::async_runtime::spawn(async move {
+        let imported = match model::ctx::Ctx::from_app(app) {
+            Ok(ctx) => model::PictureBmc::import_legacy_pictures(ctx).await,
+            Err(_) => Err(model::Error::CtxFail),
+        };
+        match imported {
+            Ok(0) => {}
+            Ok(count) => info!("{count} legacy pictures imported to the media library"),
+            Err(err) => error!("Failed to import the legacy pictures: {err}"),
+        }
+    });
+}
+
 #[tokio::main] // TODO: main doesn't be async, should await ModelStore in other place
 async fn main() -> Result<()> {
     fs::init_workspace();
@@ -83,9 +131,20 @@ async fn main() -> Result<()> {
             ipc::get_document,
             ipc::create_document,
             ipc::update_document,
+            ipc::bulk_update_documents,
             ipc::delete_document,
             ipc::list_documents,
+            ipc::list_document_summaries,
             ipc::create_untitled_document,
+            ipc::search_documents,
+            ipc::get_backlinks,
+            ipc::get_outgoing_links,
+            ipc::list_document_revisions,
+            ipc::get_document_revision,
+            ipc::diff_document_revisions,
+            ipc::restore_document_revision,
+            ipc::get_document_body,
+            ipc::convert_document,
             // Documents Folder
             ipc::get_documents_folder,
             ipc::create_documents_folder,
@@ -96,22 +155,34 @@ async fn main() -> Result<()> {
             ipc::add_folder_or_document,
             ipc::remove_folder_or_document,
             ipc::move_folder_or_document,
+            ipc::bulk_move_folder_or_document,
             ipc::list_folders_tree,
+            ipc::import_markdown_folder,
+            ipc::export_markdown_folder,
             // Documents Template
             ipc::get_documents_template,
             ipc::create_documents_template,
             ipc::update_documents_template,
             ipc::delete_documents_template,
             ipc::list_documents_templates,
+            ipc::create_document_from_template,
+            ipc::migrate_template_instances,
             // Pictures
             ipc::get_picture,
             ipc::create_picture,
             ipc::update_picture,
             ipc::delete_picture,
             ipc::list_pictures,
+            ipc::list_picture_summaries,
             ipc::get_picture_with_url,
             ipc::list_pictures_with_urls,
-            // ipc::collect_pictures_from_disk,
+            ipc::search_pictures,
+            ipc::import_picture_data_url,
+            ipc::relink_missing_pictures,
+            ipc::edit_picture,
+            ipc::reset_picture_edits,
+            ipc::find_similar_pictures,
+            ipc::find_duplicate_picture_groups,
             // Tags & Categories
             ipc::get_category,
             ipc::create_category,
@@ -128,7 +199,29 @@ async fn main() -> Result<()> {
             ipc::update_tag,
             ipc::delete_tag,
             ipc::list_tags,
+            ipc::create_tag_path,
+            ipc::reattach_subtag,
+            ipc::list_tags_tree,
+            ipc::merge_tags,
+            ipc::rename_tag,
+            // Trash
+            ipc::list_trash,
+            ipc::restore_from_trash,
+            ipc::empty_trash,
+            ipc::bulk_delete,
+            // Migrations
+            ipc::run_migrations,
+            ipc::repair_trees,
+            // Vault
+            ipc::export_vault,
+            ipc::import_vault,
+            // Live
+            ipc::subscribe_topic,
+            ipc::unsubscribe_topic,
+            // Diagnostics
+            ipc::get_entity_cache_stats,
         ])
+        .register_uri_scheme_protocol(protocol::SCHEME, protocol::handle)
         // allow only one instance and propagate args and cwd to existing instance
         .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
             println!(
@@ -147,7 +240,21 @@ async fn main() -> Result<()> {
         //  in this file and TauriProvider.jsx
         .plugin(window_state_plugin_builder.build())
         // custom setup code
-        .setup(|app| setup_tray_state(app))
+        .setup(|app| {
+            purge_expired_trash(app.handle());
+            start_live_queries(app.handle());
+            watch_picture_folders(app.handle());
+            import_legacy_pictures(app.handle());
+            setup_tray_state(app)
+        })
+        .on_window_event(|event| {
+            if let WindowEvent::Destroyed = event.event() {
+                let subscriptions = get_context(&event.window().app_handle()).subscriptions.clone();
+                if let Ok(mut subscriptions) = subscriptions.lock() {
+                    subscriptions.remove_window(event.window().label());
+                }
+            }
+        })
         .manage(app_context)
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -5,12 +5,15 @@ use std::fmt::Debug;
 use super::store::{Creatable, Filterable, Patchable};
 use super::{fire_model_event};
 use crate::model::ctx::Ctx;
-use crate::model::{Error, Result};
+use super::page::PageQuery;
+use super::tree_index::patch_updated_record;
+use crate::model::{Error, Page, PictureBmc, Result, PICTURE_LINKS};
+use crate::prelude::f;
 use std::sync::Arc;
 use surreal_qb::filter::{FilterGroups, ListOptions};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use surrealdb::sql::Object;
+use surrealdb::sql::{Object, Value};
 
 /// Backend Model Controller
 pub(super) trait Bmc {
@@ -21,7 +24,21 @@ pub(super) async fn bmc_get<E>(ctx: Arc<Ctx>, _entity: &'static str, id: &str) -
     where
         E: TryFrom<Object, Error = Error> + Sync + Send + DeserializeOwned + Serialize,
 {
-    ctx.get_model_manager().store().exec_get(id).await?.try_into()
+    ctx.get_model_manager().get_record(id).await?.try_into()
+}
+
+/// The alive records of the ids (the trashed and missing ones are left out), in the order of the ids
+pub(super) async fn bmc_get_alive<E>(ctx: Arc<Ctx>, ids: &[String]) -> Result<Vec<E>>
+    where
+        E: TryFrom<Object, Error = Error>,
+{
+    let objects = ctx.get_model_manager().get_records(ids).await?;
+
+    objects
+        .into_iter()
+        .filter(|o| matches!(o.get("dtime"), None | Some(Value::None) | Some(Value::Null)))
+        .map(|o| o.try_into())
+        .collect::<Result<_>>()
 }
 
 pub(super) async fn bmc_create<E, D>(ctx: Arc<Ctx>, entity: &'static str, data: D) -> Result<E>
@@ -43,29 +60,53 @@ pub(super) async fn bmc_update<E, D>(ctx: Arc<Ctx>, entity: &'static str, id: &s
     let ress = ctx.get_model_manager().store().exec_merge(id, data).await?;
 
     fire_model_event(&ctx, entity, "update", ress.clone());
+    patch_updated_record(&ctx, entity, &ress);
 
     ress.try_into()
 }
 
-pub(super) async fn bmc_delete<E>(ctx: Arc<Ctx>, entity: &'static str, id: &str) -> Result<E>
-    where
-        E: TryFrom<Object, Error = Error> + Sync + Send + DeserializeOwned + Serialize,
-{
-    let ress = ctx.get_model_manager().store().exec_delete(id).await?;
+/// Add the fields `E` can't be built without to the projection of the list options, if any
+pub(super) fn require_fields(list_options: &mut ListOptions, required: &[&str]) {
+    if let Some(fields) = list_options.fields.as_mut() {
+        for field in required {
+            if !fields.iter().any(|f| f == field) {
+                fields.push(field.to_string());
+            }
+        }
+    }
+}
 
-    fire_model_event(&ctx, entity, "delete", ress.clone());
+/// The record link fields of the entity its records can be listed with resolved (`ListOptions::expand`)
+fn expandable_links(entity: &str) -> &'static [&'static str] {
+    if entity == PictureBmc::ENTITY {
+        &PICTURE_LINKS
+    } else {
+        &[]
+    }
+}
 
-    ress.try_into()
+/// Reject the `ListOptions::expand` fields the entity can't be built from
+pub(super) fn check_expand(entity: &str, list_options: &ListOptions) -> Result<()> {
+    let links = expandable_links(entity);
+    match list_options.expand.iter().flatten().find(|field| !links.contains(&field.as_str())) {
+        Some(field) => Err(Error::Other(f!("The {entity} links can't be expanded through '{field}'"))),
+        None => Ok(()),
+    }
 }
 
-pub(super) async fn bmc_list<E, F>(ctx: Arc<Ctx>, entity: &'static str, filter: Option<F>, opts: ListOptions) -> Result<Vec<E>>
+/// A page of the listing (see `page`), the trashed records are left out
+pub(super) async fn bmc_list_page<E, F>(ctx: Arc<Ctx>, entity: &'static str, filter: Option<F>, opts: ListOptions) -> Result<Page<E>>
     where
         E: TryFrom<Object, Error = Error>,
         F: Into<FilterGroups> + Debug,
 {
-    let objects = ctx.get_model_manager().store().exec_select(entity, filter, opts).await?;
+    check_expand(entity, &opts)?;
+    let mut opts = opts;
+    let mut page = PageQuery::new(&mut opts)?;
+    let cond = page.cond.take();
+    let (objects, total) = ctx.get_model_manager().store().exec_select_page(entity, filter, cond, opts).await?;
 
-    objects.into_iter().map(|o| o.try_into()).collect::<Result<_>>()
+    page.into_page(objects, total)
 }
 
 pub(super) async fn bmc_custom_solo_query<E>(ctx: Arc<Ctx>, _entity: &'static str, sql: &str, vars: Option<Object>) -> Result<Vec<E>>
@@ -85,3 +126,20 @@ pub(super) async fn bmc_custom_multi_query<E>(ctx: Arc<Ctx>, _entity: &'static s
 
     objects.into_iter().map(|o| o.try_into()).collect::<Result<_>>()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_expand() {
+        let expand = |fields: &[&str]| ListOptions {
+            expand: Some(fields.iter().map(|field| field.to_string()).collect()),
+            ..Default::default()
+        };
+        assert!(check_expand("picture", &expand(&["tags", "categories"])).is_ok());
+        assert!(check_expand("picture", &expand(&["folder"])).is_err());
+        assert!(check_expand("document", &expand(&["tags"])).is_err());
+        assert!(check_expand("document", &ListOptions::default()).is_ok());
+    }
+}
@@ -1,13 +1,81 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
+use once_cell::sync::Lazy;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use surrealdb::sql::Object;
+use surrealdb::sql::{thing, Object};
+use tokio::sync::{Mutex, MutexGuard};
 use crate::model::{Error, Result, ctx::Ctx};
+use crate::model::bmc_base::bmc_custom_solo_query;
+use crate::model::store::x_take::XTake;
+use crate::model::vmap;
+use crate::prelude::f;
+
+/// Held from the tree check of an edge to its write, so two concurrent moves can't make a cycle together
+static TREE_EDGES_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+pub(super) async fn lock_tree_edges() -> MutexGuard<'static, ()> {
+    TREE_EDGES_LOCK.lock().await
+}
 
 pub(super) trait GraphBmc {
     const RELATION_ENTITY: &'static str;
 }
 
+/// Any relation table edge, only the `in` and `out` record ids
+#[derive(Debug, Clone)]
+pub(super) struct Edge {
+    pub r#in: String,
+    pub out: String,
+}
+
+impl TryFrom<Object> for Edge {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<Edge> {
+        Ok(Self {
+            r#in: val.x_take_val("in")?,
+            out: val.x_take_val("out")?,
+        })
+    }
+}
+
+pub(super) async fn bmc_list_edges(ctx: Arc<Ctx>, entity: &'static str) -> Result<Vec<Edge>> {
+    let sql = f!("SELECT in, out FROM {entity};");
+    bmc_custom_solo_query::<Edge>(ctx, entity, &sql, None).await
+}
+
+/// Children ids of every parent through the `entity` relation edges
+pub(super) async fn bmc_list_children(ctx: Arc<Ctx>, entity: &'static str) -> Result<HashMap<String, Vec<String>>> {
+    let mut children = HashMap::<String, Vec<String>>::new();
+    for edge in bmc_list_edges(ctx, entity).await? {
+        children.entry(edge.r#in).or_default().push(edge.out);
+    }
+
+    Ok(children)
+}
+
+/// Returns the id with all of its descendants in the children map (breadth-first)
+pub(super) fn subtree_of(children: &HashMap<String, Vec<String>>, id: &str) -> Vec<String> {
+    let mut subtree = vec![id.to_string()];
+    let mut queue = VecDeque::from([id.to_string()]);
+    while let Some(node) = queue.pop_front() {
+        for child in children.get(&node).into_iter().flatten() {
+            if !subtree.contains(child) {
+                subtree.push(child.clone());
+                queue.push_back(child.clone());
+            }
+        }
+    }
+
+    subtree
+}
+
+/// Returns the id with all of its descendants through the `entity` relation edges (breadth-first)
+pub(super) async fn bmc_list_subtree(ctx: Arc<Ctx>, entity: &'static str, id: &str) -> Result<Vec<String>> {
+    let children = bmc_list_children(ctx, entity).await?;
+    Ok(subtree_of(&children, id))
+}
+
 pub(super) async fn bmc_list_tree<E>(ctx: Arc<Ctx>, entity: &'static str) -> Result<Vec<E>>
     where
         E: TryFrom<Object, Error = Error>,
@@ -17,10 +85,51 @@ pub(super) async fn bmc_list_tree<E>(ctx: Arc<Ctx>, entity: &'static str) -> Res
     objects.into_iter().map(|o| o.try_into()).collect::<Result<_>>()
 }
 
+/// The parents of `id` through the `entity` relation edges (a single one in a tree)
+async fn bmc_list_parents(ctx: Arc<Ctx>, entity: &'static str, id: &str) -> Result<Vec<String>> {
+    let sql = f!("SELECT in, out FROM {entity} WHERE out = $id;");
+    let vars = vmap!("id".into() => thing(id).map_err(|ex| Error::Store(ex.into()))?.into());
+    let edges = bmc_custom_solo_query::<Edge>(ctx, entity, &sql, Some(vars.into())).await?;
+    Ok(edges.into_iter().map(|edge| edge.r#in).collect())
+}
+
+/// Fails with `Error::InvalidHierarchy` if attaching `id` under `parent_id` would make a cycle,
+/// or give a second parent to `id` (`replaced_parent_id` is the parent being detached, if any).
+/// Only the ancestors of `parent_id` are read, the caller holds `lock_tree_edges` until the edge is written.
+/// Note: All the relations edited through `bmc_relate` and `bmc_rerelate_edge` are trees.
+pub(super) async fn bmc_check_tree_edge(
+    ctx: Arc<Ctx>,
+    entity: &'static str,
+    parent_id: &str,
+    id: &str,
+    replaced_parent_id: Option<&str>,
+) -> Result<()> {
+    let parents = bmc_list_parents(ctx.clone(), entity, id).await?;
+    if let Some(parent) = parents.iter().find(|parent| Some(parent.as_str()) != replaced_parent_id) {
+        return Err(Error::InvalidHierarchy(f!("'{id}' is already attached to '{parent}'")));
+    }
+
+    // -- Walk up from the new parent, `id` must not be one of its ancestors
+    let mut visited = HashSet::new();
+    let mut ancestors = vec![parent_id.to_string()];
+    while let Some(ancestor) = ancestors.pop() {
+        if ancestor == id {
+            return Err(Error::InvalidHierarchy(f!("'{parent_id}' is '{id}' or one of its descendants")));
+        }
+        if visited.insert(ancestor.clone()) {
+            ancestors.extend(bmc_list_parents(ctx.clone(), entity, &ancestor).await?);
+        }
+    }
+
+    Ok(())
+}
+
 pub(super) async fn bmc_relate<E>(ctx: Arc<Ctx>, entity: &'static str, from_id: &str, to_id: &str) -> Result<E>
     where
         E: TryFrom<Object, Error=Error> + Sync + Send + DeserializeOwned + Serialize
 {
+    let _guard = lock_tree_edges().await;
+    bmc_check_tree_edge(ctx.clone(), entity, from_id, to_id, None).await?;
     ctx.get_model_manager().store().exec_add_edge(from_id, entity, to_id).await?.try_into()
 }
 
@@ -35,5 +144,9 @@ pub(super) async fn bmc_rerelate_edge<E>(ctx: Arc<Ctx>, entity: &'static str, id
     where
         E: TryFrom<Object, Error=Error> + Sync + Send + DeserializeOwned + Serialize
 {
+    let _guard = lock_tree_edges().await;
+    if let Some(to_id) = to_id {
+        bmc_check_tree_edge(ctx.clone(), entity, to_id, id, from_id).await?;
+    }
     ctx.get_model_manager().store().exec_recreate_edge(id, entity, from_id, to_id).await?.try_into()
 }
\ No newline at end of file
@@ -7,9 +7,10 @@
 //!     - Eventually, this will also be used for "full context" logging/tracing or even performance tracing.
 //!     - For a single user, desktop application, this object is much simpler as authorization and logging requirements are much reduced.
 
-use crate::event::HubEvent;
+use crate::event::{HubEvent, HubSubscriptionsMux};
+use crate::fs::FsStateMux;
 use crate::model::ModelStoreState;
-use crate::Result;
+use crate::{AppSettings, Result};
 use serde::Serialize;
 use std::sync::Arc;
 use tauri::{AppHandle, Manager, Wry};
@@ -32,10 +33,37 @@ impl Ctx {
         self.model_manager.clone()
     }
 
+    /// Note: Read from disk, so the changes made by `change_settings` are always picked up
+    pub fn get_settings(&self) -> AppSettings {
+        AppSettings::deserialize().unwrap_or_default()
+    }
+
     // TODO
     pub fn emit_hub_event<D: Serialize + Clone>(&self, hub_event: HubEvent<D>) {
         let _ = self.app_handle.emit_all("HubEvent", hub_event);
     }
+
+    /// Emit the event to the windows subscribed to its topic only
+    pub fn emit_hub_event_to_subscribers<D: Serialize + Clone>(&self, hub_event: HubEvent<D>) {
+        let windows = match get_context(&self.app_handle).subscriptions.lock() {
+            Ok(subscriptions) => subscriptions.windows(&hub_event.topic),
+            Err(_) => return,
+        };
+
+        for label in windows {
+            if let Some(window) = self.app_handle.get_window(&label) {
+                let _ = window.emit("HubEvent", hub_event.clone());
+            }
+        }
+    }
+
+    pub fn subscriptions(&self) -> HubSubscriptionsMux {
+        get_context(&self.app_handle).subscriptions.clone()
+    }
+
+    pub fn fs_state(&self) -> FsStateMux {
+        get_context(&self.app_handle).fs_state.clone()
+    }
 }
 
 impl Ctx {
@@ -1,12 +1,23 @@
 //! All model and controller for the Document type
 use super::bmc_base::{
-    bmc_create, bmc_custom_multi_query, bmc_delete, bmc_get, bmc_list, bmc_update, Bmc,
+    bmc_custom_solo_query, bmc_get, bmc_list_page,
+    bmc_update, require_fields, Bmc,
 };
+use super::bmc_graph::GraphBmc;
+use super::name_allocator::create_with_free_name;
 use super::store::x_take::XTake;
-use super::store::{vec_to_surreal_value, Creatable, Filterable, Patchable};
-use super::vmap;
+use super::tree_index::{patch_folders_tree_node, update_tree};
+use super::search::{finalize_search_limit, HIGHLIGHT_CLOSE, HIGHLIGHT_OPEN};
+use super::tags_and_categories::{check_tags_and_categories, link_filter_ids};
+use super::store::{
+    vec_to_surreal_links, vec_to_surreal_value, Creatable, Filterable, Patchable, StoreTransaction,
+};
+use super::{fire_model_event, vmap};
 use crate::model::ctx::Ctx;
-use crate::model::{Error, Result};
+use crate::model::{
+    DocumentBody, DocumentLinkBmc, DocumentRevisionBmc, DocumentsFolderBmc, DocumentsFolderChild,
+    DocumentsTemplateBmc, Error, Page, Result, SearchHit, TagBmc, TrashBmc,
+};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_with_macros::skip_serializing_none;
@@ -16,42 +27,13 @@ use std::sync::Arc;
 use surreal_qb::filter::{
     finalize_list_options, FilterNodes, IntoFilterNodes, ListOptions, OpValsArray, OpValsString,
 };
-use surrealdb::sql::{Datetime, Object, Value};
+use surrealdb::sql::{thing, Id, Object, Thing, Value};
 use ts_gen::TS;
 
 // TODO: Does it need an Option for Vec's if they can be empty?
 
-// #[skip_serializing_none]
-// #[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
-// #[ts(export)]
-// pub struct MarkdownDocument {}
-//
-// #[skip_serializing_none]
-// #[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
-// #[ts(export)]
-// pub struct CanvasDocument {}
-//
-// #[skip_serializing_none]
-// #[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
-// #[ts(export)]
-// pub struct LexicalDocument {}
-//
-// #[skip_serializing_none]
-// #[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
-// #[ts(export)]
-// pub struct PdfDocument {}
 //#region ---------- Document ----------
-// #[skip_serializing_none]
-// #[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
-// #[ts(export)]
-// pub enum Document {
-//     #[default]
-//     Markdown(MarkdownDocument),
-//     Canvas(CanvasDocument),
-//     Lexical(LexicalDocument),
-//     Pdf(PdfDocument),
-// }
-
+/// The body of each type is described by `DocumentBody`
 #[derive(
     Debug,
     Serialize,
@@ -73,8 +55,8 @@ pub enum DocumentType {
     Templated,
 }
 
-/// Name must be unique
-/// Documents with different types has different body content
+/// Title must be unique in the folder (enforced by the `document_title_unique` index)
+/// Documents with different types has different body content (see `DocumentBody`)
 #[skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
 #[ts(export, rename_all = "camelCase")]
@@ -119,17 +101,78 @@ impl TryFrom<Object> for Document {
     }
 }
 
-/// Folder must be created with default tittle `New Document` + {number of default named folders + 1}
+/// A document without its body, as listed in the sidebar (see `DocumentBmc::list_summaries`)
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export, rename_all = "camelCase")]
+pub struct DocumentSummary {
+    pub id: String,
+    pub r#type: DocumentType,
+    pub title: String,
+    pub tags: Option<Vec<String>>,
+    pub categories: Option<Vec<String>>,
+}
+
+impl DocumentSummary {
+    /// The projection (`ListOptions::fields`) of the summaries
+    pub const FIELDS: [&'static str; 4] = ["type", "title", "tags", "categories"];
+}
+
+impl TryFrom<Object> for DocumentSummary {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<DocumentSummary> {
+        let doc_type: String = val.x_take_val("type")?;
+
+        Ok(DocumentSummary {
+            id: val.x_take_val("id")?,
+            r#type: DocumentType::from_str(&doc_type)?,
+            title: val.x_take_val("title")?,
+            tags: val.x_take("tags")?,
+            categories: val.x_take("categories")?,
+        })
+    }
+}
+
+impl From<Document> for DocumentSummary {
+    fn from(document: Document) -> Self {
+        DocumentSummary {
+            id: document.id,
+            r#type: document.r#type,
+            title: document.title,
+            tags: document.tags,
+            categories: document.categories,
+        }
+    }
+}
+
+/// The title must be unique in the folder (or among the root documents), see `Error::TitleConflict`
 #[derive(Debug, Serialize, Deserialize, Default, TS)]
 #[ts(export)]
 pub struct DocumentForCreate {
     pub title: String,
     pub r#type: DocumentType,
+    /// Parent folder, the document is created at the root if not set
+    #[ts(optional)]
+    pub folder: Option<String>,
+    /// Only set by the model (e.g. `DocumentsTemplateBmc::create_document`), the client creates empty documents
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    #[ts(skip)]
+    pub body: Option<String>,
 }
 
 impl From<DocumentForCreate> for Value {
     fn from(val: DocumentForCreate) -> Self {
         let mut data = vmap!("title".into() => val.title.into(), "type".into() => val.r#type.to_string().into());
+
+        // Note: `DocumentBmc::create` checks the id, the folder is a record link (see the `0003_document_folder` migration)
+        if let Some(folder) = val.folder.and_then(|folder| thing(&folder).ok()) {
+            data.insert("folder".into(), folder.into());
+        }
+
+        if let Some(body) = val.body {
+            data.insert("body".into(), body.into());
+        }
+
         Value::Object(data.into())
     }
 }
@@ -145,6 +188,10 @@ pub struct DocumentForUpdate {
     pub tags: Option<Vec<String>>,
     pub categories: Option<Vec<String>>,
     pub used_pics: Option<Vec<String>>,
+    /// Only set by the model (`DocumentBmc::convert`, revision restore), the client converts with `convert_document`
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    #[ts(skip)]
+    pub r#type: Option<DocumentType>,
 }
 
 impl From<DocumentForUpdate> for Value {
@@ -160,30 +207,70 @@ impl From<DocumentForUpdate> for Value {
         }
 
         if let Some(tags) = val.tags {
-            data.insert("tags".into(), vec_to_surreal_value(tags.into()));
+            data.insert("tags".into(), vec_to_surreal_links(tags));
         }
 
         if let Some(categories) = val.categories {
-            data.insert("categories".into(), vec_to_surreal_value(categories.into()));
+            data.insert("categories".into(), vec_to_surreal_links(categories));
         }
 
         if let Some(used_pics) = val.used_pics {
             data.insert("used_pics".into(), vec_to_surreal_value(used_pics.into()));
         }
 
+        if let Some(doc_type) = val.r#type {
+            data.insert("type".into(), doc_type.to_string().into());
+        }
+
         data.into()
     }
 }
 
 impl Patchable for DocumentForUpdate {}
 
+/// Tags and categories added to (or removed from) many documents at once, see `DocumentBmc::bulk_update`
+#[derive(Debug, Serialize, Deserialize, Default, TS)]
+#[ts(export)]
+pub struct DocumentsBulkUpdate {
+    #[ts(optional)]
+    pub add_tags: Option<Vec<String>>,
+    #[ts(optional)]
+    pub remove_tags: Option<Vec<String>>,
+    #[ts(optional)]
+    pub add_categories: Option<Vec<String>>,
+    #[ts(optional)]
+    pub remove_categories: Option<Vec<String>>,
+}
+
+fn apply_bulk_change(
+    current: Option<Vec<String>>,
+    add: &Option<Vec<String>>,
+    remove: &Option<Vec<String>>,
+) -> Option<Vec<String>> {
+    if add.is_none() && remove.is_none() {
+        return None;
+    }
+
+    let mut values = current.unwrap_or_default();
+    for value in add.iter().flatten() {
+        if !values.contains(value) {
+            values.push(value.clone());
+        }
+    }
+    if let Some(remove) = remove {
+        values.retain(|value| !remove.contains(value));
+    }
+
+    Some(values)
+}
+
 #[derive(FilterNodes, Debug, Deserialize, Default)]
 pub struct DocumentFilter {
     pub id: Option<OpValsString>,
     pub ctime: Option<OpValsString>,
     pub r#type: Option<OpValsString>,
-    pub title: Option<OpValsString>, // TODO: surrealdb full-text search
-    pub body: Option<OpValsString>,  // TODO: surrealdb full-text search
+    pub title: Option<OpValsString>, // Note: use `DocumentBmc::search` for full-text search
+    pub body: Option<OpValsString>,  // Note: use `DocumentBmc::search` for full-text search
     pub tags: Option<OpValsArray>,
     pub categories: Option<OpValsArray>,
     pub used_pics: Option<OpValsArray>,
@@ -197,51 +284,212 @@ impl Bmc for DocumentBmc {
     const ENTITY: &'static str = "document";
 }
 
+const UNTITLED_PREFIX: &str = "untitled";
+
+/// The fields a `Document` can't be built without, added to the projection of `DocumentBmc::list`
+const DOCUMENT_REQUIRED_FIELDS: [&str; 3] = ["ctime", "type", "title"];
+
 impl DocumentBmc {
     pub async fn get(ctx: Arc<Ctx>, id: &str) -> Result<Document> {
         bmc_get(ctx, Self::ENTITY, id).await
     }
 
+    /// Create the document in `data.folder` (attached with a `documentsFolders` edge).
+    /// Note: The record, its edge and the links of its body are written in one transaction,
+    ///       the id is generated here to relate it. A new document has no parent nor children,
+    ///       so the edge can't break the tree.
     pub async fn create(ctx: Arc<Ctx>, data: DocumentForCreate) -> Result<Document> {
-        bmc_create(ctx, Self::ENTITY, data).await
+        let folder = data.folder.clone();
+        if let Some(folder) = &folder {
+            thing(folder).map_err(|ex| Error::Store(ex.into()))?;
+        }
+
+        let id = Thing::from((Self::ENTITY, Id::rand())).to_string();
+        let body = data.body.clone();
+        let mut tx = StoreTransaction::new();
+        let created = tx.create_with_id(&id, data);
+        if let Some(folder) = &folder {
+            tx.relate(folder, DocumentsFolderBmc::RELATION_ENTITY, &id);
+        }
+        let broken = match &body {
+            Some(body) => DocumentLinkBmc::push_sync_ops(ctx.clone(), &mut tx, &id, body).await?,
+            None => vec![],
+        };
+        let mut results = ctx.get_model_manager().store().exec_transaction(tx).await?;
+        let ress = results
+            .take(created)
+            .ok_or(Error::Store(crate::model::store::Error::ResponseIsEmpty))?;
+        fire_model_event(&ctx, Self::ENTITY, "create", ress.clone());
+        let document: Document = ress.try_into()?;
+        DocumentLinkBmc::report_broken(&ctx, &document.id, &broken);
+
+        let node = DocumentsFolderChild::DocumentSummary(document.clone().into());
+        update_tree(&ctx, |index| index.insert(folder.as_deref(), node));
+
+        DocumentLinkBmc::relink_title(ctx, &document.id, &document.title).await?;
+        Ok(document)
     }
 
+    /// Note: The previous state is stored as a `DocumentRevision`.
+    ///       The body must match the type of the document (see `DocumentBody::parse`).
+    ///       When the body changes, the outgoing wiki-links of the document are synced as well,
+    ///       when the title changes, the links of the other documents are re-resolved.
     pub async fn update(ctx: Arc<Ctx>, id: &str, data: DocumentForUpdate) -> Result<Document> {
-        bmc_update(ctx, Self::ENTITY, id, data).await
+        let previous = Self::get(ctx.clone(), id).await?;
+        check_tags_and_categories(ctx.clone(), data.tags.as_deref(), data.categories.as_deref()).await?;
+        if let Some(body) = &data.body {
+            let body = DocumentBody::parse(data.r#type.as_ref().unwrap_or(&previous.r#type), body)?;
+            // Note: The references of an instance may have been deleted since, they're only checked at creation
+            if let DocumentBody::Templated(mut instance) = body {
+                DocumentsTemplateBmc::check_instance(ctx.clone(), &mut instance).await?;
+            }
+        }
+        let body = data.body.clone();
+        let document: Document = bmc_update(ctx.clone(), Self::ENTITY, id, data).await?;
+
+        DocumentRevisionBmc::record(ctx.clone(), &previous, &document).await?;
+
+        if let Some(body) = body {
+            DocumentLinkBmc::sync_links(ctx.clone(), &document.id, &body).await?;
+        }
+        if document.title != previous.title {
+            DocumentLinkBmc::relink_title(ctx, &document.id, &document.title).await?;
+        }
+
+        Ok(document)
     }
 
     pub async fn delete(ctx: Arc<Ctx>, id: &str) -> Result<Document> {
-        bmc_delete(ctx, Self::ENTITY, id).await
+        TrashBmc::trash(ctx, Self::ENTITY, id).await
     }
 
+    async fn prepare_filters(ctx: Arc<Ctx>, filters: &mut Option<Vec<DocumentFilter>>) -> Result<()> {
+        for filter in filters.iter_mut().flatten() {
+            TagBmc::expand_filter(ctx.clone(), &mut filter.tags).await?;
+            link_filter_ids(&mut filter.tags);
+            link_filter_ids(&mut filter.categories);
+        }
+        Ok(())
+    }
+
+    /// With `ListOptions::fields`, the fields not selected are left out (e.g. the `body`)
     pub async fn list(
         ctx: Arc<Ctx>,
         filters: Option<Vec<DocumentFilter>>,
         list_options: Option<ListOptions>,
-    ) -> Result<Vec<Document>> {
-        let list_options = finalize_list_options(list_options)?;
-        bmc_list(ctx, Self::ENTITY, filters, list_options).await
+    ) -> Result<Page<Document>> {
+        let mut filters = filters;
+        Self::prepare_filters(ctx.clone(), &mut filters).await?;
+        let mut list_options = finalize_list_options(list_options)?;
+        require_fields(&mut list_options, &DOCUMENT_REQUIRED_FIELDS);
+        bmc_list_page(ctx, Self::ENTITY, filters, list_options).await
     }
 
+    /// Same as `list`, without the bodies (the `ListOptions::fields` are replaced)
+    pub async fn list_summaries(
+        ctx: Arc<Ctx>,
+        filters: Option<Vec<DocumentFilter>>,
+        list_options: Option<ListOptions>,
+    ) -> Result<Page<DocumentSummary>> {
+        let mut filters = filters;
+        Self::prepare_filters(ctx.clone(), &mut filters).await?;
+        let mut list_options = finalize_list_options(list_options)?;
+        list_options.fields = Some(DocumentSummary::FIELDS.map(String::from).to_vec());
+        bmc_list_page(ctx, Self::ENTITY, filters, list_options).await
+    }
+
+    /// Create a root Markdown document named with the lowest free `untitledN`
     pub async fn create_untitled(ctx: Arc<Ctx>) -> Result<Document> {
-        // let sql = "CREATE document SET title = function() {\ // TODO: when scripting features will compile (when rquicksj will compile)
-        // const allUntitledDocs = (await surrealdb.query(\"SELECT count(string::startsWith(title,\"untitled\")) FROM document GROUP ALL\"))[0].count;\
-        // return `untitled${allUntitledDocs + 1}`; }";
-        let sql = "LET $count = (SELECT count(string::startsWith(title,\"untitled\")) FROM document GROUP ALL)[0].count;\
-        IF $count = None THEN \
-        (CREATE document SET title = \"untitled1\", ctime = $ctime) \
-        ELSE \
-        (CREATE document SET title = string::concat(\"untitled\", <string>($count+1)), ctime = $ctime) \
-        END";
-        let now = Datetime::default().to_string();
-        let vars = vmap!("ctime".into() => now.into());
-        let result =
-            bmc_custom_multi_query::<Document>(ctx, Self::ENTITY, sql, Some(vars.into())).await?;
-
-        result
-            .into_iter()
-            .next()
-            .ok_or(Error::Store(crate::model::store::Error::ResponseIsEmpty))
+        let document: Document = create_with_free_name(
+            ctx.clone(),
+            Self::ENTITY,
+            "title",
+            UNTITLED_PREFIX,
+            "folder IS NONE AND dtime IS NONE",
+            |title| DocumentForCreate {
+                title,
+                r#type: DocumentType::Markdown,
+                folder: None,
+                body: None,
+            },
+        )
+        .await?;
+
+        let node = DocumentsFolderChild::DocumentSummary(document.clone().into());
+        update_tree(&ctx, |index| index.insert(None, node));
+        Ok(document)
+    }
+
+    /// Add/remove tags and categories of the documents, in one transaction
+    pub async fn bulk_update(ctx: Arc<Ctx>, ids: Vec<String>, data: DocumentsBulkUpdate) -> Result<Vec<Document>> {
+        check_tags_and_categories(ctx.clone(), data.add_tags.as_deref(), data.add_categories.as_deref()).await?;
+
+        let mut tx = StoreTransaction::new();
+        for id in ids.iter() {
+            let document = Self::get(ctx.clone(), id).await?;
+            let patch = DocumentForUpdate {
+                tags: apply_bulk_change(document.tags, &data.add_tags, &data.remove_tags),
+                categories: apply_bulk_change(document.categories, &data.add_categories, &data.remove_categories),
+                ..Default::default()
+            };
+            tx.merge(id, patch);
+        }
+
+        let model_manager = ctx.get_model_manager();
+        let mut results = model_manager.store().exec_transaction(tx).await?;
+
+        let mut documents = vec![];
+        for idx in 0..ids.len() {
+            let document: Document = results.take_val(idx)?;
+            fire_model_event(&ctx, Self::ENTITY, "update", document.clone());
+            patch_folders_tree_node(&ctx, DocumentsFolderChild::DocumentSummary(document.clone().into()));
+            documents.push(document);
+        }
+
+        Ok(documents)
+    }
+
+    /// The parsed body of the document (the empty body of its type if not set)
+    pub async fn get_body(ctx: Arc<Ctx>, id: &str) -> Result<DocumentBody> {
+        let document = Self::get(ctx, id).await?;
+        DocumentBody::parse(&document.r#type, document.body.as_deref().unwrap_or_default())
+    }
+
+    /// Change the type of the document, converting its body (see `DocumentBody::can_convert`)
+    pub async fn convert(ctx: Arc<Ctx>, id: &str, to: DocumentType) -> Result<Document> {
+        let document = Self::get(ctx.clone(), id).await?;
+        if document.r#type == to {
+            return Ok(document);
+        }
+
+        let body = DocumentBody::parse(&document.r#type, document.body.as_deref().unwrap_or_default())?
+            .convert(&to)?;
+        let data = DocumentForUpdate {
+            body: document.body.as_ref().map(|_| body.to_raw()).transpose()?,
+            r#type: Some(to),
+            ..Default::default()
+        };
+
+        Self::update(ctx, id, data).await
+    }
+
+    /// Full-text search over the title and body, ranked by BM25 score
+    pub async fn search(ctx: Arc<Ctx>, query: &str, limit: Option<i64>) -> Result<Vec<SearchHit>> {
+        let limit = finalize_search_limit(limit)?;
+        let sql = "SELECT id, title, \
+        (search::score(0) ?? 0) + (search::score(1) ?? 0) AS score, \
+        search::highlight($open, $close, 0) AS title_highlight, \
+        search::highlight($open, $close, 1) AS content_highlight \
+        FROM document WHERE (title @0@ $query OR body @1@ $query) AND dtime IS NONE \
+        ORDER BY score DESC LIMIT $limit;";
+        let vars = vmap!(
+            "query".into() => query.into(),
+            "limit".into() => limit.into(),
+            "open".into() => HIGHLIGHT_OPEN.into(),
+            "close".into() => HIGHLIGHT_CLOSE.into(),
+        );
+
+        bmc_custom_solo_query::<SearchHit>(ctx, Self::ENTITY, sql, Some(vars.into())).await
     }
 }
 //#endregion ---------- Document ----------
@@ -0,0 +1,483 @@
+//! Typed bodies of the documents.
+//!
+//! The body is stored as a string in the `body` field of the document (so the full-text search,
+//! the wiki-links and the revisions work the same for every type): the Markdown source for
+//! `Markdown` documents and JSON for the others. `DocumentBody` is the parsed and validated form.
+//!
+//! Notes:
+//!     - An empty body is valid for every type, it's the state of a newly created document.
+//!     - The conversion between Markdown and Lexical only handles the block structure
+//!       (headings, quotes, lists, paragraphs), the inline formatting is kept as text.
+
+use crate::model::{DocumentType, DocumentsTemplateBmc, Error, Result};
+use crate::model::bmc_base::Bmc;
+use crate::prelude::f;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use serde_with_macros::skip_serializing_none;
+use std::collections::{BTreeMap, HashSet};
+use surrealdb::sql::thing;
+use ts_gen::TS;
+
+//#region ---------- Canvas ----------
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct CanvasPosition {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct CanvasNode {
+    pub id: String,
+    pub r#type: Option<String>,
+    pub position: CanvasPosition,
+    #[ts(type = "unknown")]
+    pub data: Option<JsonValue>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct CanvasEdge {
+    pub id: String,
+    pub source: String,
+    pub target: String,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct CanvasDocument {
+    pub nodes: Vec<CanvasNode>,
+    pub edges: Vec<CanvasEdge>,
+}
+
+impl CanvasDocument {
+    fn validate(&self) -> Result<()> {
+        let mut node_ids = HashSet::new();
+        for node in self.nodes.iter() {
+            if node.id.is_empty() || !node_ids.insert(node.id.as_str()) {
+                return Err(invalid(f!("canvas node id '{}' is empty or duplicated", node.id)));
+            }
+            if !node.position.x.is_finite() || !node.position.y.is_finite() {
+                return Err(invalid(f!("canvas node '{}' has an invalid position", node.id)));
+            }
+        }
+
+        let mut edge_ids = HashSet::new();
+        for edge in self.edges.iter() {
+            if edge.id.is_empty() || !edge_ids.insert(edge.id.as_str()) {
+                return Err(invalid(f!("canvas edge id '{}' is empty or duplicated", edge.id)));
+            }
+            for end in [&edge.source, &edge.target] {
+                if !node_ids.contains(end.as_str()) {
+                    return Err(invalid(f!("canvas edge '{}' references the unknown node '{end}'", edge.id)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+//#endregion ---------- Canvas ----------
+
+//#region ---------- Lexical ----------
+/// Serialized Lexical editor state (`editorState.toJSON()`)
+#[derive(Debug, Serialize, Deserialize, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct LexicalDocument {
+    #[ts(type = "unknown")]
+    pub root: JsonValue,
+}
+
+impl Default for LexicalDocument {
+    fn default() -> Self {
+        LexicalDocument {
+            root: lexical_element("root", vec![], None),
+        }
+    }
+}
+
+fn lexical_element(r#type: &str, children: Vec<JsonValue>, extra: Option<JsonValue>) -> JsonValue {
+    let mut node = json!({
+        "type": r#type,
+        "children": children,
+        "direction": "ltr",
+        "format": "",
+        "indent": 0,
+        "version": 1,
+    });
+    if let (JsonValue::Object(fields), Some(JsonValue::Object(extra))) = (&mut node, extra) {
+        fields.extend(extra);
+    }
+    node
+}
+
+fn lexical_text(text: &str) -> JsonValue {
+    json!({ "type": "text", "text": text, "detail": 0, "format": 0, "mode": "normal", "style": "", "version": 1 })
+}
+
+/// Text and line break nodes of the lines
+fn lexical_lines<'a>(lines: impl Iterator<Item = &'a str>) -> Vec<JsonValue> {
+    let mut children = vec![];
+    for (idx, line) in lines.enumerate() {
+        if idx > 0 {
+            children.push(json!({ "type": "linebreak", "version": 1 }));
+        }
+        if !line.is_empty() {
+            children.push(lexical_text(line));
+        }
+    }
+    children
+}
+
+fn validate_lexical_node(node: &JsonValue, path: &str) -> Result<()> {
+    let node = node
+        .as_object()
+        .ok_or_else(|| invalid(f!("Lexical node {path} is not an object")))?;
+    let r#type = node
+        .get("type")
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| invalid(f!("Lexical node {path} has no type")))?;
+
+    if r#type == "text" && !node.get("text").map_or(false, JsonValue::is_string) {
+        return Err(invalid(f!("Lexical text node {path} has no text")));
+    }
+
+    match node.get("children") {
+        None => Ok(()),
+        Some(JsonValue::Array(children)) => children
+            .iter()
+            .enumerate()
+            .try_for_each(|(idx, child)| validate_lexical_node(child, &f!("{path}.{idx}"))),
+        Some(_) => Err(invalid(f!("Lexical node {path} has invalid children"))),
+    }
+}
+
+/// Text of the node, with the line breaks
+fn lexical_node_text(node: &JsonValue) -> String {
+    match node.get("type").and_then(JsonValue::as_str) {
+        Some("text") => node.get("text").and_then(JsonValue::as_str).unwrap_or_default().to_string(),
+        Some("linebreak") => "\n".to_string(),
+        _ => lexical_children(node).iter().map(lexical_node_text).collect(),
+    }
+}
+
+fn lexical_children(node: &JsonValue) -> &[JsonValue] {
+    node.get("children").and_then(JsonValue::as_array).map(Vec::as_slice).unwrap_or_default()
+}
+
+impl LexicalDocument {
+    fn validate(&self) -> Result<()> {
+        if self.root.get("type").and_then(JsonValue::as_str) != Some("root") {
+            return Err(invalid("the Lexical state has no root node".to_string()));
+        }
+        validate_lexical_node(&self.root, "root")
+    }
+
+    fn from_markdown(markdown: &str) -> LexicalDocument {
+        let mut blocks = vec![];
+        for block in markdown_blocks(markdown) {
+            let lines: Vec<&str> = block.lines().collect();
+            let first = lines[0];
+
+            let heading_level = first.chars().take_while(|c| *c == '#').count();
+            let node = if lines.len() == 1 && (1..=6).contains(&heading_level) && first[heading_level..].starts_with(' ') {
+                let text = first[heading_level + 1..].trim();
+                lexical_element("heading", lexical_lines(std::iter::once(text)), Some(json!({ "tag": f!("h{heading_level}") })))
+            } else if lines.iter().all(|line| line.starts_with('>')) {
+                let text = lines.iter().map(|line| line[1..].strip_prefix(' ').unwrap_or(&line[1..]));
+                lexical_element("quote", lexical_lines(text), None)
+            } else if lines.iter().all(|line| line.starts_with("- ") || line.starts_with("* ")) {
+                let items = lines
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, line)| lexical_element("listitem", lexical_lines(std::iter::once(&line[2..])), Some(json!({ "value": idx + 1 }))))
+                    .collect();
+                lexical_element("list", items, Some(json!({ "listType": "bullet", "start": 1, "tag": "ul" })))
+            } else {
+                lexical_element("paragraph", lexical_lines(lines.into_iter()), None)
+            };
+            blocks.push(node);
+        }
+
+        LexicalDocument {
+            root: lexical_element("root", blocks, None),
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        lexical_children(&self.root)
+            .iter()
+            .map(|block| {
+                let text = lexical_node_text(block);
+                match block.get("type").and_then(JsonValue::as_str) {
+                    Some("heading") => {
+                        let level = block
+                            .get("tag")
+                            .and_then(JsonValue::as_str)
+                            .and_then(|tag| tag.strip_prefix('h'))
+                            .and_then(|level| level.parse::<usize>().ok())
+                            .unwrap_or(1);
+                        f!("{} {text}", "#".repeat(level))
+                    }
+                    Some("quote") => text.lines().map(|line| f!("> {line}")).collect::<Vec<_>>().join("\n"),
+                    Some("list") => {
+                        let ordered = block.get("listType").and_then(JsonValue::as_str) == Some("number");
+                        lexical_children(block)
+                            .iter()
+                            .enumerate()
+                            .map(|(idx, item)| {
+                                let marker = if ordered { f!("{}.", idx + 1) } else { "-".to_string() };
+                                f!("{marker} {}", lexical_node_text(item))
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    }
+                    Some("code") => f!("```\n{text}\n```"),
+                    _ => text,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// Blocks of the Markdown separated by blank lines (the fenced code blocks are kept whole)
+fn markdown_blocks(markdown: &str) -> Vec<String> {
+    let mut blocks = vec![];
+    let mut current: Vec<&str> = vec![];
+    let mut in_fence = false;
+
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if line.trim().is_empty() && !in_fence {
+            if !current.is_empty() {
+                blocks.push(current.join("\n"));
+                current.clear();
+            }
+            continue;
+        }
+        current.push(line);
+    }
+    if !current.is_empty() {
+        blocks.push(current.join("\n"));
+    }
+
+    blocks
+}
+//#endregion ---------- Lexical ----------
+
+//#region ---------- Pdf ----------
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct PdfAnnotation {
+    pub id: String,
+    /// 1-based page number
+    pub page: u32,
+    /// `[x, y, width, height]` in PDF points
+    pub rect: Option<[f64; 4]>,
+    pub text: Option<String>,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct PdfDocument {
+    /// Path of the PDF file
+    pub file: String,
+    pub annotations: Vec<PdfAnnotation>,
+}
+
+impl PdfDocument {
+    fn validate(&self) -> Result<()> {
+        if self.file.trim().is_empty() {
+            return Err(invalid("the PDF file is not set".to_string()));
+        }
+
+        let mut ids = HashSet::new();
+        for annotation in self.annotations.iter() {
+            if annotation.id.is_empty() || !ids.insert(annotation.id.as_str()) {
+                return Err(invalid(f!("PDF annotation id '{}' is empty or duplicated", annotation.id)));
+            }
+            if annotation.page == 0 {
+                return Err(invalid(f!("PDF annotation '{}' has no page", annotation.id)));
+            }
+            if let Some(rect) = annotation.rect {
+                if rect.iter().any(|v| !v.is_finite()) || rect[2] < 0.0 || rect[3] < 0.0 {
+                    return Err(invalid(f!("PDF annotation '{}' has an invalid rect", annotation.id)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+//#endregion ---------- Pdf ----------
+
+//#region ---------- Templated ----------
+/// Instance of a `DocumentsTemplate`: the values of the template fields by field id (see `TemplateSchema`)
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct TemplatedDocument {
+    pub template: String,
+    #[ts(type = "Record<string, unknown>")]
+    pub values: BTreeMap<String, JsonValue>,
+}
+
+impl TemplatedDocument {
+    fn validate(&self) -> Result<()> {
+        match thing(&self.template) {
+            Ok(template) if template.tb == DocumentsTemplateBmc::ENTITY => Ok(()),
+            _ => Err(invalid(f!("'{}' is not a template id", self.template))),
+        }
+    }
+}
+//#endregion ---------- Templated ----------
+
+fn invalid(message: String) -> Error {
+    Error::InvalidBody(message)
+}
+
+#[derive(Debug, Serialize, Deserialize, TS, Clone, PartialEq)]
+#[ts(export)]
+#[serde(tag = "type", content = "content")]
+pub enum DocumentBody {
+    Markdown(String),
+    Canvas(CanvasDocument),
+    Lexical(LexicalDocument),
+    Pdf(PdfDocument),
+    Templated(TemplatedDocument),
+}
+
+impl DocumentBody {
+    /// Empty body of a document of the type
+    pub fn empty(r#type: &DocumentType) -> DocumentBody {
+        match r#type {
+            DocumentType::Markdown => DocumentBody::Markdown(String::new()),
+            DocumentType::Canvas => DocumentBody::Canvas(CanvasDocument::default()),
+            DocumentType::Lexical => DocumentBody::Lexical(LexicalDocument::default()),
+            DocumentType::Pdf => DocumentBody::Pdf(PdfDocument::default()),
+            DocumentType::Templated => DocumentBody::Templated(TemplatedDocument::default()),
+        }
+    }
+
+    /// Parse and validate the stored body of a document of the type
+    pub fn parse(r#type: &DocumentType, raw: &str) -> Result<DocumentBody> {
+        if raw.trim().is_empty() {
+            return Ok(Self::empty(r#type));
+        }
+
+        let body = match r#type {
+            DocumentType::Markdown => return Ok(DocumentBody::Markdown(raw.to_string())),
+            DocumentType::Canvas => DocumentBody::Canvas(from_json(r#type, raw)?),
+            DocumentType::Lexical => DocumentBody::Lexical(from_json(r#type, raw)?),
+            DocumentType::Pdf => DocumentBody::Pdf(from_json(r#type, raw)?),
+            DocumentType::Templated => DocumentBody::Templated(from_json(r#type, raw)?),
+        };
+        body.validate()?;
+
+        Ok(body)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            DocumentBody::Markdown(_) => Ok(()),
+            DocumentBody::Canvas(canvas) => canvas.validate(),
+            DocumentBody::Lexical(lexical) => lexical.validate(),
+            DocumentBody::Pdf(pdf) => pdf.validate(),
+            DocumentBody::Templated(templated) => templated.validate(),
+        }
+    }
+
+    pub fn document_type(&self) -> DocumentType {
+        match self {
+            DocumentBody::Markdown(_) => DocumentType::Markdown,
+            DocumentBody::Canvas(_) => DocumentType::Canvas,
+            DocumentBody::Lexical(_) => DocumentType::Lexical,
+            DocumentBody::Pdf(_) => DocumentType::Pdf,
+            DocumentBody::Templated(_) => DocumentType::Templated,
+        }
+    }
+
+    /// The stored form of the body
+    pub fn to_raw(&self) -> Result<String> {
+        let raw = match self {
+            DocumentBody::Markdown(markdown) => markdown.clone(),
+            DocumentBody::Canvas(canvas) => serde_json::to_string(canvas),
+            DocumentBody::Lexical(lexical) => serde_json::to_string(lexical),
+            DocumentBody::Pdf(pdf) => serde_json::to_string(pdf),
+            DocumentBody::Templated(templated) => serde_json::to_string(templated),
+        }
+        .map_err(|ex| Error::Other(ex.to_string()))?;
+
+        Ok(raw)
+    }
+
+    /// Whether a body of type `from` can be converted with `DocumentBody::convert` to `to`
+    pub fn can_convert(from: &DocumentType, to: &DocumentType) -> bool {
+        from == to
+            || matches!(
+                (from, to),
+                (DocumentType::Markdown, DocumentType::Lexical) | (DocumentType::Lexical, DocumentType::Markdown)
+            )
+    }
+
+    pub fn convert(self, to: &DocumentType) -> Result<DocumentBody> {
+        let body = match (self, to) {
+            (body, to) if body.document_type() == *to => body,
+            (DocumentBody::Markdown(markdown), DocumentType::Lexical) => {
+                DocumentBody::Lexical(LexicalDocument::from_markdown(&markdown))
+            }
+            (DocumentBody::Lexical(lexical), DocumentType::Markdown) => DocumentBody::Markdown(lexical.to_markdown()),
+            (body, to) => {
+                return Err(Error::Other(f!(
+                    "A {} document can't be converted to {to}",
+                    body.document_type()
+                )))
+            }
+        };
+
+        Ok(body)
+    }
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(doc_type: &DocumentType, raw: &str) -> Result<T> {
+    serde_json::from_str(raw).map_err(|ex| invalid(f!("not a valid {doc_type} body: {ex}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_per_type() {
+        assert!(DocumentBody::parse(&DocumentType::Markdown, "# Title").is_ok());
+        assert!(DocumentBody::parse(&DocumentType::Lexical, "").is_ok());
+        assert!(DocumentBody::parse(&DocumentType::Lexical, "# Title").is_err());
+
+        let canvas = r#"{"nodes":[{"id":"a","position":{"x":0,"y":0}}],"edges":[{"id":"e","source":"a","target":"b"}]}"#;
+        assert!(matches!(DocumentBody::parse(&DocumentType::Canvas, canvas), Err(Error::InvalidBody(_))));
+
+        let pdf = r#"{"file":"book.pdf","annotations":[{"id":"1","page":0}]}"#;
+        assert!(DocumentBody::parse(&DocumentType::Pdf, pdf).is_err());
+    }
+
+    #[test]
+    fn converts_markdown_to_lexical_and_back() {
+        let markdown = "# Title\n\nFirst line\nsecond line\n\n> quoted\n\n- one\n- two";
+        let lexical = DocumentBody::Markdown(markdown.to_string())
+            .convert(&DocumentType::Lexical)
+            .unwrap();
+        assert!(lexical.validate().is_ok());
+
+        let back = lexical.convert(&DocumentType::Markdown).unwrap();
+        assert_eq!(back, DocumentBody::Markdown(markdown.to_string()));
+    }
+}
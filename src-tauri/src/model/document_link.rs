@@ -0,0 +1,363 @@
+//! All model and controller for the wiki-links between documents.
+//!
+//! A document body can reference other documents with `[[Title]]` or `[[document:id]]`
+//! (`[[Title|Alias]]` and `[[Title#Heading]]` are accepted too).
+//! The references are stored as `links` relation edges (`document->links->document`)
+//! and are kept in sync by `DocumentBmc::update` each time the body changes.
+//! The `[[Title]]` references that don't resolve are stored as `pendingLink` records (by normalized title),
+//! they're re-resolved when a document with that title is created or renamed.
+
+use super::bmc_base::bmc_custom_solo_query;
+use super::bmc_graph::GraphBmc;
+use super::store::x_take::XTake;
+use super::store::{Creatable, StoreTransaction};
+use super::{fire_model_event, vmap, DocumentBmc};
+use crate::model::bmc_base::Bmc;
+use crate::model::ctx::Ctx;
+use crate::model::{Error, Result};
+use crate::prelude::f;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use surrealdb::sql::{thing, Object, Value};
+use ts_gen::TS;
+
+const DOCUMENT_ID_PREFIX: &str = "document:";
+
+/// Table of the unresolved `[[Title]]` references, see `DocumentLinkBmc::relink_title`
+pub(super) const PENDING_LINK_ENTITY: &str = "pendingLink";
+
+/// Key of the pending links, so `[[the empire]]` waits for `The Empire` too
+fn normalize_title(title: &str) -> String {
+    title.trim().to_lowercase()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WikiLinkRef {
+    Id(String),
+    Title(String),
+}
+
+impl WikiLinkRef {
+    fn raw(&self) -> &str {
+        match self {
+            WikiLinkRef::Id(raw) | WikiLinkRef::Title(raw) => raw.as_str(),
+        }
+    }
+}
+
+/// Returns the unique wiki-link references of the body, in order of appearance
+pub fn parse_wiki_links(body: &str) -> Vec<WikiLinkRef> {
+    let mut refs = Vec::<WikiLinkRef>::new();
+
+    for caps in lazy_regex::regex!(r"\[\[([^\[\]]+)\]\]").captures_iter(body) {
+        let target = caps[1].split(['|', '#']).next().unwrap_or_default().trim();
+        if target.is_empty() {
+            continue;
+        }
+
+        let link_ref = if target.starts_with(DOCUMENT_ID_PREFIX) {
+            WikiLinkRef::Id(target.to_string())
+        } else {
+            WikiLinkRef::Title(target.to_string())
+        };
+
+        if !refs.contains(&link_ref) {
+            refs.push(link_ref);
+        }
+    }
+
+    refs
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct LinkedDocument {
+    pub id: String,
+    pub title: String,
+}
+
+impl TryFrom<Object> for LinkedDocument {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<LinkedDocument> {
+        Ok(Self {
+            id: val.x_take_val("id")?,
+            title: val.x_take_val("title")?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct OutgoingLinks {
+    pub links: Vec<LinkedDocument>,
+    /// Raw references of the body that don't resolve to any document
+    pub broken: Vec<String>,
+}
+
+/// A `[[Title]]` reference of the document that doesn't resolve yet
+/// Note: The document is stored as a string (like the revisions), so the vault remaps it like the other ids
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingLinkForCreate {
+    document: String,
+    title: String,
+}
+
+impl From<PendingLinkForCreate> for Value {
+    fn from(val: PendingLinkForCreate) -> Self {
+        Value::Object(
+            vmap!(
+                "document".into() => val.document.into(),
+                "title".into() => val.title.into(),
+            )
+            .into(),
+        )
+    }
+}
+
+impl Creatable for PendingLinkForCreate {}
+
+/// A document that may link to another one, see `DocumentLinkBmc::relink_title`
+struct LinkingDocument {
+    id: String,
+    body: Option<String>,
+}
+
+impl TryFrom<Object> for LinkingDocument {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<LinkingDocument> {
+        Ok(Self {
+            id: val.x_take_val("id")?,
+            body: val.x_take("body")?,
+        })
+    }
+}
+
+/// Data of the `links` model event fired when a synced body contains broken links
+#[derive(Debug, Serialize, TS, Clone)]
+#[ts(export)]
+pub struct BrokenLinks {
+    pub document: String,
+    pub broken: Vec<String>,
+}
+
+pub struct DocumentLinkBmc;
+
+impl GraphBmc for DocumentLinkBmc {
+    const RELATION_ENTITY: &'static str = "links";
+}
+
+impl DocumentLinkBmc {
+    /// Resolve the references to the existing documents.
+    /// Returns the linked documents and the raw references that can't be resolved.
+    async fn resolve(
+        ctx: Arc<Ctx>,
+        id: &str,
+        refs: Vec<WikiLinkRef>,
+    ) -> Result<(Vec<LinkedDocument>, Vec<String>)> {
+        if refs.is_empty() {
+            return Ok((vec![], vec![]));
+        }
+
+        let mut ids: Vec<Value> = vec![];
+        let mut titles: Vec<Value> = vec![];
+        for link_ref in refs.iter() {
+            match link_ref {
+                WikiLinkRef::Id(tid) => {
+                    if let Ok(tid) = thing(tid) {
+                        ids.push(tid.into());
+                    }
+                }
+                WikiLinkRef::Title(title) => titles.push(title.as_str().into()),
+            }
+        }
+
+        let sql = "SELECT id, title FROM type::table($tb) WHERE (id IN $ids OR title IN $titles) AND dtime IS NONE;";
+        let vars = vmap!(
+            "tb".into() => DocumentBmc::ENTITY.into(),
+            "ids".into() => ids.into(),
+            "titles".into() => titles.into(),
+        );
+        let found =
+            bmc_custom_solo_query::<LinkedDocument>(ctx, Self::RELATION_ENTITY, sql, Some(vars.into()))
+                .await?;
+
+        let mut linked = Vec::<LinkedDocument>::new();
+        let mut broken = Vec::<String>::new();
+        for link_ref in refs {
+            let target = found.iter().find(|doc| match &link_ref {
+                WikiLinkRef::Id(tid) => doc.id.eq(tid),
+                WikiLinkRef::Title(title) => doc.title.eq(title),
+            });
+
+            match target {
+                // Note: self-references are valid, but are not stored as edges
+                Some(doc) if doc.id.eq(id) => {}
+                Some(doc) => {
+                    if !linked.contains(doc) {
+                        linked.push(doc.clone());
+                    }
+                }
+                None => broken.push(link_ref.raw().to_string()),
+            }
+        }
+
+        Ok((linked, broken))
+    }
+
+    /// Add the operations replacing the outgoing `links` edges and the pending links of the document
+    /// with the references of the body to the transaction (the document may be created by the same transaction).
+    /// Returns the broken references, see `report_broken`.
+    pub(super) async fn push_sync_ops(
+        ctx: Arc<Ctx>,
+        tx: &mut StoreTransaction,
+        id: &str,
+        body: &str,
+    ) -> Result<Vec<String>> {
+        let (linked, broken) = Self::resolve(ctx, id, parse_wiki_links(body)).await?;
+
+        tx.unrelate_all(id, Self::RELATION_ENTITY);
+        for doc in linked.iter() {
+            tx.relate(id, Self::RELATION_ENTITY, &doc.id);
+        }
+
+        tx.delete_where(PENDING_LINK_ENTITY, "document", id.into());
+        let titles = broken
+            .iter()
+            .filter(|raw| !raw.starts_with(DOCUMENT_ID_PREFIX))
+            .map(|title| normalize_title(title))
+            .unique();
+        for title in titles {
+            tx.create(PENDING_LINK_ENTITY, PendingLinkForCreate { document: id.to_string(), title });
+        }
+
+        Ok(broken)
+    }
+
+    /// Report the broken references of the document with a `links` model event
+    pub(super) fn report_broken(ctx: &Arc<Ctx>, id: &str, broken: &[String]) {
+        if broken.is_empty() {
+            return;
+        }
+
+        warn!("Document {id} contains broken links: {broken:?}");
+        fire_model_event(
+            ctx,
+            Self::RELATION_ENTITY,
+            "broken",
+            BrokenLinks {
+                document: id.to_string(),
+                broken: broken.to_vec(),
+            },
+        );
+    }
+
+    /// Replace the outgoing `links` edges of the document with the references of the body.
+    /// Returns the broken references (also reported with a `links` model event).
+    pub async fn sync_links(ctx: Arc<Ctx>, id: &str, body: &str) -> Result<Vec<String>> {
+        // Note: the edges are replaced in one transaction, the previous links are kept if any statement fails
+        let mut tx = StoreTransaction::new();
+        let broken = Self::push_sync_ops(ctx.clone(), &mut tx, id, body).await?;
+        ctx.get_model_manager().store().exec_transaction(tx).await?;
+
+        Self::report_broken(&ctx, id, &broken);
+        Ok(broken)
+    }
+
+    /// Re-sync the links of the documents that may reference the document by its title:
+    /// the documents with a pending link to the title (e.g. written before the document was created)
+    /// and the current backlinks (they may reference a previous title).
+    /// Called when a document is created or renamed.
+    pub async fn relink_title(ctx: Arc<Ctx>, id: &str, title: &str) -> Result<()> {
+        let sql = f!("SELECT document FROM {PENDING_LINK_ENTITY} WHERE title = $title;");
+        let vars = vmap!("title".into() => normalize_title(title).into());
+        let mut ids: Vec<Value> = vec![];
+        for mut pending in ctx
+            .get_model_manager()
+            .store()
+            .exec_custom_solo_query(sql.as_str(), Some(vars.into()))
+            .await?
+        {
+            let document: String = pending.x_take_val("document")?;
+            ids.push(thing(&document).map_err(|ex| Error::Store(ex.into()))?.into());
+        }
+
+        // Note: The document itself is re-synced if it references its own title (a self-reference isn't broken)
+        let sql = f!(
+            "LET $ids = array::union($pending, (SELECT VALUE in FROM {} WHERE out = $id));
+            SELECT id, body FROM $ids WHERE dtime IS NONE;",
+            Self::RELATION_ENTITY
+        );
+        let vars = vmap!(
+            "id".into() => thing(id).map_err(|ex| Error::Store(ex.into()))?.into(),
+            "pending".into() => ids.into(),
+        );
+        let linking = ctx
+            .get_model_manager()
+            .store()
+            .exec_custom_multi_query(&sql, Some(vars.into()), 1)
+            .await?
+            .into_iter()
+            .map(LinkingDocument::try_from)
+            .collect::<Result<Vec<_>>>()?;
+
+        for doc in linking {
+            Self::sync_links(ctx.clone(), &doc.id, doc.body.as_deref().unwrap_or_default()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Documents that link to the given document
+    pub async fn get_backlinks(ctx: Arc<Ctx>, id: &str) -> Result<Vec<LinkedDocument>> {
+        let sql = f!(
+            "SELECT in.id AS id, in.title AS title FROM {} WHERE out = $id AND in.dtime IS NONE ORDER BY title ASC;",
+            Self::RELATION_ENTITY
+        );
+        let vars = vmap!("id".into() => thing(id).map_err(|ex| Error::Store(ex.into()))?.into());
+
+        bmc_custom_solo_query::<LinkedDocument>(ctx, Self::RELATION_ENTITY, &sql, Some(vars.into()))
+            .await
+    }
+
+    /// Documents the given document links to, plus the references of its body that are broken
+    pub async fn get_outgoing_links(ctx: Arc<Ctx>, id: &str) -> Result<OutgoingLinks> {
+        let sql = f!(
+            "SELECT out.id AS id, out.title AS title FROM {} WHERE in = $id ORDER BY title ASC;",
+            Self::RELATION_ENTITY
+        );
+        let vars = vmap!("id".into() => thing(id).map_err(|ex| Error::Store(ex.into()))?.into());
+        let links = bmc_custom_solo_query::<LinkedDocument>(
+            ctx.clone(),
+            Self::RELATION_ENTITY,
+            &sql,
+            Some(vars.into()),
+        )
+        .await?;
+
+        let document = DocumentBmc::get(ctx.clone(), id).await?;
+        let refs = parse_wiki_links(document.body.as_deref().unwrap_or_default());
+        let (_, broken) = Self::resolve(ctx, id, refs).await?;
+
+        Ok(OutgoingLinks { links, broken })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wiki_links() {
+        let body = "See [[The Empire]] and [[document:abc123|the capital]], \
+        also [[The Empire#History]], [[ ]] and [not a link].";
+
+        assert_eq!(
+            parse_wiki_links(body),
+            vec![
+                WikiLinkRef::Title("The Empire".into()),
+                WikiLinkRef::Id("document:abc123".into()),
+            ]
+        );
+    }
+}
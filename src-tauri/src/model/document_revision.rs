@@ -0,0 +1,238 @@
+//! All model and controller for the Document revisions.
+//!
+//! Every `DocumentBmc::update` that changes the title, the type, the body, the tags or the categories
+//! stores the previous state of the document as a revision record, together with a compact diff of the body.
+//! Revisions are pruned with the `RevisionsSettings` retention policy of the `AppSettings`.
+
+use super::bmc_base::{bmc_create, bmc_custom_solo_query, bmc_get, Bmc};
+use super::store::x_take::XTake;
+use super::store::{vec_to_surreal_value, Creatable};
+use super::tags_and_categories::retain_alive_links;
+use super::vmap;
+use crate::model::ctx::Ctx;
+use crate::model::{
+    CategoryBmc, Document, DocumentBmc, DocumentForUpdate, DocumentType, Error, Result, TagBmc,
+};
+use crate::prelude::f;
+use crate::utils::{diff_lines, to_compact_diff, LineChange};
+use serde::{Deserialize, Serialize};
+use serde_with_macros::skip_serializing_none;
+use std::str::FromStr;
+use std::sync::Arc;
+use surrealdb::sql::{Datetime, Object, Value};
+use ts_gen::TS;
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct DocumentRevision {
+    pub id: String,
+    pub ctime: String,
+    pub document: String,
+    pub title: String,
+    /// Not set for the revisions recorded before the typed bodies
+    pub r#type: Option<DocumentType>,
+    pub body: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub categories: Option<Vec<String>>,
+    /// Compact diff from the body of this revision to the body that replaced it
+    pub diff: Option<String>,
+}
+
+impl TryFrom<Object> for DocumentRevision {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<DocumentRevision> {
+        Ok(Self {
+            id: val.x_take_val("id")?,
+            ctime: val.x_take_val("ctime")?,
+            document: val.x_take_val("document")?,
+            title: val.x_take_val("title")?,
+            r#type: val
+                .x_take::<String>("type")?
+                .map(|doc_type| DocumentType::from_str(&doc_type))
+                .transpose()?,
+            body: val.x_take("body")?,
+            tags: val.x_take("tags")?,
+            categories: val.x_take("categories")?,
+            diff: val.x_take("diff")?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct DocumentRevisionForCreate {
+    document: String,
+    title: String,
+    r#type: DocumentType,
+    body: Option<String>,
+    tags: Option<Vec<String>>,
+    categories: Option<Vec<String>>,
+    diff: Option<String>,
+}
+
+impl From<DocumentRevisionForCreate> for Value {
+    fn from(val: DocumentRevisionForCreate) -> Self {
+        let mut data = vmap!(
+            "document".into() => val.document.into(),
+            "title".into() => val.title.into(),
+            "type".into() => val.r#type.to_string().into(),
+        );
+
+        if let Some(body) = val.body {
+            data.insert("body".into(), body.into());
+        }
+
+        if let Some(tags) = val.tags {
+            data.insert("tags".into(), vec_to_surreal_value(tags));
+        }
+
+        if let Some(categories) = val.categories {
+            data.insert("categories".into(), vec_to_surreal_value(categories));
+        }
+
+        if let Some(diff) = val.diff {
+            data.insert("diff".into(), diff.into());
+        }
+
+        Value::Object(data.into())
+    }
+}
+
+impl Creatable for DocumentRevisionForCreate {}
+
+pub struct DocumentRevisionBmc;
+
+impl Bmc for DocumentRevisionBmc {
+    const ENTITY: &'static str = "documentRevision";
+}
+
+impl DocumentRevisionBmc {
+    /// Store the `previous` state of the document if the update changed it, then prune the revisions of the document.
+    /// The diff is only computed when the body changed.
+    pub(super) async fn record(
+        ctx: Arc<Ctx>,
+        previous: &Document,
+        current: &Document,
+    ) -> Result<Option<DocumentRevision>> {
+        if previous.title == current.title
+            && previous.r#type == current.r#type
+            && previous.body == current.body
+            && previous.tags == current.tags
+            && previous.categories == current.categories
+        {
+            return Ok(None);
+        }
+
+        let diff = (previous.body != current.body).then(|| {
+            let changes = diff_lines(
+                previous.body.as_deref().unwrap_or_default(),
+                current.body.as_deref().unwrap_or_default(),
+            );
+            to_compact_diff(&changes)
+        });
+        let data = DocumentRevisionForCreate {
+            document: previous.id.clone(),
+            title: previous.title.clone(),
+            r#type: previous.r#type.clone(),
+            body: previous.body.clone(),
+            tags: previous.tags.clone(),
+            categories: previous.categories.clone(),
+            diff: diff.filter(|d| !d.is_empty()),
+        };
+
+        let revision: DocumentRevision = bmc_create(ctx.clone(), Self::ENTITY, data).await?;
+        Self::prune(ctx, &previous.id).await?;
+
+        Ok(Some(revision))
+    }
+
+    /// Apply the retention policy of the settings to the revisions of the document
+    async fn prune(ctx: Arc<Ctx>, document_id: &str) -> Result<()> {
+        let settings = ctx.get_settings().revisions;
+
+        let mut sql = String::new();
+        let mut vars = vmap!(
+            "tb".into() => Self::ENTITY.into(),
+            "document".into() => document_id.into(),
+        );
+
+        if settings.max_per_document > 0 {
+            sql.push_str("DELETE type::table($tb) WHERE document = $document AND id NOT IN \
+            (SELECT id, ctime FROM type::table($tb) WHERE document = $document ORDER BY ctime DESC LIMIT $keep).id;");
+            vars.insert("keep".into(), (settings.max_per_document as i64).into());
+        }
+
+        if settings.max_age_days > 0 {
+            let min_ctime = Datetime::from(
+                chrono::Utc::now() - chrono::Duration::days(settings.max_age_days as i64),
+            );
+            sql.push_str("DELETE type::table($tb) WHERE document = $document AND ctime < $min_ctime;");
+            vars.insert("min_ctime".into(), min_ctime.to_string().into());
+        }
+
+        if sql.is_empty() {
+            return Ok(());
+        }
+
+        ctx.get_model_manager()
+            .store()
+            .exec_custom_multi_query(&sql, Some(vars.into()), 0)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get(ctx: Arc<Ctx>, id: &str) -> Result<DocumentRevision> {
+        bmc_get(ctx, Self::ENTITY, id).await
+    }
+
+    /// Revisions of the document, newest first
+    pub async fn list(ctx: Arc<Ctx>, document_id: &str) -> Result<Vec<DocumentRevision>> {
+        let sql = f!(
+            "SELECT * FROM {} WHERE document = $document ORDER BY ctime DESC;",
+            Self::ENTITY
+        );
+        let vars = vmap!("document".into() => document_id.into());
+
+        bmc_custom_solo_query(ctx, Self::ENTITY, &sql, Some(vars.into())).await
+    }
+
+    /// Diff between the bodies of two revisions.
+    /// If `to_id` is `None`, the revision is compared to the current state of its document.
+    pub async fn diff(
+        ctx: Arc<Ctx>,
+        from_id: &str,
+        to_id: Option<&str>,
+    ) -> Result<Vec<LineChange>> {
+        let from = Self::get(ctx.clone(), from_id).await?;
+        let to_body = match to_id {
+            Some(to_id) => Self::get(ctx, to_id).await?.body,
+            None => DocumentBmc::get(ctx, &from.document).await?.body,
+        };
+
+        Ok(diff_lines(
+            from.body.as_deref().unwrap_or_default(),
+            to_body.as_deref().unwrap_or_default(),
+        ))
+    }
+
+    /// Restore the document to the state of the revision.
+    /// Note: The state being replaced is stored as a new revision, so a restore can be undone.
+    pub async fn restore(ctx: Arc<Ctx>, id: &str) -> Result<Document> {
+        let revision = Self::get(ctx.clone(), id).await?;
+        // Note: The tags and categories purged since the revision are dropped
+        let tags = retain_alive_links(ctx.clone(), TagBmc::ENTITY, revision.tags).await?;
+        let categories = retain_alive_links(ctx.clone(), CategoryBmc::ENTITY, revision.categories).await?;
+
+        let data = DocumentForUpdate {
+            title: Some(revision.title),
+            body: Some(revision.body.unwrap_or_default()),
+            tags,
+            categories,
+            used_pics: None,
+            r#type: revision.r#type,
+        };
+
+        DocumentBmc::update(ctx, &revision.document, data).await
+    }
+}
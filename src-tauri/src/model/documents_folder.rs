@@ -5,18 +5,25 @@ use serde::ser::SerializeStruct;
 use serde::{Deserialize, Serialize};
 use serde_with_macros::skip_serializing_none;
 use surreal_qb::filter::{finalize_list_options, FilterNodes, ListOptions, OpValsString};
-use surrealdb::sql::{Datetime, Object, Value};
+use surrealdb::sql::{thing, Id, Object, Thing, Value};
 use ts_gen::TS;
 
 use crate::model::bmc_base::{
-    bmc_create, bmc_custom_multi_query, bmc_custom_solo_query, bmc_delete, bmc_get, bmc_list,
-    bmc_update, Bmc,
+    bmc_custom_solo_query, bmc_get, bmc_list_page,
+    bmc_update, require_fields, Bmc,
+};
+use crate::model::bmc_graph::{
+    bmc_delete_edge, bmc_list_edges, bmc_relate, bmc_rerelate_edge, lock_tree_edges, subtree_of, GraphBmc,
 };
-use crate::model::bmc_graph::{bmc_delete_edge, bmc_relate, bmc_rerelate_edge, GraphBmc};
 use crate::model::ctx::Ctx;
-use crate::model::store::{vec_to_surreal_value, Creatable, Filterable, Patchable};
-use crate::model::{get_parent_id, DocumentBmc, Error, Result};
-use crate::model::{vmap, Document, DocumentFilter};
+use crate::model::name_allocator::create_with_free_name;
+use crate::model::store::{
+    vec_to_surreal_value, Creatable, Filterable, Patchable, StoreTransaction,
+};
+use crate::model::tree_index::update_tree;
+use crate::model::{fire_model_event, get_parent_id, DocumentBmc, Error, Page, Result, TrashBmc};
+use crate::model::{TreeDelta, TreeIndex, TreeIndexes, TreeNode};
+use crate::model::{vmap, Document, DocumentFilter, DocumentSummary};
 use crate::prelude::f;
 
 use super::store::x_take::XTake;
@@ -68,18 +75,18 @@ impl TryFrom<Object> for DocumentsFolders {
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
-pub struct DocumentWithParent {
-    document: Document,
+pub struct DocumentSummaryWithParent {
+    document: DocumentSummary,
     parent: Option<String>,
 }
 
-impl TryFrom<Object> for DocumentWithParent {
+impl TryFrom<Object> for DocumentSummaryWithParent {
     type Error = Error;
 
-    fn try_from(mut val: Object) -> Result<DocumentWithParent> {
-        let document: Document = val.clone().try_into()?;
+    fn try_from(mut val: Object) -> Result<DocumentSummaryWithParent> {
+        let document: DocumentSummary = val.clone().try_into()?;
 
-        Ok(DocumentWithParent {
+        Ok(DocumentSummaryWithParent {
             document,
             parent: get_parent_id(val),
         })
@@ -105,13 +112,33 @@ impl TryFrom<Object> for DocumentsFolderWithParent {
     }
 }
 
+/// The cached tree holds the `DocumentSummary`s, they are replaced by their `Document` on demand
+/// (see `DocumentsFolderBmc::list_tree`)
 #[derive(Debug, Serialize, Deserialize, TS, Clone, PartialEq)]
 #[ts(export)]
 pub enum DocumentsFolderChild {
     Document(Document),
+    DocumentSummary(DocumentSummary),
     DocumentsFolder(DocumentsFolderNode),
 }
 
+impl DocumentsFolderChild {
+    /// The same subtree, with the documents instead of their summary (the missing ones are left as is)
+    pub fn with_documents(self, documents: &mut HashMap<String, Document>) -> Self {
+        match self {
+            DocumentsFolderChild::DocumentSummary(summary) => match documents.remove(&summary.id) {
+                Some(document) => DocumentsFolderChild::Document(document),
+                None => DocumentsFolderChild::DocumentSummary(summary),
+            },
+            DocumentsFolderChild::DocumentsFolder(folder) => DocumentsFolderChild::DocumentsFolder(DocumentsFolderNode {
+                children: folder.children.into_iter().map(|child| child.with_documents(documents)).collect(),
+                ..folder
+            }),
+            document => document,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
 #[ts(export)]
 pub struct DocumentsFolderNode {
@@ -120,7 +147,7 @@ pub struct DocumentsFolderNode {
     pub name: String,
     // pub documents: Vec<Document>,
     // pub folders: Vec<DocumentsFolderNode>,
-    children: Vec<DocumentsFolderChild>, // TODO: Is it necessary?
+    pub children: Vec<DocumentsFolderChild>, // TODO: Is it necessary?
 }
 
 #[derive(Debug, Serialize, Default, TS, PartialEq)]
@@ -128,133 +155,119 @@ pub struct DocumentsFolderNode {
 pub struct DocumentsFolderTree {
     // root_folders: Vec<DocumentsFolderNode>,
     // root_documents: Vec<Document>,
-    roots: Vec<DocumentsFolderChild>,
+    pub roots: Vec<DocumentsFolderChild>,
 }
 
-pub fn build_folders_tree(
-    mut dfwps: Vec<DocumentsFolderWithParent>,
-    mut dwps: Vec<DocumentWithParent>,
-) -> DocumentsFolderTree {
-    fn recursive_loop<'a>(
-        node: &'a mut DocumentsFolderNode,
-        id: &String,
-    ) -> Option<&'a mut DocumentsFolderNode> {
-        if (node.id.eq(id)) {
-            return Some(node);
-        }
+impl TreeNode for DocumentsFolderChild {
+    const TOPIC: &'static str = DocumentsFolderBmc::RELATION_ENTITY;
 
-        // for child in node.folders.iter_mut() {
-        //     if let Some(find) = recursive_loop(child, id) {
-        //         return Some(find);
-        //     }
-        // }
-
-        for child in node.children.iter_mut() {
-            match child {
-                DocumentsFolderChild::DocumentsFolder(folder) => {
-                    if let Some(find) = recursive_loop(folder, id) {
-                        return Some(find);
-                    }
-                }
-                _ => {}
-            }
+    fn id(&self) -> &str {
+        match self {
+            DocumentsFolderChild::Document(document) => &document.id,
+            DocumentsFolderChild::DocumentSummary(summary) => &summary.id,
+            DocumentsFolderChild::DocumentsFolder(folder) => &folder.id,
         }
-
-        None
     }
 
-    let mut folders_map = HashMap::<String, DocumentsFolderNode>::new();
-    let mut docs_map = HashMap::<String, Document>::new();
-
-    let mut root_folders_ids = Vec::<String>::new();
-    let mut root_docs_ids = Vec::<String>::new();
-
-    for dfwp in &dfwps {
-        folders_map.insert(
-            dfwp.folder.id.clone(),
-            DocumentsFolderNode {
-                id: dfwp.folder.id.clone(),
-                name: dfwp.folder.name.clone(),
-                ctime: dfwp.folder.ctime.clone(),
-                // documents: vec![],
-                // folders: vec![],
-                children: vec![],
-            },
-        );
-        if dfwp.parent.is_none() {
-            root_folders_ids.push(dfwp.folder.id.clone());
+    fn children_mut(&mut self) -> Option<&mut Vec<Self>> {
+        match self {
+            DocumentsFolderChild::Document(_) | DocumentsFolderChild::DocumentSummary(_) => None,
+            DocumentsFolderChild::DocumentsFolder(folder) => Some(&mut folder.children),
         }
     }
 
-    for dwp in &dwps {
-        docs_map.insert(dwp.document.id.clone(), dwp.document.clone());
-        if dwp.parent.is_none() {
-            root_docs_ids.push(dwp.document.id.clone());
+    fn children(&self) -> &[Self] {
+        match self {
+            DocumentsFolderChild::Document(_) | DocumentsFolderChild::DocumentSummary(_) => &[],
+            DocumentsFolderChild::DocumentsFolder(folder) => &folder.children,
         }
     }
 
-    for dwp in dwps {
-        if let Some(parent_id) = dwp.parent {
-            if let Some(mut parent_node) = folders_map.remove(&parent_id) {
-                // parent_node.documents.push(dwp.document.clone());
-                parent_node
-                    .children
-                    .push(DocumentsFolderChild::Document(dwp.document.clone()));
-                folders_map.insert(parent_id, parent_node);
-            }
-        }
+    fn slot(indexes: &mut TreeIndexes) -> &mut Option<TreeIndex<Self>> {
+        indexes.folders_slot()
     }
+}
 
-    for dfwp in dfwps {
-        if let Some(parent_id) = dfwp.parent {
-            if let Some(child_node) = folders_map.remove(&dfwp.folder.id) {
-                if let Some(mut parent_node) = folders_map.remove(&parent_id) {
-                    // parent_node.folders.push(child_node.clone());
-                    parent_node
-                        .children
-                        .push(DocumentsFolderChild::DocumentsFolder(child_node.clone()));
-                    folders_map.insert(parent_id, parent_node);
-                } else {
-                    for (_, node) in folders_map.iter_mut() {
-                        if let Some(parent_node) = recursive_loop(node, &parent_id) {
-                            // parent_node.folders.push(child_node.clone());
-                            parent_node
-                                .children
-                                .push(DocumentsFolderChild::DocumentsFolder(child_node.clone()));
-                        }
-                    }
-                }
-            }
-        }
+impl From<DocumentsFolder> for DocumentsFolderChild {
+    fn from(folder: DocumentsFolder) -> Self {
+        DocumentsFolderChild::DocumentsFolder(DocumentsFolderNode {
+            id: folder.id,
+            ctime: folder.ctime,
+            name: folder.name,
+            children: vec![],
+        })
     }
+}
 
-    let mut roots = Vec::<DocumentsFolderChild>::new();
-    for id in root_folders_ids {
-        if let Some(node) = folders_map.remove(&id) {
-            roots.push(DocumentsFolderChild::DocumentsFolder(node));
+/// The folders (and documents) without parent are the roots, the ones whose parent isn't listed are left out.
+/// In a folder, the documents come before the sub folders.
+pub fn build_folders_tree(
+    dfwps: Vec<DocumentsFolderWithParent>,
+    dwps: Vec<DocumentSummaryWithParent>,
+) -> DocumentsFolderTree {
+    fn build_node(
+        folder: DocumentsFolder,
+        folders: &mut HashMap<Option<String>, Vec<DocumentsFolder>>,
+        documents: &mut HashMap<Option<String>, Vec<DocumentSummary>>,
+    ) -> DocumentsFolderNode {
+        let key = Some(folder.id.clone());
+        let mut children: Vec<DocumentsFolderChild> = documents
+            .remove(&key)
+            .unwrap_or_default()
+            .into_iter()
+            .map(DocumentsFolderChild::DocumentSummary)
+            .collect();
+        for sub_folder in folders.remove(&key).unwrap_or_default() {
+            children.push(DocumentsFolderChild::DocumentsFolder(build_node(sub_folder, folders, documents)));
         }
-    }
 
-    for id in root_docs_ids {
-        if let Some(node) = docs_map.remove(&id) {
-            roots.push(DocumentsFolderChild::Document(node));
+        DocumentsFolderNode {
+            id: folder.id,
+            ctime: folder.ctime,
+            name: folder.name,
+            children,
         }
     }
 
-    println!("{roots:#?}");
+    let mut folders = HashMap::<Option<String>, Vec<DocumentsFolder>>::new();
+    for dfwp in dfwps {
+        folders.entry(dfwp.parent).or_default().push(dfwp.folder);
+    }
+    let mut documents = HashMap::<Option<String>, Vec<DocumentSummary>>::new();
+    for dwp in dwps {
+        documents.entry(dwp.parent).or_default().push(dwp.document);
+    }
+
+    let mut roots = Vec::<DocumentsFolderChild>::new();
+    for folder in folders.remove(&None).unwrap_or_default() {
+        roots.push(DocumentsFolderChild::DocumentsFolder(build_node(folder, &mut folders, &mut documents)));
+    }
+    for document in documents.remove(&None).unwrap_or_default() {
+        roots.push(DocumentsFolderChild::DocumentSummary(document));
+    }
 
     DocumentsFolderTree { roots }
 }
 
+/// The name must be unique in the parent folder (or among the root folders), see `Error::NameConflict`
 #[derive(Debug, Serialize, Deserialize, Default, TS)]
 #[ts(export)]
 pub struct DocumentsFolderForCreate {
     pub name: String,
+    /// Parent folder, the folder is created at the root if not set
+    #[ts(optional)]
+    pub parent: Option<String>,
 }
 
 impl From<DocumentsFolderForCreate> for Value {
     fn from(val: DocumentsFolderForCreate) -> Self {
         let mut data = vmap!("name".into() => val.name.into());
+
+        // Note: `DocumentsFolderBmc::create` checks the id, the parent is a record link (see the `0003_document_folder` migration)
+        if let Some(parent) = val.parent.and_then(|parent| thing(&parent).ok()) {
+            data.insert("parent".into(), parent.into());
+        }
+
         Value::Object(data.into())
     }
 }
@@ -311,13 +324,56 @@ impl GraphBmc for DocumentsFolderBmc {
     const RELATION_ENTITY: &'static str = "documentsFolders";
 }
 
+const UNNAMED_PREFIX: &str = "unnamed";
+
+/// The fields a `DocumentsFolder` can't be built without, added to the projection of `DocumentsFolderBmc::list`
+const DOCUMENTS_FOLDER_REQUIRED_FIELDS: [&str; 2] = ["ctime", "name"];
+
 impl DocumentsFolderBmc {
     pub async fn get(ctx: Arc<Ctx>, id: &str) -> Result<DocumentsFolder> {
         bmc_get(ctx, Self::ENTITY, id).await
     }
 
+    /// Note: The folder and its edge to the parent are created in the same transaction,
+    ///       the unique name index is checked against the parent's subfolders.
     pub async fn create(ctx: Arc<Ctx>, data: DocumentsFolderForCreate) -> Result<DocumentsFolder> {
-        bmc_create(ctx, Self::ENTITY, data).await
+        let parent = data.parent.clone();
+        if let Some(parent) = &parent {
+            thing(parent).map_err(|ex| Error::Store(ex.into()))?;
+        }
+
+        let id = Thing::from((Self::ENTITY, Id::rand())).to_string();
+        let mut tx = StoreTransaction::new();
+        let created = tx.create_with_id(&id, data);
+        if let Some(parent) = &parent {
+            tx.relate(parent, Self::RELATION_ENTITY, &id);
+        }
+        let mut results = ctx.get_model_manager().store().exec_transaction(tx).await?;
+        let ress = results
+            .take(created)
+            .ok_or(Error::Store(crate::model::store::Error::ResponseIsEmpty))?;
+        fire_model_event(&ctx, Self::ENTITY, "create", ress.clone());
+        let folder: DocumentsFolder = ress.try_into()?;
+
+        update_tree::<DocumentsFolderChild, _>(&ctx, |index| index.insert(parent.as_deref(), folder.clone().into()));
+        Ok(folder)
+    }
+
+    /// Alive subfolder of `parent` (a root folder if not set) named `name`
+    pub async fn find_child(ctx: Arc<Ctx>, parent: Option<&str>, name: &str) -> Result<Option<DocumentsFolder>> {
+        let parent = match parent {
+            Some(parent) => Value::Thing(thing(parent).map_err(|ex| Error::Store(ex.into()))?),
+            None => Value::None,
+        };
+        let sql = f!(
+            "SELECT id, {} FROM {} WHERE parent = $parent AND name = $name AND dtime IS NONE LIMIT 1;",
+            DOCUMENTS_FOLDER_REQUIRED_FIELDS.join(", "),
+            Self::ENTITY
+        );
+        let vars = vmap!("parent".into() => parent, "name".into() => name.into());
+        let mut folders: Vec<DocumentsFolder> =
+            bmc_custom_solo_query(ctx, Self::ENTITY, sql.as_str(), Some(vars.into())).await?;
+        Ok(folders.pop())
     }
 
     pub async fn update(
@@ -329,53 +385,54 @@ impl DocumentsFolderBmc {
     }
 
     pub async fn delete(ctx: Arc<Ctx>, id: &str) -> Result<DocumentsFolder> {
-        bmc_delete(ctx, Self::ENTITY, id).await
+        TrashBmc::trash(ctx, Self::ENTITY, id).await
     }
 
     pub async fn list(
         ctx: Arc<Ctx>,
         filters: Option<Vec<DocumentFilter>>,
         list_options: Option<ListOptions>,
-    ) -> Result<Vec<DocumentsFolder>> {
-        let list_options = finalize_list_options(list_options)?;
-        bmc_list(ctx, Self::ENTITY, filters, list_options).await
+    ) -> Result<Page<DocumentsFolder>> {
+        let mut list_options = finalize_list_options(list_options)?;
+        require_fields(&mut list_options, &DOCUMENTS_FOLDER_REQUIRED_FIELDS);
+        bmc_list_page(ctx, Self::ENTITY, filters, list_options).await
     }
 
+    /// Create a root folder named with the lowest free `unnamedN`
     pub async fn create_unnamed(ctx: Arc<Ctx>) -> Result<DocumentsFolder> {
-        let sql = "LET $count = (SELECT count(string::startsWith(name,\"unnamed\")) FROM documentsFolder GROUP ALL)[0].count;\
-        IF $count = None THEN \
-        (CREATE documentsFolder SET name = \"unnamed1\", ctime = $ctime) \
-        ELSE \
-        (CREATE documentsFolder SET name = string::concat(\"unnamed\", <string>($count+1)), ctime = $ctime) \
-        END";
-        let now = Datetime::default().to_string();
-        let vars = vmap!("ctime".into() => now.into());
-        let result =
-            bmc_custom_multi_query::<DocumentsFolder>(ctx, Self::ENTITY, sql, Some(vars.into()))
-                .await?;
-
-        result
-            .into_iter()
-            .next()
-            .ok_or(Error::Store(crate::model::store::Error::ResponseIsEmpty))
+        let scope = f!("dtime IS NONE AND count(<-{}) = 0", Self::RELATION_ENTITY);
+        let folder: DocumentsFolder =
+            create_with_free_name(ctx.clone(), Self::ENTITY, "name", UNNAMED_PREFIX, &scope, |name| {
+                DocumentsFolderForCreate { name, parent: None }
+            })
+            .await?;
+        update_tree::<DocumentsFolderChild, _>(&ctx, |index| index.insert(None, folder.clone().into()));
+        Ok(folder)
     }
 
+    /// The tree mutations return (and broadcast) the patch of the tree, see `tree_index`
     pub async fn attach_folder_or_document(
         ctx: Arc<Ctx>,
         id: &str,
         sub_id: &str,
-    ) -> Result<DocumentsFolderTree> {
+    ) -> Result<TreeDelta<DocumentsFolderChild>> {
+        Self::cached_summary_tree(ctx.clone()).await?;
         bmc_relate::<DocumentsFolders>(ctx.clone(), Self::RELATION_ENTITY, id, sub_id).await?;
-        Self::list_tree(ctx).await
+        Ok(update_tree(&ctx, |index| index.move_node(sub_id, Some(id))))
     }
 
+    /// The detached folder or document goes back to the root
     pub async fn detach_folder_or_document(
         ctx: Arc<Ctx>,
         id: &str,
         sub_id: &str,
-    ) -> Result<DocumentsFolderTree> {
+    ) -> Result<TreeDelta<DocumentsFolderChild>> {
+        Self::cached_summary_tree(ctx.clone()).await?;
         bmc_delete_edge::<DocumentsFolders>(ctx.clone(), Self::RELATION_ENTITY, id, sub_id).await?;
-        Self::list_tree(ctx).await
+        Ok(update_tree(&ctx, |index| {
+            let attached = index.parent(sub_id) == Some(Some(id));
+            attached.then(|| index.move_node(sub_id, None)).flatten()
+        }))
     }
 
     pub async fn reattach_folder_or_document(
@@ -383,7 +440,8 @@ impl DocumentsFolderBmc {
         id: &str,
         from_id: Option<&str>,
         to_id: Option<&str>,
-    ) -> Result<DocumentsFolderTree> {
+    ) -> Result<TreeDelta<DocumentsFolderChild>> {
+        Self::cached_summary_tree(ctx.clone()).await?;
         let df = bmc_rerelate_edge::<DocumentsFolders>(
             ctx.clone(),
             Self::RELATION_ENTITY,
@@ -393,68 +451,111 @@ impl DocumentsFolderBmc {
         )
         .await?;
         println!("{df:?}");
-        Self::list_tree(ctx).await
+        Ok(update_tree(&ctx, |index| index.move_node(id, to_id)))
     }
 
-    pub async fn list_tree(ctx: Arc<Ctx>) -> Result<DocumentsFolderTree> {
-        let mut sql = f!(
-            "SELECT *, <-{}<-documentsFolder.id AS parent FROM documentsFolder ORDER BY id ASC;",
-            Self::RELATION_ENTITY
-        )
-        .into_boxed_str();
+    /// Move the folders and documents to the folder (to the root if `to_id` is not set), in one transaction
+    pub async fn bulk_move_folder_or_document(
+        ctx: Arc<Ctx>,
+        ids: Vec<String>,
+        to_id: Option<&str>,
+    ) -> Result<Vec<TreeDelta<DocumentsFolderChild>>> {
+        Self::cached_summary_tree(ctx.clone()).await?;
+        let _guard = lock_tree_edges().await;
+        let mut parents = HashMap::<String, String>::new();
+        let mut children = HashMap::<String, Vec<String>>::new();
+        for edge in bmc_list_edges(ctx.clone(), Self::RELATION_ENTITY).await? {
+            parents.insert(edge.out.clone(), edge.r#in.clone());
+            children.entry(edge.r#in).or_default().push(edge.out);
+        }
+
+        let mut tx = StoreTransaction::new();
+        let mut moved = vec![];
+        for id in ids.iter() {
+            let from_id = parents.get(id).map(String::as_str);
+            if from_id == to_id {
+                continue;
+            }
+            if let Some(to_id) = to_id {
+                if subtree_of(&children, id).iter().any(|sub_id| sub_id == to_id) {
+                    return Err(Error::InvalidHierarchy(f!("'{to_id}' is '{id}' or one of its descendants")));
+                }
+            }
+            // Note: The new edge is added first, like `SurrealStore::exec_recreate_edge`
+            if let Some(to_id) = to_id {
+                tx.relate(to_id, Self::RELATION_ENTITY, id);
+            }
+            if let Some(from_id) = from_id {
+                tx.unrelate(from_id, Self::RELATION_ENTITY, id);
+            }
+            // -- The next ids are checked against the tree with this move
+            if let Some(siblings) = from_id.and_then(|from_id| children.get_mut(from_id)) {
+                siblings.retain(|sibling| sibling != id);
+            }
+            if let Some(to_id) = to_id {
+                children.entry(to_id.to_string()).or_default().push(id.clone());
+            }
+            moved.push(id);
+        }
+
+        if tx.is_empty() {
+            return Ok(vec![]);
+        }
+        let model_manager = ctx.get_model_manager();
+        model_manager.store().exec_transaction(tx).await?;
 
-        let dfwps =
-            bmc_custom_solo_query::<DocumentsFolderWithParent>(ctx.clone(), "", &sql, None).await?;
-        println!("{dfwps:?}");
+        let deltas = moved.into_iter().map(|id| update_tree(&ctx, |index| index.move_node(id, to_id))).collect();
+        fire_model_event(&ctx, Self::RELATION_ENTITY, "move", ids);
+        Ok(deltas)
+    }
 
-        sql = f!(
-            "SELECT *, <-{}<-documentsFolder.id AS parent FROM document ORDER BY id ASC;",
+    /// The cached tree (see `tree_index`), built on the first call from the summaries of the documents
+    async fn cached_summary_tree(ctx: Arc<Ctx>) -> Result<Vec<DocumentsFolderChild>> {
+        let model_manager = ctx.get_model_manager();
+        if let Some(roots) = model_manager.cached_tree() {
+            return Ok(roots);
+        }
+        let generation = model_manager.tree_generation();
+
+        let sql = f!(
+            "SELECT *, <-{}<-documentsFolder.id AS parent FROM documentsFolder WHERE dtime IS NONE ORDER BY id ASC;",
             Self::RELATION_ENTITY
-        )
-        .into_boxed_str();
-        let dwps = bmc_custom_solo_query::<DocumentWithParent>(ctx.clone(), "", &sql, None).await?;
-        println!("{dwps:?}");
+        );
+        let dfwps = bmc_custom_solo_query::<DocumentsFolderWithParent>(ctx.clone(), "", &sql, None).await?;
+
+        let sql = f!(
+            "SELECT id, {}, <-{}<-documentsFolder.id AS parent FROM document WHERE dtime IS NONE ORDER BY id ASC;",
+            DocumentSummary::FIELDS.join(", "),
+            Self::RELATION_ENTITY
+        );
+        let dwps = bmc_custom_solo_query::<DocumentSummaryWithParent>(ctx.clone(), "", &sql, None).await?;
 
         let tree = build_folders_tree(dfwps, dwps);
-        Ok(tree)
-
-        //     Ok(
-        //         DocumentsFolderTree {
-        //             roots: vec![
-        //                 DocumentsFolderChild::Document(Document {
-        //                     id: "document:d1".into(),
-        //                     title: "D1".into(),
-        //                     ctime: "".into(),
-        //                     body: None,
-        //                     tags: None,
-        //                     categories: None,
-        //                     used_pics: None,
-        //                 }),
-        //                 DocumentsFolderChild::DocumentsFolder(DocumentsFolderNode {
-        //                     id: "documentsFolder:df1".to_string(),
-        //                     ctime: "".to_string(),
-        //                     name: "DF1".to_string(),
-        //                     children: vec![
-        //                         DocumentsFolderChild::DocumentsFolder(DocumentsFolderNode {
-        //                             id: "documentsFolder:df2".to_string(),
-        //                             ctime: "".to_string(),
-        //                             name: "DF2".to_string(),
-        //                             children: vec![],
-        //                         }),
-        //                         DocumentsFolderChild::Document(Document {
-        //                             id: "document:d2".to_string(),
-        //                             ctime: "".to_string(),
-        //                             title: "D2".to_string(),
-        //                             body: None,
-        //                             tags: None,
-        //                             categories: None,
-        //                             used_pics: None,
-        //                         })
-        //                     ],
-        //                 })
-        //             ]
-        //         }
-        //     )
+        model_manager.cache_tree(tree.roots.clone(), generation);
+        Ok(tree.roots)
+    }
+
+    /// The tree with the documents (and their body)
+    pub async fn list_tree(ctx: Arc<Ctx>) -> Result<DocumentsFolderTree> {
+        let roots = Self::cached_summary_tree(ctx.clone()).await?;
+
+        let sql = "SELECT * FROM document WHERE dtime IS NONE;";
+        let mut documents: HashMap<String, Document> =
+            bmc_custom_solo_query::<Document>(ctx, DocumentBmc::ENTITY, sql, None)
+                .await?
+                .into_iter()
+                .map(|document| (document.id.clone(), document))
+                .collect();
+
+        Ok(DocumentsFolderTree {
+            roots: roots.into_iter().map(|root| root.with_documents(&mut documents)).collect(),
+        })
+    }
+
+    /// Same as `list_tree`, with the `DocumentSummary` of the documents instead of their body
+    pub async fn list_summary_tree(ctx: Arc<Ctx>) -> Result<DocumentsFolderTree> {
+        let roots = Self::cached_summary_tree(ctx).await?;
+        Ok(DocumentsFolderTree { roots })
     }
 }
 //#endregion ---------- Documents Folder ----------
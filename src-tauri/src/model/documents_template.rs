@@ -1,22 +1,27 @@
 use super::bmc_base::{
-    bmc_create, bmc_custom_multi_query, bmc_custom_solo_query, bmc_delete, bmc_get, bmc_list,
-    bmc_update, Bmc,
+    bmc_create, bmc_custom_multi_query, bmc_custom_solo_query, bmc_get, bmc_list_page,
+    bmc_update, require_fields, Bmc,
 };
 use super::store::x_take::XTake;
 use super::store::{Creatable, Filterable, Patchable};
-use super::{vmap, ModelMutateResultData};
+use super::{fire_model_event, vmap, ModelMutateResultData};
 use crate::model::bmc_graph::{bmc_delete_edge, bmc_relate, bmc_rerelate_edge};
 use crate::model::ctx::Ctx;
-use crate::model::{Error, Result};
+use crate::model::{
+    Document, DocumentBmc, DocumentBody, DocumentForCreate, DocumentForUpdate, DocumentLinkBmc,
+    DocumentRevisionBmc, DocumentType, Error, Page, Result, TemplateSchema, TemplatedDocument, TrashBmc,
+};
 use crate::prelude::f;
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 use serde_with_macros::skip_serializing_none;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::sync::Arc;
 use surreal_qb::filter::{
     finalize_list_options, FilterNode, FilterNodes, IntoFilterNodes, ListOptions, OpValsArray,
     OpValsString,
 };
-use surrealdb::sql::{Datetime, Object, Value};
+use surrealdb::sql::{thing, Datetime, Object, Value};
 use ts_gen::TS;
 
 // TODO: maybe add icon
@@ -29,6 +34,8 @@ pub struct DocumentsTemplate {
     pub name: String,
     pub description: Option<String>,
     pub data: String,
+    /// Fields of the instances, see `DocumentsTemplateBmc::create_document`
+    pub schema: TemplateSchema,
     // TODO: ?
     // pub tags: Option<Vec<String>>,
     // pub categories: Option<Vec<String>>,
@@ -44,6 +51,10 @@ impl TryFrom<Object> for DocumentsTemplate {
             name: val.x_take_val("name")?,
             description: val.x_take("description")?,
             data: val.x_take_val("data")?,
+            schema: match val.x_take::<String>("schema")? {
+                Some(schema) => serde_json::from_str(&schema).map_err(|ex| Error::Other(ex.to_string()))?,
+                None => TemplateSchema::default(),
+            },
         };
 
         Ok(template)
@@ -58,6 +69,8 @@ pub struct DocumentsTemplateForCreate {
     pub description: Option<String>,
     // TODO: maybe add default value for data field like one __body element
     pub data: String,
+    #[ts(optional)]
+    pub schema: Option<TemplateSchema>,
 }
 
 impl From<DocumentsTemplateForCreate> for Value {
@@ -68,6 +81,10 @@ impl From<DocumentsTemplateForCreate> for Value {
             data.insert("description".into(), description.into());
         }
 
+        if let Some(schema) = val.schema.as_ref().and_then(|schema| serde_json::to_string(schema).ok()) {
+            data.insert("schema".into(), schema.into());
+        }
+
         Value::Object(data.into())
     }
 }
@@ -81,6 +98,8 @@ pub struct DocumentsTemplateForUpdate {
     pub description: Option<String>,
     // TODO: maybe add default value for data field like one __body element
     pub data: Option<String>,
+    /// The instances are migrated when the schema changes
+    pub schema: Option<TemplateSchema>,
 }
 
 impl From<DocumentsTemplateForUpdate> for Value {
@@ -99,6 +118,10 @@ impl From<DocumentsTemplateForUpdate> for Value {
             data.insert("data".into(), t_data.into());
         }
 
+        if let Some(schema) = val.schema.as_ref().and_then(|schema| serde_json::to_string(schema).ok()) {
+            data.insert("schema".into(), schema.into());
+        }
+
         Value::Object(data.into())
     }
 }
@@ -121,6 +144,30 @@ impl Bmc for DocumentsTemplateBmc {
     const ENTITY: &'static str = "documentsTemplate";
 }
 
+/// The fields a `DocumentsTemplate` can't be built without, added to the projection of `DocumentsTemplateBmc::list`
+const DOCUMENTS_TEMPLATE_REQUIRED_FIELDS: [&str; 3] = ["ctime", "name", "data"];
+
+/// Values removed from an instance by `DocumentsTemplateBmc::migrate_instances`
+#[derive(Debug, Serialize, Deserialize, TS, Clone)]
+#[ts(export)]
+pub struct DroppedTemplateValues {
+    pub document: String,
+    pub title: String,
+    #[ts(type = "Record<string, unknown>")]
+    pub values: BTreeMap<String, JsonValue>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone)]
+#[ts(export)]
+pub struct TemplateMigrationReport {
+    pub template: String,
+    /// Instances whose values changed
+    pub migrated: Vec<String>,
+    /// Fields of the instances that are no longer in the schema
+    pub removed_fields: Vec<String>,
+    pub dropped: Vec<DroppedTemplateValues>,
+}
+
 impl DocumentsTemplateBmc {
     pub async fn get(ctx: Arc<Ctx>, id: &str) -> Result<DocumentsTemplate> {
         bmc_get(ctx, Self::ENTITY, id).await
@@ -130,27 +177,179 @@ impl DocumentsTemplateBmc {
         ctx: Arc<Ctx>,
         data: DocumentsTemplateForCreate,
     ) -> Result<DocumentsTemplate> {
+        if let Some(schema) = &data.schema {
+            schema.validate()?;
+        }
         bmc_create(ctx, Self::ENTITY, data).await
     }
 
+    /// Note: When the schema changes, the instances are migrated (see `migrate_instances`)
     pub async fn update(
         ctx: Arc<Ctx>,
         id: &str,
         data: DocumentsTemplateForUpdate,
     ) -> Result<DocumentsTemplate> {
-        bmc_update(ctx, Self::ENTITY, id, data).await
+        let schema_changed = match &data.schema {
+            Some(schema) => {
+                schema.validate()?;
+                Self::get(ctx.clone(), id).await?.schema != *schema
+            }
+            None => false,
+        };
+
+        let template: DocumentsTemplate = bmc_update(ctx.clone(), Self::ENTITY, id, data).await?;
+        if schema_changed {
+            let report = Self::migrate_instances(ctx.clone(), id).await?;
+            fire_model_event(&ctx, Self::ENTITY, "migrate", report);
+        }
+
+        Ok(template)
     }
 
     pub async fn delete(ctx: Arc<Ctx>, id: &str) -> Result<DocumentsTemplate> {
-        bmc_delete(ctx, Self::ENTITY, id).await
+        TrashBmc::trash(ctx, Self::ENTITY, id).await
     }
 
     pub async fn list(
         ctx: Arc<Ctx>,
         filters: Option<Vec<DocumentsTemplateFilter>>,
         list_options: Option<ListOptions>,
-    ) -> Result<Vec<DocumentsTemplate>> {
-        let list_options = finalize_list_options(list_options)?;
-        bmc_list(ctx, Self::ENTITY, filters, list_options).await
+    ) -> Result<Page<DocumentsTemplate>> {
+        let mut list_options = finalize_list_options(list_options)?;
+        require_fields(&mut list_options, &DOCUMENTS_TEMPLATE_REQUIRED_FIELDS);
+        bmc_list_page(ctx, Self::ENTITY, filters, list_options).await
+    }
+
+    /// Fail if some of the records don't exist (or are trashed)
+    async fn check_references(ctx: Arc<Ctx>, references: Vec<String>) -> Result<()> {
+        if references.is_empty() {
+            return Ok(());
+        }
+
+        let mut things: Vec<Value> = vec![];
+        for id in references.iter() {
+            things.push(thing(id).map_err(|ex| Error::Store(ex.into()))?.into());
+        }
+        let sql = "SELECT id FROM $ids WHERE dtime IS NONE;";
+        let vars = vmap!("ids".into() => things.into());
+        let found = ctx
+            .get_model_manager()
+            .store()
+            .exec_custom_solo_query(sql, Some(vars.into()))
+            .await?
+            .into_iter()
+            .map(|mut obj| obj.x_take_val::<String>("id"))
+            .collect::<core::result::Result<HashSet<_>, _>>()?;
+
+        match references.iter().find(|id| !found.contains(*id)) {
+            Some(missing) => Err(Error::InvalidBody(f!("'{missing}' doesn't exist"))),
+            None => Ok(()),
+        }
+    }
+
+    /// Validate the values of an instance against the schema of its template, and fill the defaults.
+    /// Returns the record ids referenced by the values.
+    pub(super) async fn check_instance(ctx: Arc<Ctx>, instance: &mut TemplatedDocument) -> Result<Vec<String>> {
+        let template = Self::get(ctx, &instance.template).await?;
+        template.schema.check_values(&mut instance.values)
+    }
+
+    /// Create a `Templated` document with the values of the template fields
+    pub async fn create_document(
+        ctx: Arc<Ctx>,
+        id: &str,
+        title: String,
+        folder: Option<String>,
+        values: BTreeMap<String, JsonValue>,
+    ) -> Result<Document> {
+        let mut instance = TemplatedDocument {
+            template: id.to_string(),
+            values,
+        };
+        let references = Self::check_instance(ctx.clone(), &mut instance).await?;
+        Self::check_references(ctx.clone(), references).await?;
+        let body = DocumentBody::Templated(instance).to_raw()?;
+
+        // Note: The document is created with its body (and links), there is no empty document left if it fails
+        let data = DocumentForCreate {
+            title,
+            r#type: DocumentType::Templated,
+            folder,
+            body: Some(body),
+        };
+
+        DocumentBmc::create(ctx, data).await
+    }
+
+    /// Alive `Templated` documents created from the template
+    async fn list_instances(ctx: Arc<Ctx>, id: &str) -> Result<Vec<(Document, TemplatedDocument)>> {
+        // Note: `string::contains` only narrows the candidates, the body is parsed to check the template
+        let sql = "SELECT * FROM document WHERE type = $type AND dtime IS NONE AND string::contains(body ?? '', $id);";
+        let vars = vmap!(
+            "type".into() => DocumentType::Templated.to_string().into(),
+            "id".into() => id.into(),
+        );
+        let documents = bmc_custom_solo_query::<Document>(ctx, DocumentBmc::ENTITY, sql, Some(vars.into())).await?;
+
+        let mut instances = vec![];
+        for document in documents {
+            let body = DocumentBody::parse(&document.r#type, document.body.as_deref().unwrap_or_default());
+            if let Ok(DocumentBody::Templated(instance)) = body {
+                if instance.template == id {
+                    instances.push((document, instance));
+                }
+            }
+        }
+
+        Ok(instances)
+    }
+
+    /// Conform the values of the instances to the current schema of the template.
+    /// The values of the removed fields and the values that can't be converted to the new kind
+    /// of their field are dropped and reported (they stay in the revisions of the documents).
+    pub async fn migrate_instances(ctx: Arc<Ctx>, id: &str) -> Result<TemplateMigrationReport> {
+        let template = Self::get(ctx.clone(), id).await?;
+        let mut report = TemplateMigrationReport {
+            template: template.id.clone(),
+            ..Default::default()
+        };
+        let mut removed_fields = BTreeSet::new();
+
+        for (document, mut instance) in Self::list_instances(ctx.clone(), &template.id).await? {
+            let previous = instance.values.clone();
+            let dropped = template.schema.migrate_values(&mut instance.values);
+            if instance.values == previous {
+                continue;
+            }
+
+            removed_fields.extend(
+                dropped
+                    .keys()
+                    .filter(|field| !template.schema.fields.iter().any(|f| &f.id == *field))
+                    .cloned(),
+            );
+            if !dropped.is_empty() {
+                report.dropped.push(DroppedTemplateValues {
+                    document: document.id.clone(),
+                    title: document.title.clone(),
+                    values: dropped,
+                });
+            }
+
+            // Note: Not `DocumentBmc::update`, a new required field without default has no value yet
+            let body = DocumentBody::Templated(instance).to_raw()?;
+            let data = DocumentForUpdate {
+                body: Some(body.clone()),
+                ..Default::default()
+            };
+            let updated: Document = bmc_update(ctx.clone(), DocumentBmc::ENTITY, &document.id, data).await?;
+            DocumentRevisionBmc::record(ctx.clone(), &document, &updated).await?;
+            DocumentLinkBmc::sync_links(ctx.clone(), &document.id, &body).await?;
+            report.migrated.push(document.id);
+        }
+
+        report.removed_fields = removed_fields.into_iter().collect();
+
+        Ok(report)
     }
 }
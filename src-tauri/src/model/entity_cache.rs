@@ -0,0 +1,264 @@
+//! Read-through cache of the records of the hot entities (tags, categories, ...), in the `ModelStore`.
+//!
+//! `bmc_get` and `bmc_get_alive` read through it, each cached entity has its own LRU of records
+//! (`CacheSettings::entity_cache_size` records) with its hit/miss counters.
+//!
+//! Notes:
+//!     - `fire_model_event` drops the records of the event entity, the events of the whole store
+//!       (e.g. the vault import, the migrations) drop all of them.
+//!     - Only the entities written through the model events are cached, see `cached_entities`.
+//!     - A record read while its entity is invalidated isn't cached (the generation changed meanwhile).
+
+use crate::algo::lru::LruCache;
+use crate::model::ctx::Ctx;
+use crate::model::{CategoryBmc, DocumentsFolderBmc, DocumentsTemplateBmc, Error, ModelStore, Result, TagBmc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use surrealdb::sql::{thing, Object, Value};
+use ts_gen::TS;
+
+use super::bmc_base::Bmc;
+use super::vmap;
+
+/// The entities whose records are cached.
+/// Note: the other ones are also written with raw queries (e.g. the tags of the documents on a tag merge).
+fn cached_entities() -> [&'static str; 4] {
+    [
+        TagBmc::ENTITY,
+        CategoryBmc::ENTITY,
+        DocumentsFolderBmc::ENTITY,
+        DocumentsTemplateBmc::ENTITY,
+    ]
+}
+
+fn entity_of(id: &str) -> &str {
+    id.split_once(':').map_or(id, |(entity, _)| entity)
+}
+
+#[derive(Debug, Serialize, Clone, TS)]
+#[ts(export)]
+pub struct EntityCacheStats {
+    pub entity: String,
+    pub hits: u64,
+    pub misses: u64,
+    pub len: usize,
+    pub capacity: usize,
+}
+
+struct EntityRecords {
+    records: LruCache<String, Object>,
+    /// Bumped on each invalidation
+    generation: u64,
+    hits: u64,
+    misses: u64,
+}
+
+enum Lookup {
+    Hit(Object),
+    /// The generation to `put` the record read from the store with
+    Miss(u64),
+}
+
+pub struct EntityCache {
+    entities: HashMap<&'static str, EntityRecords>,
+}
+
+impl EntityCache {
+    pub fn new(capacity: usize) -> Self {
+        let entities = cached_entities()
+            .into_iter()
+            .map(|entity| {
+                let records = EntityRecords {
+                    records: LruCache::new(capacity),
+                    generation: 0,
+                    hits: 0,
+                    misses: 0,
+                };
+                (entity, records)
+            })
+            .collect();
+
+        EntityCache { entities }
+    }
+
+    /// None for the records of the entities not cached
+    fn lookup(&mut self, id: &str) -> Option<Lookup> {
+        let entity = self.entities.get_mut(entity_of(id))?;
+        match entity.records.get(&id.to_string()) {
+            Some(record) => {
+                entity.hits += 1;
+                Some(Lookup::Hit(record.clone()))
+            }
+            None => {
+                entity.misses += 1;
+                Some(Lookup::Miss(entity.generation))
+            }
+        }
+    }
+
+    fn put(&mut self, id: &str, record: Object, generation: u64) {
+        if let Some(entity) = self.entities.get_mut(entity_of(id)).filter(|entity| entity.generation == generation) {
+            entity.records.insert(id.to_string(), record);
+        }
+    }
+
+    fn invalidate(&mut self, entity: &str) {
+        // Note: the events of the whole store (e.g. "vault", "_migrations") drop all the records
+        let global = entity.starts_with('_') || entity == "vault";
+        for (name, records) in self.entities.iter_mut() {
+            if global || *name == entity {
+                records.records.clear();
+                records.generation += 1;
+            }
+        }
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        for records in self.entities.values_mut() {
+            records.records.set_capacity(capacity);
+        }
+    }
+
+    fn stats(&self) -> Vec<EntityCacheStats> {
+        let mut stats: Vec<EntityCacheStats> = self
+            .entities
+            .iter()
+            .map(|(entity, records)| EntityCacheStats {
+                entity: entity.to_string(),
+                hits: records.hits,
+                misses: records.misses,
+                len: records.records.len(),
+                capacity: records.records.capacity(),
+            })
+            .collect();
+        stats.sort_by(|a, b| a.entity.cmp(&b.entity));
+        stats
+    }
+}
+
+fn record_id(record: &Object) -> Option<String> {
+    match record.get("id") {
+        Some(Value::Thing(id)) => Some(id.to_raw()),
+        _ => None,
+    }
+}
+
+impl ModelStore {
+    /// The record (trashed or not), read through the entity cache
+    pub(in crate::model) async fn get_record(&self, id: &str) -> Result<Object> {
+        let lookup = self.entity_cache().lock().ok().and_then(|mut cache| cache.lookup(id));
+        let generation = match lookup {
+            Some(Lookup::Hit(record)) => return Ok(record),
+            Some(Lookup::Miss(generation)) => Some(generation),
+            None => None,
+        };
+
+        let record = self.store().exec_get(id).await?;
+        if let (Some(generation), Ok(mut cache)) = (generation, self.entity_cache().lock()) {
+            cache.put(id, record.clone(), generation);
+        }
+        Ok(record)
+    }
+
+    /// The records of the ids (trashed or not, the missing ones are left out), read through the entity
+    /// cache. The records not cached are read with a single query.
+    pub(in crate::model) async fn get_records(&self, ids: &[String]) -> Result<Vec<Object>> {
+        let mut found = HashMap::<String, Object>::new();
+        let mut generations = HashMap::<String, u64>::new();
+        let mut missing: Vec<Value> = vec![];
+        {
+            let mut cache = self.entity_cache().lock().map_err(|ex| Error::Other(ex.to_string()))?;
+            for id in ids {
+                match cache.lookup(id) {
+                    Some(Lookup::Hit(record)) => {
+                        found.insert(id.clone(), record);
+                        continue;
+                    }
+                    Some(Lookup::Miss(generation)) => {
+                        generations.insert(id.clone(), generation);
+                    }
+                    None => {}
+                }
+                missing.push(thing(id).map_err(|ex| Error::Store(ex.into()))?.into());
+            }
+        }
+
+        if !missing.is_empty() {
+            let vars = vmap!("ids".into() => missing.into());
+            let records = self.store().exec_custom_solo_query("SELECT * FROM $ids;", Some(vars.into())).await?;
+
+            let mut cache = self.entity_cache().lock().map_err(|ex| Error::Other(ex.to_string()))?;
+            for record in records {
+                let Some(id) = record_id(&record) else {
+                    continue;
+                };
+                if let Some(&generation) = generations.get(&id) {
+                    cache.put(&id, record.clone(), generation);
+                }
+                found.insert(id, record);
+            }
+        }
+
+        Ok(ids.iter().filter_map(|id| found.remove(id)).collect())
+    }
+}
+
+/// Drop the cached records of the entity, called for each model event
+pub(super) fn invalidate_entity_cache(ctx: &Ctx, entity: &str) {
+    let model_manager = ctx.get_model_manager();
+    if let Ok(mut cache) = model_manager.entity_cache().lock() {
+        cache.invalidate(entity);
+    }
+}
+
+pub struct EntityCacheBmc;
+
+impl EntityCacheBmc {
+    /// The hit/miss counters of the cached entities, since the app started
+    pub fn stats(ctx: Arc<Ctx>) -> Result<Vec<EntityCacheStats>> {
+        let model_manager = ctx.get_model_manager();
+        let cache = model_manager.entity_cache().lock().map_err(|ex| Error::Other(ex.to_string()))?;
+        Ok(cache.stats())
+    }
+
+    /// Apply the `CacheSettings::entity_cache_size` of the settings
+    pub fn resize(ctx: Arc<Ctx>) -> Result<()> {
+        let capacity = ctx.get_settings().cache.entity_cache_size as usize;
+        let model_manager = ctx.get_model_manager();
+        let mut cache = model_manager.entity_cache().lock().map_err(|ex| Error::Other(ex.to_string()))?;
+        cache.set_capacity(capacity);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str) -> Object {
+        let mut record = Object::default();
+        record.insert("id".into(), Value::Thing(thing(id).unwrap()));
+        record
+    }
+
+    #[test]
+    fn test_entity_cache_generation() {
+        let mut cache = EntityCache::new(8);
+        assert!(cache.lookup("picture:a").is_none());
+
+        let Some(Lookup::Miss(generation)) = cache.lookup("tag:a") else {
+            panic!("tag:a should be a miss");
+        };
+        // -- Invalidated while read from the store
+        cache.invalidate("tag");
+        cache.put("tag:a", record("tag:a"), generation);
+        assert!(matches!(cache.lookup("tag:a"), Some(Lookup::Miss(_))));
+
+        cache.put("tag:a", record("tag:a"), generation + 1);
+        assert!(matches!(cache.lookup("tag:a"), Some(Lookup::Hit(_))));
+
+        let tag = cache.stats().into_iter().find(|stats| stats.entity == "tag").unwrap();
+        assert_eq!((tag.hits, tag.misses, tag.len), (1, 2, 1));
+    }
+}
@@ -2,16 +2,81 @@ use crate::model::store;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+/// Unique index on the (folder, title, dtime) of the documents, see the `0003_document_folder` migration
+pub(super) const DOCUMENT_TITLE_INDEX: &str = "document_title_unique";
+/// Unique indexes on the (parent, name, dtime) of the folders and the (name, dtime) of the categories
+pub(super) const NAME_INDEXES: &[&str] = &["documents_folder_name_unique", "category_name_unique"];
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("")]
     CtxFail,
     #[error("{0}")]
-    Store(#[from] store::Error),
+    Store(store::Error),
     #[error("surreal-qb ERROR: {0}")]
     QB(#[from] surreal_qb::Error),
     #[error("{0}")]
     ParseError(#[from] magic_utils::ParseError),
+    #[error("A document with the same title already exists in this folder: {0}")]
+    TitleConflict(String),
+    #[error("The name is already taken: {0}")]
+    NameConflict(String),
+    #[error("Invalid document body: {0}")]
+    InvalidBody(String),
+    #[error("Unknown tag or category: {0}")]
+    UnknownReference(String),
+    #[error("Invalid hierarchy: {0}")]
+    InvalidHierarchy(String),
+    #[error("Invalid picture edit: {0}")]
+    InvalidEdit(String),
+    #[error("Invalid or outdated page cursor: {0}")]
+    InvalidCursor(String),
+    #[error("Record not found: {0}")]
+    RecordNotFound(String),
+    #[error("Record already in the trash: {0}")]
+    AlreadyTrashed(String),
     #[error("{0}")]
     Other(String),
 }
+
+impl Error {
+    /// Stable code of the error kinds the client has to handle (sent with the `IpcError`)
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            Error::TitleConflict(_) => Some("TitleConflict"),
+            Error::NameConflict(_) => Some("NameConflict"),
+            Error::InvalidBody(_) => Some("InvalidBody"),
+            Error::UnknownReference(_) => Some("UnknownReference"),
+            Error::InvalidHierarchy(_) => Some("InvalidHierarchy"),
+            Error::InvalidEdit(_) => Some("InvalidEdit"),
+            Error::InvalidCursor(_) => Some("InvalidCursor"),
+            Error::RecordNotFound(_) => Some("RecordNotFound"),
+            Error::AlreadyTrashed(_) => Some("AlreadyTrashed"),
+            _ => None,
+        }
+    }
+}
+
+impl From<store::Error> for Error {
+    fn from(err: store::Error) -> Self {
+        match &err {
+            store::Error::Surreal(surrealdb::Error::Db(surrealdb::error::Db::IndexExists { index, value, .. })) => {
+                if index == DOCUMENT_TITLE_INDEX {
+                    return Error::TitleConflict(value.clone());
+                }
+                if NAME_INDEXES.contains(&index.as_str()) {
+                    return Error::NameConflict(value.clone());
+                }
+            }
+            // Note: Thrown by the `StoreTransaction` merges of a missing record
+            store::Error::Surreal(surrealdb::Error::Db(surrealdb::error::Db::Thrown(message))) => {
+                if let Some(id) = message.strip_prefix(store::RECORD_NOT_FOUND) {
+                    return Error::RecordNotFound(id.to_string());
+                }
+            }
+            _ => {}
+        }
+
+        Error::Store(err)
+    }
+}
@@ -0,0 +1,130 @@
+//! Push of the store changes to the windows with SurrealDB live queries.
+//!
+//! A `LIVE SELECT` is registered on every table (the relation tables included), so the writes are reported
+//! whatever made them (`bmc_*` helpers, custom queries, graph execs, another window).
+//! Each notification is emitted as a "Live" `HubEvent`: the topic is the table, the label the `LiveAction`
+//! and the data the full record. A window only receives the topics it subscribed to (see `HubSubscriptions`).
+
+use super::bmc_base::Bmc;
+use super::bmc_graph::GraphBmc;
+use crate::event::HubEvent;
+use crate::model::ctx::Ctx;
+use crate::model::{
+    CategoryBmc, DocumentBmc, DocumentLinkBmc, DocumentRevisionBmc, DocumentsFolderBmc,
+    DocumentsTemplateBmc, Error, PictureBmc, Result, TagBmc,
+};
+use crate::prelude::f;
+use futures::StreamExt;
+use serde::Serialize;
+use std::sync::Arc;
+use surrealdb::Action;
+use ts_gen::TS;
+
+pub const LIVE_HUB: &str = "Live";
+
+/// Tables pushed to the windows, i.e. the topics of the "Live" hub
+pub fn live_tables() -> [&'static str; 11] {
+    [
+        DocumentBmc::ENTITY,
+        DocumentRevisionBmc::ENTITY,
+        DocumentsFolderBmc::ENTITY,
+        DocumentsTemplateBmc::ENTITY,
+        PictureBmc::ENTITY,
+        TagBmc::ENTITY,
+        CategoryBmc::ENTITY,
+        DocumentsFolderBmc::RELATION_ENTITY,
+        CategoryBmc::RELATION_ENTITY,
+        TagBmc::RELATION_ENTITY,
+        DocumentLinkBmc::RELATION_ENTITY,
+    ]
+}
+
+#[derive(Debug, Serialize, TS, Clone, Copy, PartialEq)]
+#[ts(export)]
+#[serde(rename_all = "lowercase")]
+pub enum LiveAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl LiveAction {
+    fn label(&self) -> &'static str {
+        match self {
+            LiveAction::Create => "create",
+            LiveAction::Update => "update",
+            LiveAction::Delete => "delete",
+        }
+    }
+}
+
+pub struct LiveBmc;
+
+impl LiveBmc {
+    fn check_topic(topic: &str) -> Result<()> {
+        if live_tables().contains(&topic) {
+            Ok(())
+        } else {
+            Err(Error::Other(f!("'{topic}' is not a live topic")))
+        }
+    }
+
+    /// Start the live queries of all the tables, in the background
+    pub fn start(ctx: Arc<Ctx>) {
+        for tb in live_tables() {
+            let ctx = ctx.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(err) = Self::forward(ctx, tb).await {
+                    error!("Live query of '{tb}' stopped: {err}");
+                }
+            });
+        }
+    }
+
+    /// Emit the notifications of the live query of the table until the store is closed
+    async fn forward(ctx: Arc<Ctx>, tb: &'static str) -> Result<()> {
+        let model_manager = ctx.get_model_manager();
+        let mut notifications = model_manager.store().exec_live_select(tb).await?;
+
+        while let Some(notification) = notifications.next().await {
+            let action = match notification.action {
+                Action::Create => LiveAction::Create,
+                Action::Update => LiveAction::Update,
+                Action::Delete => LiveAction::Delete,
+                #[allow(unreachable_patterns)]
+                _ => continue,
+            };
+
+            ctx.emit_hub_event_to_subscribers(HubEvent {
+                hub: LIVE_HUB.to_string(),
+                topic: tb.to_string(),
+                label: Some(action.label().to_string()),
+                data: Some(notification.data.into_json()),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Subscribe the window to the topic. Returns false if it was already subscribed.
+    pub fn subscribe(ctx: Arc<Ctx>, window: &str, topic: &str) -> Result<bool> {
+        Self::check_topic(topic)?;
+        let subscriptions = ctx.subscriptions();
+        let mut subscriptions = subscriptions
+            .lock()
+            .map_err(|ex| Error::Other(ex.to_string()))?;
+
+        Ok(subscriptions.subscribe(topic, window))
+    }
+
+    /// Unsubscribe the window from the topic. Returns false if it wasn't subscribed.
+    pub fn unsubscribe(ctx: Arc<Ctx>, window: &str, topic: &str) -> Result<bool> {
+        Self::check_topic(topic)?;
+        let subscriptions = ctx.subscriptions();
+        let mut subscriptions = subscriptions
+            .lock()
+            .map_err(|ex| Error::Other(ex.to_string()))?;
+
+        Ok(subscriptions.unsubscribe(topic, window))
+    }
+}
@@ -0,0 +1,431 @@
+//! Markdown folder import and export (e.g. an Obsidian vault).
+//!
+//! The directories become `DocumentsFolder`s and the `.md` files become Markdown `Document`s,
+//! attached with `documentsFolders` edges that mirror the directory layout.
+//! The YAML front matter `tags` and `categories` are mapped by name to the existing tags and categories
+//! (the missing ones are created), and `title` overrides the file name.
+//!
+//! The export writes the `DocumentsFolderTree` back to disk the same way. Existing files are never overwritten.
+
+use super::bmc_base::{bmc_update, Bmc};
+use super::store::x_take::XTake;
+use super::vmap;
+use crate::fs::{free_file_path, list_files_recursively, path_to_string, sanitize_file_name};
+use crate::model::ctx::Ctx;
+use crate::model::{
+    CategoryBmc, CategoryForCreate, Document, DocumentBmc, DocumentForCreate, DocumentForUpdate,
+    DocumentLinkBmc, DocumentType, DocumentsFolderBmc, DocumentsFolderChild,
+    DocumentsFolderForCreate, Error, Result, TagBmc, TagForCreate,
+};
+use crate::prelude::f;
+use itertools::Itertools;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use ts_gen::TS;
+
+const MARKDOWN_EXTENSION: &str = "md";
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub categories: Vec<String>,
+}
+
+impl FrontMatter {
+    fn is_empty(&self) -> bool {
+        self.title.is_none() && self.tags.is_empty() && self.categories.is_empty()
+    }
+}
+
+#[derive(Serialize)]
+struct FrontMatterOut<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    categories: &'a [String],
+}
+
+/// Tags and categories can be either a YAML list or a comma separated string (`#` prefixes are dropped)
+fn yaml_names(value: Option<&serde_yaml::Value>) -> Vec<String> {
+    let raw: Vec<String> = match value {
+        Some(serde_yaml::Value::String(s)) => s.split(',').map(str::to_string).collect(),
+        Some(serde_yaml::Value::Sequence(values)) => values
+            .iter()
+            .filter_map(|v| match v {
+                serde_yaml::Value::String(s) => Some(s.clone()),
+                serde_yaml::Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    };
+
+    raw.iter()
+        .map(|name| name.trim().trim_start_matches('#').trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unique()
+        .collect()
+}
+
+fn parse_front_matter(yaml: &str) -> FrontMatter {
+    let mapping = match serde_yaml::from_str::<serde_yaml::Value>(yaml) {
+        Ok(serde_yaml::Value::Mapping(mapping)) => mapping,
+        Ok(_) => return FrontMatter::default(),
+        Err(ex) => {
+            warn!("Invalid YAML front matter: {ex}");
+            return FrontMatter::default();
+        }
+    };
+
+    FrontMatter {
+        title: mapping
+            .get("title")
+            .and_then(|title| title.as_str())
+            .map(|title| title.trim().to_string())
+            .filter(|title| !title.is_empty()),
+        tags: yaml_names(mapping.get("tags")),
+        categories: yaml_names(mapping.get("categories")),
+    }
+}
+
+/// Split the YAML front matter (delimited by `---` lines) from the Markdown body
+pub fn split_front_matter(text: &str) -> (FrontMatter, &str) {
+    let content = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (FrontMatter::default(), text);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            let front_matter = parse_front_matter(&rest[..offset]);
+            return (front_matter, &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+
+    (FrontMatter::default(), text)
+}
+
+/// Prepend the front matter (if not empty) to the Markdown body
+pub fn render_markdown(front_matter: &FrontMatter, body: &str) -> String {
+    if front_matter.is_empty() {
+        return body.to_string();
+    }
+
+    let out = FrontMatterOut {
+        title: front_matter.title.as_deref(),
+        tags: &front_matter.tags,
+        categories: &front_matter.categories,
+    };
+    let yaml = serde_yaml::to_string(&out).unwrap_or_default();
+
+    f!("---\n{yaml}---\n{body}")
+}
+
+#[derive(Debug, Serialize, Default, TS, Clone)]
+#[ts(export)]
+pub struct MarkdownImportReport {
+    /// Created folders, an existing folder with the same name in the same parent is reused
+    pub folders: usize,
+    pub documents: usize,
+    pub tags_created: usize,
+    pub categories_created: usize,
+    /// Paths of the files that are not Markdown or can't be read
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Default, TS, Clone)]
+#[ts(export)]
+pub struct MarkdownExportReport {
+    pub folders: usize,
+    pub documents: usize,
+    /// Ids of the documents that are not Markdown
+    pub skipped: Vec<String>,
+}
+
+pub struct MarkdownBmc;
+
+impl MarkdownBmc {
+    /// Name -> id of the alive records of the table (tags or categories)
+    async fn names_to_ids(ctx: Arc<Ctx>, tb: &str) -> Result<HashMap<String, String>> {
+        let sql = "SELECT id, name FROM type::table($tb) WHERE dtime IS NONE;";
+        let vars = vmap!("tb".into() => tb.into());
+        let objects = ctx
+            .get_model_manager()
+            .store()
+            .exec_custom_solo_query(sql, Some(vars.into()))
+            .await?;
+
+        let mut names = HashMap::<String, String>::new();
+        for mut obj in objects {
+            let id: String = obj.x_take_val("id")?;
+            let name: String = obj.x_take_val("name")?;
+            names.entry(name).or_insert(id);
+        }
+
+        Ok(names)
+    }
+
+    async fn resolve_tags(
+        ctx: Arc<Ctx>,
+        names: &[String],
+        ids: &mut HashMap<String, String>,
+        created: &mut usize,
+    ) -> Result<Vec<String>> {
+        let mut resolved = vec![];
+        for name in names {
+            if !ids.contains_key(name) {
+                let tag = TagBmc::create(
+                    ctx.clone(),
+                    TagForCreate {
+                        name: name.clone(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+                ids.insert(name.clone(), tag.id);
+                *created += 1;
+            }
+            resolved.push(ids[name].clone());
+        }
+
+        Ok(resolved)
+    }
+
+    async fn resolve_categories(
+        ctx: Arc<Ctx>,
+        names: &[String],
+        ids: &mut HashMap<String, String>,
+        created: &mut usize,
+    ) -> Result<Vec<String>> {
+        let mut resolved = vec![];
+        for name in names {
+            if !ids.contains_key(name) {
+                let category =
+                    CategoryBmc::create(ctx.clone(), CategoryForCreate { name: name.clone() }).await?;
+                ids.insert(name.clone(), category.id);
+                *created += 1;
+            }
+            resolved.push(ids[name].clone());
+        }
+
+        Ok(resolved)
+    }
+
+    /// Import the directory as folders and Markdown documents.
+    /// Hidden files and directories (e.g. `.obsidian`) are ignored.
+    pub async fn import_folder(ctx: Arc<Ctx>, path: &str) -> Result<MarkdownImportReport> {
+        let root = Path::new(path);
+        if !root.is_dir() {
+            return Err(Error::Other(f!("'{path}' is not a directory")));
+        }
+
+        let mut report = MarkdownImportReport::default();
+        let mut tag_ids = Self::names_to_ids(ctx.clone(), TagBmc::ENTITY).await?;
+        let mut category_ids = Self::names_to_ids(ctx.clone(), CategoryBmc::ENTITY).await?;
+        let mut folder_ids = HashMap::<PathBuf, String>::new();
+        let mut imported = Vec::<(String, String)>::new();
+
+        for entry in list_files_recursively(path, true) {
+            let entry_path = PathBuf::from(&entry);
+            let Ok(relative) = entry_path.strip_prefix(root) else {
+                continue;
+            };
+            if relative.as_os_str().is_empty()
+                || relative
+                    .components()
+                    .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+            {
+                continue;
+            }
+
+            // Note: the walker lists the directories before their content
+            let parent_id = relative.parent().and_then(|p| folder_ids.get(p)).cloned();
+            if entry_path.is_dir() {
+                let name = entry_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                // Note: The folder names are unique per parent, an existing folder is merged into
+                let folder = match DocumentsFolderBmc::find_child(ctx.clone(), parent_id.as_deref(), &name).await? {
+                    Some(folder) => folder,
+                    None => {
+                        report.folders += 1;
+                        let data = DocumentsFolderForCreate { name, parent: parent_id.clone() };
+                        DocumentsFolderBmc::create(ctx.clone(), data).await?
+                    }
+                };
+                folder_ids.insert(relative.to_path_buf(), folder.id);
+                continue;
+            }
+
+            let is_markdown = entry_path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(MARKDOWN_EXTENSION));
+            if !is_markdown {
+                report.skipped.push(entry);
+                continue;
+            }
+
+            let text = match fs::read_to_string(&entry_path) {
+                Ok(text) => text,
+                Err(ex) => {
+                    warn!("Can't read the Markdown file {entry}: {ex}");
+                    report.skipped.push(entry);
+                    continue;
+                }
+            };
+            let (front_matter, body) = split_front_matter(&text);
+
+            let tags = Self::resolve_tags(
+                ctx.clone(),
+                &front_matter.tags,
+                &mut tag_ids,
+                &mut report.tags_created,
+            )
+            .await?;
+            let categories = Self::resolve_categories(
+                ctx.clone(),
+                &front_matter.categories,
+                &mut category_ids,
+                &mut report.categories_created,
+            )
+            .await?;
+
+            let title = front_matter.title.clone().unwrap_or_else(|| {
+                entry_path.file_stem().unwrap_or_default().to_string_lossy().to_string()
+            });
+            let data = DocumentForCreate {
+                title,
+                r#type: DocumentType::Markdown,
+                folder: parent_id.clone(),
+                body: None,
+            };
+            let document = match DocumentBmc::create(ctx.clone(), data).await {
+                Ok(document) => document,
+                Err(Error::TitleConflict(title)) => {
+                    warn!("A document titled '{title}' already exists in the folder of {entry}");
+                    report.skipped.push(entry);
+                    continue;
+                }
+                Err(ex) => return Err(ex),
+            };
+
+            // Note: `bmc_update` instead of `DocumentBmc::update`, an imported document has no revision to record
+            let data = DocumentForUpdate {
+                title: None,
+                body: Some(body.to_string()),
+                tags: Some(tags).filter(|tags| !tags.is_empty()),
+                categories: Some(categories).filter(|categories| !categories.is_empty()),
+                used_pics: None,
+                r#type: None,
+            };
+            bmc_update::<Document, _>(ctx.clone(), DocumentBmc::ENTITY, &document.id, data).await?;
+
+            imported.push((document.id, body.to_string()));
+            report.documents += 1;
+        }
+
+        // Note: synced once all the documents exist, so the links between imported documents resolve
+        for (id, body) in imported {
+            DocumentLinkBmc::sync_links(ctx.clone(), &id, &body).await?;
+        }
+
+        Ok(report)
+    }
+
+    fn export_children(
+        dir: &Path,
+        children: &[DocumentsFolderChild],
+        tag_names: &HashMap<String, String>,
+        category_names: &HashMap<String, String>,
+        report: &mut MarkdownExportReport,
+    ) -> Result<()> {
+        let io_error = |path: &Path, ex: std::io::Error| Error::Other(f!("{}: {ex}", path.display()));
+        let names = |ids: &Option<Vec<String>>, lookup: &HashMap<String, String>| -> Vec<String> {
+            ids.iter().flatten().filter_map(|id| lookup.get(id).cloned()).collect()
+        };
+
+        for child in children {
+            match child {
+                DocumentsFolderChild::DocumentsFolder(folder) => {
+                    let sub_dir = free_file_path(dir, &sanitize_file_name(&folder.name));
+                    fs::create_dir_all(&sub_dir).map_err(|ex| io_error(&sub_dir, ex))?;
+                    report.folders += 1;
+
+                    Self::export_children(&sub_dir, &folder.children, tag_names, category_names, report)?;
+                }
+                // Note: `list_tree` fills the body of every document, a summary left was trashed meanwhile
+                DocumentsFolderChild::DocumentSummary(summary) => report.skipped.push(summary.id.clone()),
+                DocumentsFolderChild::Document(document) => {
+                    if document.r#type != DocumentType::Markdown {
+                        report.skipped.push(document.id.clone());
+                        continue;
+                    }
+
+                    let file_name = sanitize_file_name(&document.title);
+                    let file_path = free_file_path(dir, &f!("{file_name}.{MARKDOWN_EXTENSION}"));
+                    let front_matter = FrontMatter {
+                        title: Some(document.title.clone()).filter(|title| title.ne(&file_name)),
+                        tags: names(&document.tags, tag_names),
+                        categories: names(&document.categories, category_names),
+                    };
+                    let text = render_markdown(&front_matter, document.body.as_deref().unwrap_or_default());
+                    fs::write(&file_path, text).map_err(|ex| io_error(&file_path, ex))?;
+                    report.documents += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the folders tree to the directory as nested folders of Markdown files
+    pub async fn export_folder(ctx: Arc<Ctx>, path: &str) -> Result<MarkdownExportReport> {
+        let root = PathBuf::from(path);
+        fs::create_dir_all(&root).map_err(|ex| Error::Other(f!("{}: {ex}", path_to_string(&root))))?;
+
+        let tree = DocumentsFolderBmc::list_tree(ctx.clone()).await?;
+        let invert = |names: HashMap<String, String>| -> HashMap<String, String> {
+            names.into_iter().map(|(name, id)| (id, name)).collect()
+        };
+        let tag_names = invert(Self::names_to_ids(ctx.clone(), TagBmc::ENTITY).await?);
+        let category_names = invert(Self::names_to_ids(ctx, CategoryBmc::ENTITY).await?);
+
+        let mut report = MarkdownExportReport::default();
+        Self::export_children(&root, &tree.roots, &tag_names, &category_names, &mut report)?;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_front_matter() {
+        let text = "---\ntitle: The Empire\ntags: [history, \"#war\"]\ncategories: Nations, Places\n---\n# Body\n";
+        let (front_matter, body) = split_front_matter(text);
+
+        assert_eq!(
+            front_matter,
+            FrontMatter {
+                title: Some("The Empire".into()),
+                tags: vec!["history".into(), "war".into()],
+                categories: vec!["Nations".into(), "Places".into()],
+            }
+        );
+        assert_eq!(body, "# Body\n");
+        assert_eq!(split_front_matter("# No front matter"), (FrontMatter::default(), "# No front matter"));
+        assert_eq!(split_front_matter(&render_markdown(&front_matter, body)), (front_matter, body));
+    }
+}
@@ -0,0 +1,25 @@
+//! Controller for the store schema migrations (see `store::migrations`).
+//!
+//! The pending migrations are applied when the store is created, this controller
+//! allows to check them (dry run) or to apply them again without a restart.
+
+use super::fire_model_event;
+use crate::model::ctx::Ctx;
+use crate::model::store::MigrationReport;
+use crate::model::Result;
+use std::sync::Arc;
+
+pub struct MigrationBmc;
+
+impl MigrationBmc {
+    /// Apply the pending migrations, or only report them if `dry_run` is set
+    pub async fn migrate(ctx: Arc<Ctx>, dry_run: bool) -> Result<MigrationReport> {
+        let report = ctx.get_model_manager().store().exec_migrate(dry_run).await?;
+
+        if !dry_run && !report.migrations.is_empty() {
+            fire_model_event(&ctx, "_migrations", "migrate", report.clone());
+        }
+
+        Ok(report)
+    }
+}
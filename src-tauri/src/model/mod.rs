@@ -16,23 +16,61 @@ mod bmc_base;
 mod bmc_graph;
 pub mod ctx;
 mod document;
+mod document_body;
+mod document_link;
+mod document_revision;
 mod documents_folder;
 mod documents_template;
+mod entity_cache;
 mod error;
+mod live;
+mod markdown;
+mod migration;
 mod model_store;
+mod name_allocator;
+mod page;
 mod picture;
+mod picture_asset;
+mod picture_edit;
+mod picture_similarity;
+mod picture_watch;
+mod search;
 mod seed_for_dev;
 mod store;
 mod tags_and_categories;
+mod template_schema;
+mod trash;
+mod tree_index;
+mod tree_repair;
+mod vault;
 
 // --- Re-exports
 pub use document::*;
+pub use document_body::*;
+pub use document_link::*;
+pub use document_revision::*;
 pub use documents_folder::*;
 pub use documents_template::*;
+pub use entity_cache::*;
 pub use error::{Error, Result};
+pub use live::*;
+pub use markdown::*;
+pub use migration::*;
 pub use model_store::*;
+pub use page::*;
 pub use picture::*;
+pub use picture_asset::*;
+pub use picture_edit::*;
+pub use picture_similarity::*;
+pub use picture_watch::*;
+pub use search::SearchHit;
+pub use store::{MigrationInfo, MigrationReport};
 pub use tags_and_categories::*;
+pub use template_schema::*;
+pub use trash::*;
+pub use tree_index::*;
+pub use tree_repair::*;
+pub use vault::*;
 // For dev only
 pub use seed_for_dev::seed_store_for_dev;
 
@@ -40,6 +78,8 @@ fn fire_model_event<D>(ctx: &Ctx, entity: &str, action: &str, data: D)
 where
     D: Serialize + Clone,
 {
+    entity_cache::invalidate_entity_cache(ctx, entity);
+    tree_index::invalidate_trees(ctx, entity, action);
     ctx.emit_hub_event(HubEvent {
         hub: "Model".to_string(),
         topic: entity.to_string(),
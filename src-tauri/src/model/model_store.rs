@@ -17,20 +17,62 @@
 //! - ModelManager are designed to be passed as an argument to
 //!   all Model Controllers functions.
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use super::SurrealStore;
-use crate::model::Result;
+use crate::model::picture_asset::select_picture_assets;
+use crate::model::{EntityCache, PictureAsset, PictureAssets, PictureHashes, Result, TreeIndexes};
+use crate::AppSettings;
 
-pub struct ModelStore(SurrealStore);
+pub struct ModelStore {
+    store: SurrealStore,
+    /// The trees built by the `list_tree`s, patched by the tree mutations (see `tree_index`)
+    trees: Mutex<TreeIndexes>,
+    /// The records of the hot entities, read through by `bmc_get` (see `entity_cache`)
+    entity_cache: Mutex<EntityCache>,
+    /// The perceptual hashes of the pictures, for the similarity queries (see `picture_similarity`)
+    picture_hashes: Mutex<PictureHashes>,
+    /// The files of the pictures, for the `loreapp://` protocol (see `picture_asset`)
+    picture_assets: Mutex<PictureAssets>,
+}
 pub type ModelStoreState = Arc<ModelStore>;
 
 impl ModelStore {
     /// Create a new ModelStore instance and its corresponding SurrealStore
     pub async fn new() -> Result<ModelStoreState> {
-        Ok(Arc::new(ModelStore(SurrealStore::new().await?)))
+        let settings = AppSettings::deserialize().unwrap_or_default();
+        let store = SurrealStore::new().await?;
+        let picture_assets = select_picture_assets(&store).await?;
+        Ok(Arc::new(ModelStore {
+            store,
+            trees: Mutex::default(),
+            entity_cache: Mutex::new(EntityCache::new(settings.cache.entity_cache_size as usize)),
+            picture_hashes: Mutex::default(),
+            picture_assets: Mutex::new(PictureAssets::new(picture_assets)),
+        }))
     }
 
     pub(in crate::model) fn store(&self) -> &SurrealStore {
-        &self.0
+        &self.store
     }
-}
\ No newline at end of file
+
+    pub(in crate::model) fn trees(&self) -> &Mutex<TreeIndexes> {
+        &self.trees
+    }
+
+    pub(in crate::model) fn entity_cache(&self) -> &Mutex<EntityCache> {
+        &self.entity_cache
+    }
+
+    pub(in crate::model) fn picture_hashes(&self) -> &Mutex<PictureHashes> {
+        &self.picture_hashes
+    }
+
+    pub(in crate::model) fn picture_assets(&self) -> &Mutex<PictureAssets> {
+        &self.picture_assets
+    }
+
+    /// The file of the alive picture, without querying the store
+    pub fn picture_asset(&self, id: &str) -> Option<PictureAsset> {
+        self.picture_assets.lock().ok()?.get(id).cloned()
+    }
+}
@@ -0,0 +1,87 @@
+//! Allocation of the default names (`untitledN`, `unnamedN`, `New Category N`).
+//!
+//! The lowest free number is picked, so the names of the deleted records are reused.
+//!
+//! Notes:
+//!     - The allocations are serialized by a process-wide lock, so two concurrent creations can't pick the same name.
+//!       The embedded store (RocksDB) is only opened by one process at a time.
+
+use super::bmc_base::bmc_create;
+use super::store::x_take::XTake;
+use super::store::Creatable;
+use super::vmap;
+use crate::model::ctx::Ctx;
+use crate::model::{Error, Result};
+use crate::prelude::f;
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use surrealdb::sql::Object;
+use tokio::sync::Mutex;
+
+static ALLOCATION_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// `{prefix}N` with the lowest N (starting at 1) not in `taken`
+pub(super) fn lowest_free_name<S: AsRef<str>>(prefix: &str, taken: &[S]) -> String {
+    let numbers: HashSet<u64> = taken
+        .iter()
+        .filter_map(|name| name.as_ref().strip_prefix(prefix))
+        .filter(|rest| !rest.starts_with('0') && !rest.starts_with('+'))
+        .filter_map(|rest| rest.parse::<u64>().ok())
+        .collect();
+
+    let number = (1..).find(|n| !numbers.contains(n)).unwrap_or(1);
+
+    f!("{prefix}{number}")
+}
+
+/// Create a record of `entity` named with the lowest free `{prefix}N`.
+/// `scope` is the `WHERE` condition of the records the name must be unique among.
+pub(super) async fn create_with_free_name<E, D>(
+    ctx: Arc<Ctx>,
+    entity: &'static str,
+    field: &str,
+    prefix: &str,
+    scope: &str,
+    make_data: impl FnOnce(String) -> D,
+) -> Result<E>
+where
+    D: Creatable + Sync + Send + DeserializeOwned + Serialize,
+    E: TryFrom<Object, Error = Error> + Sync + Send + DeserializeOwned + Serialize,
+{
+    let _guard = ALLOCATION_LOCK.lock().await;
+
+    let sql = f!("SELECT {field} FROM type::table($tb) WHERE {scope} AND string::starts_with({field}, $prefix);");
+    let vars = vmap!("tb".into() => entity.into(), "prefix".into() => prefix.into());
+    let taken = ctx
+        .get_model_manager()
+        .store()
+        .exec_custom_solo_query(sql.as_str(), Some(vars.into()))
+        .await?
+        .into_iter()
+        .map(|mut obj| obj.x_take_val::<String>(field))
+        .collect::<core::result::Result<Vec<_>, _>>()?;
+
+    let name = lowest_free_name(prefix, &taken);
+    bmc_create(ctx, entity, make_data(name)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lowest_free_name;
+
+    #[test]
+    fn picks_the_lowest_free_number() {
+        let none: [&str; 0] = [];
+        assert_eq!(lowest_free_name("untitled", &none), "untitled1");
+        assert_eq!(lowest_free_name("untitled", &["untitled1", "untitled2"]), "untitled3");
+        assert_eq!(lowest_free_name("untitled", &["untitled1", "untitled3"]), "untitled2");
+        assert_eq!(
+            lowest_free_name("untitled", &["untitled", "untitled01", "untitled2", "untitled x"]),
+            "untitled1"
+        );
+        assert_eq!(lowest_free_name("New Category ", &["New Category 1"]), "New Category 2");
+    }
+}
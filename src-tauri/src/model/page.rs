@@ -0,0 +1,233 @@
+//! Keyset pagination of the listings (`ListOptions::cursor`), on the `(order field, id)` of the records.
+//!
+//! The cursor of the next page holds the order of the listing and the values of the last record of the
+//! page: the next page starts right after that record, whatever was created or trashed meanwhile.
+//!
+//! Notes:
+//!     - A single order field is paged with cursors (the `id` breaks the ties), `id` when none.
+//!       The listings with more order fields only have the `offset` (no `next_cursor`).
+//!     - The cursors are opaque to the client (base64 of JSON), a cursor of another order is rejected.
+//!     - The `total` is only counted when asked (`ListOptions::total`), it costs one more query.
+
+use crate::model::{Error, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use surreal_qb::filter::{ListOptions, OrderBy, OrderBys};
+use surreal_qb::{Expr, SimpleExpr};
+use surrealdb::sql::{thing, Object, Value};
+use ts_gen::TS;
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// The `ListOptions::cursor` of the next page, none on the last page
+    pub next_cursor: Option<String>,
+    /// The number of records matching the filters, when asked (`ListOptions::total`)
+    pub total: Option<i64>,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            total: self.total,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Cursor {
+    field: String,
+    desc: bool,
+    /// The order field of the last record
+    value: Value,
+    /// The id of the last record
+    id: String,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(token: &str) -> Result<Cursor> {
+        let invalid = || Error::InvalidCursor(token.to_string());
+        let bytes = URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+        serde_json::from_slice(&bytes).map_err(|_| invalid())
+    }
+}
+
+/// The value at the field path of the record, `NONE` if missing
+fn field_value(record: &Object, path: &str) -> Value {
+    let mut parts = path.split('.');
+    let mut value = parts.next().and_then(|part| record.get(part));
+    for part in parts {
+        value = match value {
+            Some(Value::Object(object)) => object.get(part),
+            _ => None,
+        };
+    }
+    value.cloned().unwrap_or(Value::None)
+}
+
+/// The order of a page listing: the keyset condition of its cursor and the cursor of the next page
+pub(super) struct PageQuery {
+    /// The order field and direction, none for the listings that can't be paged with cursors
+    keyset: Option<(String, bool)>,
+    limit: usize,
+    pub(super) cond: Option<SimpleExpr>,
+}
+
+impl PageQuery {
+    /// Order the list options by `(field, id)` and read one more record (to know if there is a next page).
+    /// The list options must be finalized (`finalize_list_options`).
+    pub(super) fn new(list_options: &mut ListOptions) -> Result<PageQuery> {
+        let order_bys = list_options.order_bys.take().map(OrderBys::order_bys).unwrap_or_default();
+        let keyset = match order_bys.as_slice() {
+            [] => Some(("id".to_string(), false)),
+            [OrderBy::Asc(field)] => Some((field.clone(), false)),
+            [OrderBy::Desc(field)] => Some((field.clone(), true)),
+            _ => None,
+        };
+        let limit = list_options.limit.unwrap_or_default().max(0) as usize;
+
+        let Some((field, desc)) = keyset else {
+            if let Some(token) = list_options.cursor.take() {
+                return Err(Error::InvalidCursor(token));
+            }
+            list_options.order_bys = Some(OrderBys::new(order_bys));
+            return Ok(PageQuery { keyset: None, limit, cond: None });
+        };
+
+        let cond = match list_options.cursor.as_deref() {
+            Some(token) => {
+                let cursor = Cursor::decode(token)?;
+                if cursor.field != field || cursor.desc != desc {
+                    return Err(Error::InvalidCursor(token.to_string()));
+                }
+                Some(keyset_cond(cursor)?)
+            }
+            None => None,
+        };
+
+        let order = |col: &str| {
+            if desc {
+                OrderBy::Desc(col.to_string())
+            } else {
+                OrderBy::Asc(col.to_string())
+            }
+        };
+        let mut order_bys = vec![order(&field)];
+        if field != "id" {
+            order_bys.push(order("id"));
+        }
+        list_options.order_bys = Some(OrderBys::new(order_bys));
+        list_options.limit = Some(limit as i64 + 1);
+
+        Ok(PageQuery {
+            keyset: Some((field, desc)),
+            limit,
+            cond,
+        })
+    }
+
+    /// The page of the records read with the list options
+    pub(super) fn into_page<E>(self, mut records: Vec<Object>, total: Option<i64>) -> Result<Page<E>>
+    where
+        E: TryFrom<Object, Error = Error>,
+    {
+        let mut next_cursor = None;
+        if let Some((field, desc)) = self.keyset.filter(|_| records.len() > self.limit) {
+            records.truncate(self.limit);
+            next_cursor = records.last().map(|last| {
+                let id = match last.get("id") {
+                    Some(Value::Thing(id)) => id.to_raw(),
+                    _ => String::new(),
+                };
+                let value = field_value(last, &field);
+                Cursor { field, desc, value, id }.encode()
+            });
+        }
+
+        Ok(Page {
+            items: records.into_iter().map(E::try_from).collect::<Result<_>>()?,
+            next_cursor,
+            total,
+        })
+    }
+}
+
+/// The records after the cursor: `field > value OR (field = value AND id > id)` (`<` when descending)
+fn keyset_cond(cursor: Cursor) -> Result<SimpleExpr> {
+    let id: Value = thing(&cursor.id).map_err(|_| Error::InvalidCursor(cursor.id.clone()))?.into();
+    let after = |col: &str, value: Value| {
+        let col = Expr::col(col.to_string());
+        let value = SimpleExpr::Value(surreal_qb::Value(value));
+        if cursor.desc {
+            col.lt(value)
+        } else {
+            col.gt(value)
+        }
+    };
+
+    if cursor.field == "id" {
+        return Ok(after("id", id));
+    }
+    let same = Expr::col(cursor.field.clone()).eq(SimpleExpr::Value(surreal_qb::Value(cursor.value.clone())));
+    Ok(after(&cursor.field, cursor.value.clone()).or(same.and(after("id", id))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Title(String);
+
+    impl TryFrom<Object> for Title {
+        type Error = Error;
+        fn try_from(mut val: Object) -> Result<Title> {
+            match val.remove("title") {
+                Some(Value::Strand(title)) => Ok(Title(title.0)),
+                _ => Err(Error::Other("no title".to_string())),
+            }
+        }
+    }
+
+    fn record(id: &str, title: &str) -> Object {
+        let mut record = Object::default();
+        record.insert("id".into(), Value::Thing(thing(id).unwrap()));
+        record.insert("title".into(), Value::from(title));
+        record
+    }
+
+    #[test]
+    fn test_page_cursor() {
+        let mut list_options = ListOptions {
+            limit: Some(2),
+            order_bys: Some("!title".into()),
+            ..Default::default()
+        };
+        let query = PageQuery::new(&mut list_options).unwrap();
+        assert_eq!(list_options.limit, Some(3));
+
+        let records = vec![record("tag:c", "C"), record("tag:b", "B"), record("tag:a", "A")];
+        let page = query.into_page::<Title>(records, None).unwrap();
+        assert_eq!(page.items.iter().map(|title| title.0.as_str()).collect::<Vec<_>>(), ["C", "B"]);
+
+        let token = page.next_cursor.unwrap();
+        let cursor = Cursor::decode(&token).unwrap();
+        assert_eq!((cursor.field.as_str(), cursor.desc, cursor.id.as_str()), ("title", true, "tag:b"));
+        assert_eq!(cursor.value, Value::from("B"));
+
+        // -- A cursor of another order
+        let mut list_options = ListOptions {
+            limit: Some(2),
+            cursor: Some(token),
+            ..Default::default()
+        };
+        assert!(matches!(PageQuery::new(&mut list_options), Err(Error::InvalidCursor(_))));
+    }
+}
@@ -1,32 +1,56 @@
 //! All model and controller for the Item type
 //!
+//! The picture files are imported to the media library (see `fs::media`): `path` is the managed copy,
+//! `source` is the file it was imported from (kept in sync for the watched folders, see `picture_watch`).
+//!
 
 use super::bmc_base::{
-    bmc_create, bmc_custom_multi_query, bmc_custom_solo_query, bmc_delete, bmc_get, bmc_list,
-    bmc_update, Bmc,
+    bmc_create, bmc_custom_multi_query, bmc_custom_solo_query, bmc_get_alive, bmc_list_page,
+    bmc_update, require_fields, Bmc,
 };
+use super::picture_asset::register_picture_asset;
+use super::picture_similarity::index_picture_hash;
+use super::search::{finalize_search_limit, HIGHLIGHT_CLOSE, HIGHLIGHT_OPEN};
 use super::store::x_take::XTake;
-use super::store::{vec_to_surreal_value, Creatable, Filterable, Patchable};
+use super::tags_and_categories::{check_tags_and_categories, link_filter_ids};
+use super::store::{self, vec_to_surreal_links, Creatable, Filterable, Patchable};
 use super::{vmap, ModelMutateResultData};
-use crate::fs::read_file_as_data_url;
+use crate::fs::media::{
+    decode_data_url, dhash_to_string, find_media_file, import_media_file, sha256_hex, store_media, thumbnail_path, MediaFile,
+    THUMBNAIL_SIZES,
+};
+use crate::fs::{path_to_string, IMAGE_EXTENSIONS};
 use crate::model::ctx::Ctx;
-use crate::model::{Category, Error, Result, Tag, TagBmc};
+use crate::model::{Category, Error, Page, PictureEdits, Result, SearchHit, Tag, TagBmc, TrashBmc};
+use crate::prelude::f;
+use crate::protocol::{picture_url, thumbnail_url};
 use serde::{Deserialize, Serialize};
 use serde_with_macros::skip_serializing_none;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 use surreal_qb::filter::{
     finalize_list_options, FilterNodes, ListOptions, OpValsArray, OpValsString,
 };
 use surrealdb::sql::{thing, Object, Value};
 use ts_gen::TS;
+use walkdir::WalkDir;
 
 #[skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct PicturePrototype {
     pub id: String,
     pub ctime: String,
-    pub path: String, // TODO: this can be either a DataURL or an absolute path to the file (under development)
+    pub path: String,
+    pub source: Option<String>,
+    pub source_mtime: Option<i64>, // seconds since the UNIX epoch
+    pub hash: Option<String>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub exif: Option<String>, // JSON object of the EXIF fields
+    pub dhash: Option<String>, // hex, see `fs::media::dhash`
+    pub edits: Option<String>, // JSON of the `PictureEdits`
     pub name: Option<String>,
     pub desc: Option<String>,
     pub tags: Option<Vec<String>>,
@@ -40,6 +64,14 @@ impl TryFrom<Object> for PicturePrototype {
             id: val.x_take_val("id")?,
             ctime: val.x_take_val("ctime")?,
             path: val.x_take_val("path")?,
+            source: val.x_take("source")?,
+            source_mtime: val.x_take("source_mtime")?,
+            hash: val.x_take("hash")?,
+            width: val.x_take("width")?,
+            height: val.x_take("height")?,
+            exif: val.x_take("exif")?,
+            dhash: val.x_take("dhash")?,
+            edits: val.x_take("edits")?,
             name: val.x_take("name")?,
             desc: val.x_take("desc")?,
             tags: val.x_take("tags")?,
@@ -56,24 +88,203 @@ impl TryFrom<Object> for PicturePrototype {
 pub struct Picture {
     pub id: String,
     pub ctime: String,
-    pub path: String, // TODO: this can be either a DataURL or an absolute path to the file (under development)
+    pub path: String,
+    /// `loreapp://` URL of the file, for the webviews
+    pub url: String,
+    pub source: Option<String>,
+    pub hash: Option<String>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub exif: Option<BTreeMap<String, String>>,
+    pub dhash: Option<String>,
+    /// The non-destructive edits, served by the `url`s
+    pub edits: Option<PictureEdits>,
+    pub thumbnails: Option<Vec<PictureThumbnail>>,
     pub name: Option<String>,
     pub desc: Option<String>,
     pub tags: Option<Vec<Tag>>,
     pub categories: Option<Vec<Category>>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone)]
+#[ts(export)]
+pub struct PictureThumbnail {
+    /// Max width/height
+    pub size: u32,
+    pub path: String,
+    pub url: String,
+}
+
+/// A picture without its metadata (EXIF, edits, ...) and links, as listed in the galleries
+/// (see `PictureBmc::list_summaries`)
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone)]
+#[ts(export)]
+pub struct PictureSummary {
+    pub id: String,
+    pub name: Option<String>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    /// `loreapp://` URL of the file, for the webviews
+    pub url: String,
+    /// The smallest of the `THUMBNAIL_SIZES`, none for the pictures without thumbnails
+    pub thumbnail_url: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+impl PictureSummary {
+    /// The projection (`ListOptions::fields`) of the summaries
+    pub const FIELDS: [&'static str; 5] = ["name", "width", "height", "hash", "tags"];
+}
+
+impl TryFrom<Object> for PictureSummary {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<PictureSummary> {
+        let id: String = val.x_take_val("id")?;
+        let hash: Option<String> = val.x_take("hash")?;
+
+        Ok(PictureSummary {
+            url: picture_url(&id),
+            thumbnail_url: hash.map(|_| thumbnail_url(&id, THUMBNAIL_SIZES[0])),
+            name: val.x_take("name")?,
+            width: val.x_take("width")?,
+            height: val.x_take("height")?,
+            tags: val.x_take("tags")?,
+            id,
+        })
+    }
+}
+
+/// The record link fields of a picture, resolved (`ListOptions::expand`) by `PictureBmc::list`
+pub const PICTURE_LINKS: [&str; 2] = ["tags", "categories"];
+
+/// The fields a `Picture` can't be built without, added to the projection of `PictureBmc::list`
+const PICTURE_REQUIRED_FIELDS: [&str; 2] = ["ctime", "path"];
+
+fn build_picture(prototype: PicturePrototype, tags: Option<Vec<Tag>>, categories: Option<Vec<Category>>) -> Picture {
+    let exif = prototype.exif.and_then(|exif| serde_json::from_str(&exif).ok());
+    let thumbnails = prototype.hash.as_ref().map(|hash| {
+        THUMBNAIL_SIZES
+            .iter()
+            .map(|&size| PictureThumbnail {
+                size,
+                path: path_to_string(&thumbnail_path(hash, size)),
+                url: thumbnail_url(&prototype.id, size),
+            })
+            .collect()
+    });
+
+    Picture {
+        id: prototype.id.clone(),
+        ctime: prototype.ctime,
+        url: picture_url(&prototype.id),
+        path: prototype.path,
+        source: prototype.source,
+        hash: prototype.hash,
+        width: prototype.width,
+        height: prototype.height,
+        exif,
+        dhash: prototype.dhash,
+        edits: prototype.edits.and_then(|edits| serde_json::from_str(&edits).ok()),
+        thumbnails,
+        name: prototype.name,
+        desc: prototype.desc,
+        tags,
+        categories,
+    }
+}
+
+/// The resolved records of the links, in the links order (none when there is no link)
+fn linked<E: Clone>(links: Option<Vec<String>>, records: &[E], id: impl Fn(&E) -> &String) -> Option<Vec<E>> {
+    let links = links.filter(|links| !links.is_empty())?;
+    let by_id: HashMap<&String, &E> = records.iter().map(|record| (id(record), record)).collect();
+    Some(links.iter().filter_map(|link| by_id.get(link).map(|&record| record.clone())).collect())
+}
+
+/// Take the records of a fetched link field (`FETCH`), skipping the trashed and the dangling ones.
+/// Returns none when the field is absent, empty, or wasn't fetched (record ids).
+fn take_fetched<E>(val: &mut Object, field: &str) -> Result<Option<Vec<E>>>
+where
+    E: TryFrom<Object, Error = Error>,
+{
+    let Some(Value::Array(items)) = val.remove(field) else {
+        return Ok(None);
+    };
+
+    let mut records = vec![];
+    for item in items.0 {
+        match item {
+            Value::Object(object) => {
+                let trashed = object.get("dtime").map_or(false, |dtime| !matches!(dtime, Value::None | Value::Null));
+                if !trashed {
+                    records.push(E::try_from(object)?);
+                }
+            }
+            Value::Thing(_) => return Ok(None),
+            _ => {}
+        }
+    }
+
+    Ok(Some(records).filter(|records| !records.is_empty()))
+}
+
+/// A picture record with its `PICTURE_LINKS` fetched
+struct FetchedPicture(Picture);
+
+impl TryFrom<Object> for FetchedPicture {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<FetchedPicture> {
+        let tags = take_fetched::<Tag>(&mut val, "tags")?;
+        let categories = take_fetched::<Category>(&mut val, "categories")?;
+        let prototype = PicturePrototype::try_from(val)?;
+
+        Ok(FetchedPicture(build_picture(prototype, tags, categories)))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Default, TS)]
 #[ts(export)]
 pub struct PictureForCreate {
+    /// Absolute path of the file to import, or a base64 `data:image/...` URL (e.g. from the clipboard)
     pub path: String,
     pub name: Option<String>,
 }
 
-impl From<PictureForCreate> for Value {
-    fn from(val: PictureForCreate) -> Self {
-        let mut data = vmap!["path".into() => val.path.into()];
+/// The record of an imported media file
+#[derive(Debug, Serialize, Deserialize)]
+struct PictureForImport {
+    media: MediaFile,
+    source: Option<String>,
+    source_mtime: Option<i64>,
+    name: Option<String>,
+}
+
+fn insert_media_fields(data: &mut BTreeMap<String, Value>, media: MediaFile) {
+    data.insert("path".into(), media.path.into());
+    data.insert("hash".into(), media.hash.into());
+    data.insert("width".into(), (media.width as i64).into());
+    data.insert("height".into(), (media.height as i64).into());
+    data.insert("dhash".into(), dhash_to_string(media.dhash).into());
+    data.insert("dhash_error".into(), Value::None);
+
+    let exif = match media.exif.is_empty() {
+        true => Value::None,
+        false => serde_json::to_string(&media.exif).unwrap_or_default().into(),
+    };
+    data.insert("exif".into(), exif);
+}
 
+impl From<PictureForImport> for Value {
+    fn from(val: PictureForImport) -> Self {
+        let mut data = vmap!();
+        insert_media_fields(&mut data, val.media);
+
+        if let Some(source) = val.source {
+            data.insert("source".into(), source.into());
+        }
+        if let Some(source_mtime) = val.source_mtime {
+            data.insert("source_mtime".into(), source_mtime.into());
+        }
         if let Some(name) = val.name {
             data.insert("name".into(), name.into());
         }
@@ -82,7 +293,39 @@ impl From<PictureForCreate> for Value {
     }
 }
 
-impl Creatable for PictureForCreate {}
+impl Creatable for PictureForImport {}
+
+/// The new file of a picture (relinked, or changed in a watched folder)
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct PictureFileUpdate {
+    /// The media library copy, when it's still there
+    path: Option<String>,
+    /// The new content, imported to the media library
+    media: Option<MediaFile>,
+    source: Option<String>,
+    source_mtime: Option<i64>,
+}
+
+impl From<PictureFileUpdate> for Value {
+    fn from(val: PictureFileUpdate) -> Self {
+        let mut data = vmap!();
+        if let Some(path) = val.path {
+            data.insert("path".into(), path.into());
+        }
+        if let Some(media) = val.media {
+            insert_media_fields(&mut data, media);
+        }
+        if let Some(source) = val.source {
+            data.insert("source".into(), source.into());
+        }
+        if let Some(source_mtime) = val.source_mtime {
+            data.insert("source_mtime".into(), source_mtime.into());
+        }
+        Value::Object(data.into())
+    }
+}
+
+impl Patchable for PictureFileUpdate {}
 
 #[skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize, Default, TS)]
@@ -104,10 +347,10 @@ impl From<PictureForUpdate> for Value {
             data.insert("desc".into(), desc.into());
         }
         if let Some(tags) = val.tags {
-            data.insert("tags".into(), vec_to_surreal_value(tags.into()));
+            data.insert("tags".into(), vec_to_surreal_links(tags));
         }
         if let Some(categories) = val.categories {
-            data.insert("categories".into(), vec_to_surreal_value(categories.into()));
+            data.insert("categories".into(), vec_to_surreal_links(categories));
         }
         Value::Object(data.into())
     }
@@ -119,14 +362,31 @@ impl Patchable for PictureForUpdate {}
 pub struct PictureFilter {
     pub id: Option<OpValsString>,
     pub ctime: Option<OpValsString>,
-    pub name: Option<OpValsString>, // TODO: surrealdb full-text search
-    pub desc: Option<OpValsString>, // TODO: surrealdb full-text search
+    pub name: Option<OpValsString>, // Note: use `PictureBmc::search` for full-text search
+    pub desc: Option<OpValsString>, // Note: use `PictureBmc::search` for full-text search
     pub tags: Option<OpValsArray>,
     pub categories: Option<OpValsArray>,
 }
 
 impl Filterable for PictureFilter {}
 
+/// What `PictureBmc::import_source` did with the file
+#[derive(Debug, PartialEq)]
+pub(super) enum SourceSync {
+    Created,
+    Updated,
+    Unchanged,
+}
+
+#[derive(Debug, Serialize, Default, TS)]
+#[ts(export)]
+pub struct RelinkReport {
+    /// Ids of the pictures pointing again to an existing file
+    pub relinked: Vec<String>,
+    /// Ids of the pictures whose file wasn't found
+    pub missing: Vec<String>,
+}
+
 pub struct PictureBmc;
 
 impl Bmc for PictureBmc {
@@ -134,107 +394,346 @@ impl Bmc for PictureBmc {
 }
 
 impl PictureBmc {
-    async fn make_picture(ctx: Arc<Ctx>, prototype: PicturePrototype) -> Result<Picture> {
-        let mut picture_categories: Option<Vec<Category>> = None;
-        let mut picture_tags: Option<Vec<Tag>> = None;
-
-        if let Some(categories) = prototype.categories {
-            if !categories.is_empty() {
-                let sql = "SELECT * FROM $categories;";
-
-                let mut things: Vec<Value> = vec![];
-                for c in categories.iter() {
-                    let thing = thing(c).map_err(|ex| Error::Store(ex.into()))?;
-                    things.push(thing.into());
-                }
+    pub(super) async fn make_picture(ctx: Arc<Ctx>, prototype: PicturePrototype) -> Result<Picture> {
+        let mut pictures = Self::make_pictures(ctx, vec![prototype]).await?;
+        Ok(pictures.remove(0))
+    }
 
-                let vars = vmap!("categories".into() => things.into());
-                let result = bmc_custom_solo_query::<Category>(
-                    ctx.clone(),
-                    Self::ENTITY,
-                    sql,
-                    Some(vars.into()),
-                )
-                .await?;
-                picture_categories = Some(result);
-            }
+    /// Resolve the links of the pictures with one query per linked table
+    pub(super) async fn make_pictures(ctx: Arc<Ctx>, prototypes: Vec<PicturePrototype>) -> Result<Vec<Picture>> {
+        let categories = Self::resolve_links::<Category>(
+            ctx.clone(),
+            prototypes.iter().flat_map(|p| p.categories.iter().flatten()),
+        )
+        .await?;
+        let tags =
+            Self::resolve_links::<Tag>(ctx, prototypes.iter().flat_map(|p| p.tags.iter().flatten())).await?;
+
+        Ok(prototypes
+            .into_iter()
+            .map(|mut prototype| {
+                let picture_categories = linked(prototype.categories.take(), &categories, |c| &c.id);
+                let picture_tags = linked(prototype.tags.take(), &tags, |t| &t.id);
+                build_picture(prototype, picture_tags, picture_categories)
+            })
+            .collect())
+    }
+
+    /// The alive records of the ids (deduplicated), read through the entity cache
+    async fn resolve_links<'a, E>(ctx: Arc<Ctx>, ids: impl Iterator<Item = &'a String>) -> Result<Vec<E>>
+    where
+        E: TryFrom<Object, Error = Error>,
+    {
+        let mut seen = HashSet::new();
+        let ids: Vec<String> = ids.filter(|id| seen.insert(id.as_str())).cloned().collect();
+        if ids.is_empty() {
+            return Ok(vec![]);
         }
 
-        if let Some(tags) = prototype.tags {
-            if !tags.is_empty() {
-                let sql = "SELECT * FROM $tags;";
+        bmc_get_alive::<E>(ctx, &ids).await
+    }
 
-                let mut things: Vec<Value> = vec![];
-                for t in tags.iter() {
-                    let thing = thing(t).map_err(|ex| Error::Store(ex.into()))?;
-                    things.push(thing.into());
-                }
+    pub async fn get(ctx: Arc<Ctx>, id: &str) -> Result<Picture> {
+        let sql = "SELECT * FROM $id FETCH tags, categories;";
+        let id = thing(id).map_err(|ex| Error::Store(ex.into()))?;
+        let vars = vmap!("id".into() => id.into());
+        let result =
+            bmc_custom_solo_query::<FetchedPicture>(ctx, Self::ENTITY, sql, Some(vars.into())).await?;
+        let picture = result.into_iter().next().ok_or(Error::Store(store::Error::ResponseIsEmpty))?;
+        Ok(picture.0)
+    }
 
-                let vars = vmap!("tags".into() => things.into());
-                let result =
-                    bmc_custom_solo_query::<Tag>(ctx.clone(), Self::ENTITY, sql, Some(vars.into()))
-                        .await?;
-                picture_tags = Some(result);
-            }
+    /// Returns the created Picture struct, or the existing one if the same content was already imported
+    pub async fn create(ctx: Arc<Ctx>, data: PictureForCreate) -> Result<Picture> {
+        if data.path.starts_with("data:") {
+            Self::import_data_url(ctx, &data.path, data.name).await
+        } else {
+            Self::import_file(ctx, &data.path, data.name).await
         }
+    }
 
-        Ok(Picture {
-            id: prototype.id,
-            ctime: prototype.ctime,
-            path: prototype.path,
-            name: prototype.name,
-            desc: prototype.desc,
-            categories: picture_categories,
-            tags: picture_tags,
-        })
+    pub async fn import_file(ctx: Arc<Ctx>, path: &str, name: Option<String>) -> Result<Picture> {
+        let file_path = path.to_string();
+        let media = run_blocking(move || import_media_file(file_path)).await?;
+        let name = name.or_else(|| Path::new(path).file_stem().map(|n| n.to_string_lossy().to_string()));
+        Self::create_from_media(ctx, media, Some(path.to_string()), name).await
     }
 
-    pub async fn get(ctx: Arc<Ctx>, id: &str) -> Result<Picture> {
-        let prototype = bmc_get::<PicturePrototype>(ctx.clone(), Self::ENTITY, id).await?;
-        Self::make_picture(ctx, prototype).await
+    pub async fn import_data_url(ctx: Arc<Ctx>, data_url: &str, name: Option<String>) -> Result<Picture> {
+        let data = decode_data_url(data_url).map_err(|ex| Error::Other(ex.to_string()))?;
+        let media = run_blocking(move || store_media(&data)).await?;
+        Self::create_from_media(ctx, media, None, name).await
     }
 
-    /// Returns the created Picture struct
-    pub async fn create(ctx: Arc<Ctx>, data: PictureForCreate) -> Result<Picture> {
+    async fn create_from_media(
+        ctx: Arc<Ctx>,
+        media: MediaFile,
+        source: Option<String>,
+        name: Option<String>,
+    ) -> Result<Picture> {
+        if let Some(existing) = Self::find_by_hash(ctx.clone(), &media.hash).await? {
+            return Self::make_picture(ctx, existing).await;
+        }
+
+        let data = PictureForImport { media, source, source_mtime: None, name };
         let prototype = bmc_create::<PicturePrototype, _>(ctx.clone(), Self::ENTITY, data).await?;
+        index_picture_hash(&ctx, &prototype);
+        register_picture_asset(&ctx, &prototype);
         Self::make_picture(ctx, prototype).await
     }
 
+    pub(super) async fn find_by_hash(ctx: Arc<Ctx>, hash: &str) -> Result<Option<PicturePrototype>> {
+        let sql = "SELECT * FROM picture WHERE hash = $hash AND dtime IS NONE LIMIT 1;";
+        let vars = vmap!("hash".into() => hash.into());
+        let result = Self::custom_solo_query(ctx, sql, Some(vars.into())).await?;
+        Ok(result.into_iter().next())
+    }
+
+    /// Repair the pictures whose file is missing: the media library copy is used if it still exists,
+    /// otherwise `search_dirs` are scanned for a file with the same content (or the same name for the
+    /// pictures created before the media library), which is then imported.
+    pub async fn relink_missing_pictures(ctx: Arc<Ctx>, search_dirs: Vec<String>) -> Result<RelinkReport> {
+        let sql = "SELECT * FROM picture WHERE dtime IS NONE;";
+        let pictures = Self::custom_solo_query(ctx.clone(), sql, None).await?;
+        let missing: Vec<PicturePrototype> =
+            pictures.into_iter().filter(|p| !Path::new(&p.path).is_file()).collect();
+
+        let mut report = RelinkReport::default();
+        if missing.is_empty() {
+            return Ok(report);
+        }
+
+        let candidates = run_blocking(move || Ok(scan_picture_files(&search_dirs))).await?;
+        for picture in missing {
+            let managed = picture.hash.as_deref().and_then(find_media_file);
+            let relink = if let Some(path) = managed {
+                PictureFileUpdate {
+                    path: Some(path_to_string(&path)),
+                    ..Default::default()
+                }
+            } else {
+                let found = match picture.hash.as_deref() {
+                    Some(hash) => candidates.by_hash.get(hash),
+                    None => Path::new(&picture.path)
+                        .file_name()
+                        .and_then(|name| candidates.by_name.get(&name.to_string_lossy().to_string())),
+                };
+                let Some(found) = found.cloned() else {
+                    report.missing.push(picture.id);
+                    continue;
+                };
+
+                PictureFileUpdate {
+                    media: Some(run_blocking(move || import_media_file(found)).await?),
+                    ..Default::default()
+                }
+            };
+
+            let relinked = bmc_update::<PicturePrototype, _>(ctx.clone(), Self::ENTITY, &picture.id, relink).await?;
+            index_picture_hash(&ctx, &relinked);
+            register_picture_asset(&ctx, &relinked);
+            report.relinked.push(picture.id);
+        }
+
+        Ok(report)
+    }
+
+    /// Import the files of the pictures created before the media library (without a `hash`) to the media library,
+    /// the `loreapp://` protocol only serves the media library. The pictures whose file is missing are left
+    /// to `relink_missing_pictures`. Returns the number of imported pictures.
+    pub async fn import_legacy_pictures(ctx: Arc<Ctx>) -> Result<usize> {
+        let sql = "SELECT * FROM picture WHERE dtime IS NONE AND hash IS NONE;";
+        let pictures = Self::custom_solo_query(ctx.clone(), sql, None).await?;
+
+        let mut imported = 0;
+        for picture in pictures {
+            let path = picture.path.clone();
+            let media = run_blocking(move || {
+                if path.starts_with("data:") {
+                    store_media(&decode_data_url(&path)?)
+                } else {
+                    import_media_file(path)
+                }
+            })
+            .await;
+            let media = match media {
+                Ok(media) => media,
+                Err(err) => {
+                    warn!("The picture {} isn't imported to the media library: {err}", picture.id);
+                    continue;
+                }
+            };
+
+            let data = PictureFileUpdate {
+                media: Some(media),
+                ..Default::default()
+            };
+            let updated = bmc_update::<PicturePrototype, _>(ctx.clone(), Self::ENTITY, &picture.id, data).await?;
+            index_picture_hash(&ctx, &updated);
+            register_picture_asset(&ctx, &updated);
+            imported += 1;
+        }
+
+        Ok(imported)
+    }
+
+    /// The pictures imported from the file, or from the files of the directory
+    pub(super) async fn list_by_source(ctx: Arc<Ctx>, path: &Path) -> Result<Vec<PicturePrototype>> {
+        let sql = "SELECT * FROM picture WHERE dtime IS NONE AND source IS NOT NONE \
+        AND string::starts_with(source, $path);";
+        let vars = vmap!("path".into() => path.display().to_string().into());
+        let pictures = Self::custom_solo_query(ctx, sql, Some(vars.into())).await?;
+
+        // Note: `starts_with` on the strings also matches the siblings (e.g. `/a/b` for `/a/b2`)
+        Ok(pictures
+            .into_iter()
+            .filter(|p| p.source.as_deref().map_or(false, |source| Path::new(source).starts_with(path)))
+            .collect())
+    }
+
+    /// Create or update the picture of a file, identified by its path (the `source`).
+    /// Returns `Unchanged` when the file wasn't modified since its last import, or when its content
+    /// is already imported from another file that still exists.
+    pub(super) async fn import_source(ctx: Arc<Ctx>, path: &Path) -> Result<SourceSync> {
+        let source = path.display().to_string();
+        let source_mtime = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .map(|mtime| mtime.as_secs() as i64);
+
+        let sql = "SELECT * FROM picture WHERE source = $source AND dtime IS NONE LIMIT 1;";
+        let vars = vmap!("source".into() => source.clone().into());
+        let existing = Self::custom_solo_query(ctx.clone(), sql, Some(vars.into())).await?.into_iter().next();
+        if existing.as_ref().map_or(false, |p| p.source_mtime.is_some() && p.source_mtime == source_mtime) {
+            return Ok(SourceSync::Unchanged);
+        }
+
+        let file_path = path.to_path_buf();
+        let media = run_blocking(move || import_media_file(file_path)).await?;
+
+        if let Some(picture) = existing {
+            let changed = picture.hash.as_deref() != Some(media.hash.as_str());
+            let data = PictureFileUpdate {
+                media: changed.then_some(media),
+                source_mtime,
+                ..Default::default()
+            };
+            let updated = bmc_update::<PicturePrototype, _>(ctx.clone(), Self::ENTITY, &picture.id, data).await?;
+            if changed {
+                index_picture_hash(&ctx, &updated);
+                register_picture_asset(&ctx, &updated);
+            }
+            return Ok(if changed { SourceSync::Updated } else { SourceSync::Unchanged });
+        }
+
+        if let Some(picture) = Self::find_by_hash(ctx.clone(), &media.hash).await? {
+            // -- The same content, moved while it wasn't watched
+            let moved = picture.source.as_deref().map_or(true, |source| !Path::new(source).is_file());
+            if !moved {
+                return Ok(SourceSync::Unchanged);
+            }
+            let data = PictureFileUpdate {
+                source: Some(source),
+                source_mtime,
+                ..Default::default()
+            };
+            bmc_update::<PicturePrototype, _>(ctx, Self::ENTITY, &picture.id, data).await?;
+            return Ok(SourceSync::Updated);
+        }
+
+        let data = PictureForImport {
+            media,
+            name: path.file_stem().map(|name| name.to_string_lossy().to_string()),
+            source: Some(source),
+            source_mtime,
+        };
+        let created = bmc_create::<PicturePrototype, _>(ctx.clone(), Self::ENTITY, data).await?;
+        index_picture_hash(&ctx, &created);
+        register_picture_asset(&ctx, &created);
+        Ok(SourceSync::Created)
+    }
+
+    /// Follow a renamed (or moved) file or directory. Returns the number of updated pictures.
+    pub(super) async fn move_source(ctx: Arc<Ctx>, from: &Path, to: &Path) -> Result<usize> {
+        let pictures = Self::list_by_source(ctx.clone(), from).await?;
+        for picture in pictures.iter() {
+            let Some(relative) = picture.source.as_deref().and_then(|s| Path::new(s).strip_prefix(from).ok()) else {
+                continue;
+            };
+            let source = match relative.as_os_str().is_empty() {
+                true => to.to_path_buf(),
+                false => to.join(relative),
+            };
+            let data = PictureFileUpdate {
+                source: Some(source.display().to_string()),
+                ..Default::default()
+            };
+            bmc_update::<PicturePrototype, _>(ctx.clone(), Self::ENTITY, &picture.id, data).await?;
+        }
+
+        Ok(pictures.len())
+    }
+
     pub async fn update(ctx: Arc<Ctx>, id: &str, data: PictureForUpdate) -> Result<Picture> {
+        check_tags_and_categories(ctx.clone(), data.tags.as_deref(), data.categories.as_deref()).await?;
         let prototype =
             bmc_update::<PicturePrototype, _>(ctx.clone(), Self::ENTITY, id, data).await?;
         Self::make_picture(ctx, prototype).await
     }
 
     pub async fn delete(ctx: Arc<Ctx>, id: &str) -> Result<Picture> {
-        let prototype = bmc_delete::<PicturePrototype>(ctx.clone(), Self::ENTITY, id).await?;
+        let prototype = TrashBmc::trash::<PicturePrototype>(ctx.clone(), Self::ENTITY, id).await?;
         Self::make_picture(ctx, prototype).await
     }
 
+    async fn prepare_filter(ctx: Arc<Ctx>, filter: &mut Option<Vec<PictureFilter>>) -> Result<()> {
+        for filter in filter.iter_mut().flatten() {
+            TagBmc::expand_filter(ctx.clone(), &mut filter.tags).await?;
+            link_filter_ids(&mut filter.tags);
+            link_filter_ids(&mut filter.categories);
+        }
+        Ok(())
+    }
+
+    /// With `ListOptions::fields`, the fields not selected are left out
     pub async fn list(
         ctx: Arc<Ctx>,
         filter: Option<Vec<PictureFilter>>,
         list_options: Option<ListOptions>,
-    ) -> Result<Vec<Picture>> {
-        let list_options = finalize_list_options(list_options)?;
-        let prototypes =
-            bmc_list::<PicturePrototype, _>(ctx.clone(), Self::ENTITY, filter, list_options)
-                .await?;
-        let mut result: Vec<Picture> = vec![];
-        for p in prototypes.iter() {
-            let pic = Self::make_picture(ctx.clone(), p.clone()).await?;
-            result.push(pic);
-        }
-        Ok(result)
+    ) -> Result<Page<Picture>> {
+        let mut filter = filter;
+        Self::prepare_filter(ctx.clone(), &mut filter).await?;
+        let mut list_options = finalize_list_options(list_options)?;
+        require_fields(&mut list_options, &PICTURE_REQUIRED_FIELDS);
+        list_options.expand.get_or_insert_with(|| PICTURE_LINKS.map(String::from).to_vec());
+
+        // Note: the links not expanded are left out (`None`) rather than resolved one picture at a time
+        let pictures =
+            bmc_list_page::<FetchedPicture, _>(ctx, Self::ENTITY, filter, list_options).await?;
+        Ok(pictures.map(|p| p.0))
     }
 
+    /// Same as `list`, with the summaries only (the `ListOptions::fields` and `expand` are replaced)
+    pub async fn list_summaries(
+        ctx: Arc<Ctx>,
+        filter: Option<Vec<PictureFilter>>,
+        list_options: Option<ListOptions>,
+    ) -> Result<Page<PictureSummary>> {
+        let mut filter = filter;
+        Self::prepare_filter(ctx.clone(), &mut filter).await?;
+        let mut list_options = finalize_list_options(list_options)?;
+        list_options.fields = Some(PictureSummary::FIELDS.map(String::from).to_vec());
+        list_options.expand = None;
+        bmc_list_page(ctx, Self::ENTITY, filter, list_options).await
+    }
+
+    /// The picture with its `loreapp://` URL as `path`
     pub async fn get_with_url(ctx: Arc<Ctx>, id: &str) -> Result<Picture> {
         let pic = Self::get(ctx, id).await?;
-        let data_url =
-            read_file_as_data_url(pic.path).map_err(|ex| Error::Other(ex.to_string()))?;
 
         Ok(Picture {
-            path: data_url,
+            path: pic.url.clone(),
             ..pic
         })
     }
@@ -243,17 +742,35 @@ impl PictureBmc {
         ctx: Arc<Ctx>,
         filter: Option<Vec<PictureFilter>>,
         list_options: Option<ListOptions>,
-    ) -> Result<Vec<Picture>> {
+    ) -> Result<Page<Picture>> {
         let mut pics = Self::list(ctx, filter, list_options).await?;
 
-        for pic in &mut pics {
-            pic.path =
-                read_file_as_data_url(&pic.path).map_err(|ex| Error::Other(ex.to_string()))?;
+        for pic in &mut pics.items {
+            pic.path = pic.url.clone();
         }
 
         Ok(pics)
     }
 
+    /// Full-text search over the name and description, ranked by BM25 score
+    pub async fn search(ctx: Arc<Ctx>, query: &str, limit: Option<i64>) -> Result<Vec<SearchHit>> {
+        let limit = finalize_search_limit(limit)?;
+        let sql = "SELECT id, name ?? path AS title, \
+        (search::score(0) ?? 0) + (search::score(1) ?? 0) AS score, \
+        search::highlight($open, $close, 0) AS title_highlight, \
+        search::highlight($open, $close, 1) AS content_highlight \
+        FROM picture WHERE (name @0@ $query OR desc @1@ $query) AND dtime IS NONE \
+        ORDER BY score DESC LIMIT $limit;";
+        let vars = vmap!(
+            "query".into() => query.into(),
+            "limit".into() => limit.into(),
+            "open".into() => HIGHLIGHT_OPEN.into(),
+            "close".into() => HIGHLIGHT_CLOSE.into(),
+        );
+
+        bmc_custom_solo_query::<SearchHit>(ctx, Self::ENTITY, sql, Some(vars.into())).await
+    }
+
     pub async fn get_document_used_in(ctx: Arc<Ctx>, id: &str) -> Result<Vec<String>> {
         let sql = "SELECT id FROM type::table($tb) WHERE $id IN used_pics;";
         let vars = vmap!["tb".into() => "document".into(), "id".into() => id.into()];
@@ -285,6 +802,56 @@ impl PictureBmc {
     }
 }
 
+/// The picture files found under the search directories of `relink_missing_pictures`
+#[derive(Default)]
+struct PictureCandidates {
+    by_hash: HashMap<String, String>,
+    by_name: HashMap<String, String>,
+}
+
+fn scan_picture_files(dirs: &[String]) -> PictureCandidates {
+    let mut candidates = PictureCandidates::default();
+    for path in dirs.iter().flat_map(picture_files) {
+        let Ok(data) = std::fs::read(&path) else {
+            continue;
+        };
+
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let path = path.display().to_string();
+        candidates.by_name.entry(name).or_insert_with(|| path.clone());
+        candidates.by_hash.entry(sha256_hex(&data)).or_insert(path);
+    }
+
+    candidates
+}
+
+pub(super) fn is_picture_file(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |ext| IMAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+}
+
+/// The picture files under the directory, recursively
+pub(super) fn picture_files<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && is_picture_file(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Run the file work (hashing, decoding, thumbnails) out of the async runtime
+pub(super) async fn run_blocking<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> crate::Result<T> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|ex| Error::Other(ex.to_string()))?
+        .map_err(|ex| Error::Other(ex.to_string()))
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use serde_json::json;
@@ -0,0 +1,109 @@
+//! The files of the pictures, for the `loreapp://` protocol (see `protocol`).
+//!
+//! Notes:
+//!     - The protocol handler runs on the webview thread: it reads the `PictureAssets` cached by the `ModelStore`
+//!       and never waits on the store.
+//!     - The assets of the alive pictures are loaded with the `ModelStore`, then patched when a picture is
+//!       created, re-imported, edited or trashed (reloaded on restore and vault import).
+
+use super::store::x_take::XTake;
+use super::SurrealStore;
+use crate::model::ctx::Ctx;
+use crate::model::{PictureEdits, PicturePrototype, Result};
+use std::collections::HashMap;
+use surrealdb::sql::Object;
+
+/// The file of a picture, and what its render depends on
+#[derive(Debug, Clone)]
+pub struct PictureAsset {
+    pub path: String,
+    pub hash: Option<String>,
+    pub edits: Option<PictureEdits>,
+}
+
+impl From<&PicturePrototype> for PictureAsset {
+    fn from(val: &PicturePrototype) -> Self {
+        PictureAsset {
+            path: val.path.clone(),
+            hash: val.hash.clone(),
+            edits: val.edits.as_deref().and_then(|edits| serde_json::from_str(edits).ok()),
+        }
+    }
+}
+
+/// The `PictureAsset`s of the alive pictures by id, cached by the `ModelStore`
+#[derive(Default)]
+pub struct PictureAssets {
+    assets: HashMap<String, PictureAsset>,
+    /// Bumped by every change, a reload done meanwhile is stale and is done again
+    generation: u64,
+}
+
+impl PictureAssets {
+    pub(super) fn new(assets: HashMap<String, PictureAsset>) -> Self {
+        PictureAssets { assets, generation: 0 }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&PictureAsset> {
+        self.assets.get(id)
+    }
+}
+
+fn asset_entry(mut val: Object) -> Result<(String, PictureAsset)> {
+    let edits: Option<String> = val.x_take("edits")?;
+    let asset = PictureAsset {
+        path: val.x_take_val("path")?,
+        hash: val.x_take("hash")?,
+        edits: edits.and_then(|edits| serde_json::from_str(&edits).ok()),
+    };
+    Ok((val.x_take_val("id")?, asset))
+}
+
+/// The assets of the alive pictures
+pub(super) async fn select_picture_assets(store: &SurrealStore) -> Result<HashMap<String, PictureAsset>> {
+    let sql = "SELECT id, path, hash, edits FROM picture WHERE dtime IS NONE;";
+    let objects = store.exec_custom_solo_query(sql, None).await?;
+    objects.into_iter().map(asset_entry).collect()
+}
+
+fn update_picture_assets(ctx: &Ctx, f: impl FnOnce(&mut HashMap<String, PictureAsset>)) {
+    let model_manager = ctx.get_model_manager();
+    let Ok(mut assets) = model_manager.picture_assets().lock() else {
+        return;
+    };
+    assets.generation += 1;
+    f(&mut assets.assets);
+}
+
+/// Register the file of the created (re-imported, or edited) picture
+pub(super) fn register_picture_asset(ctx: &Ctx, picture: &PicturePrototype) {
+    update_picture_assets(ctx, |assets| {
+        assets.insert(picture.id.clone(), picture.into());
+    });
+}
+
+/// Drop the trashed pictures
+pub(super) fn unregister_picture_assets(ctx: &Ctx, ids: &[String]) {
+    update_picture_assets(ctx, |assets| {
+        for id in ids {
+            assets.remove(id);
+        }
+    });
+}
+
+/// Load the assets again from the store (e.g. after a restore or a vault import)
+pub(super) async fn reload_picture_assets(ctx: &Ctx) -> Result<()> {
+    let model_manager = ctx.get_model_manager();
+    loop {
+        let generation = model_manager.picture_assets().lock().map_or(0, |assets| assets.generation);
+        let loaded = select_picture_assets(model_manager.store()).await?;
+
+        let Ok(mut assets) = model_manager.picture_assets().lock() else {
+            return Ok(());
+        };
+        if assets.generation == generation {
+            assets.assets = loaded;
+            return Ok(());
+        }
+    }
+}
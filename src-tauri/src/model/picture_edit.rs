@@ -0,0 +1,358 @@
+//! Non-destructive picture edits: the edit stack is stored on the picture record (`edits`),
+//! the original file of the media library is never modified.
+//!
+//! Notes:
+//!     - The renders are generated when the edits are saved and cached in `get_renders_path()` as `<hash>_<key>.<ext>`,
+//!       where the key is a hash of the edits, so an edited stack never gets a stale render.
+//!     - The `loreapp://` protocol serves the render (and its thumbnails) of the edited pictures, a missing
+//!       render is generated in the background (see `protocol`).
+//!     - The pictures with the same content and the same edits share their renders, the renders of replaced
+//!       edits are removed only when no other picture uses them.
+//!     - Only the pictures of the media library (with a `hash`) can be edited.
+
+use super::bmc_base::{bmc_get, bmc_update, Bmc};
+use super::picture_asset::register_picture_asset;
+use super::picture::run_blocking;
+use super::store::Patchable;
+use super::vmap;
+use crate::fs::get_renders_path;
+use crate::fs::media::{sha256_hex, THUMBNAIL_SIZES};
+use crate::model::ctx::Ctx;
+use crate::model::{Error, Picture, PictureBmc, PicturePrototype, Result};
+use crate::prelude::f;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use serde_with_macros::skip_serializing_none;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use surrealdb::sql::{thing, Value};
+use ts_gen::TS;
+
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+/// Largest side of a resize box, a render is never bigger than its original anyway (see `apply_edits`)
+const MAX_RESIZE_SIDE: u32 = 16384;
+
+#[derive(Debug, Serialize, Deserialize, TS, Clone, Copy, PartialEq)]
+#[ts(export)]
+pub enum FlipDirection {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS, Clone, Copy, PartialEq)]
+#[ts(export)]
+pub enum PictureFormat {
+    Png,
+    Jpeg,
+    WebP,
+}
+
+impl PictureFormat {
+    fn of_file(path: &Path) -> Option<Self> {
+        match ImageFormat::from_path(path).ok()? {
+            ImageFormat::Png => Some(PictureFormat::Png),
+            ImageFormat::Jpeg => Some(PictureFormat::Jpeg),
+            ImageFormat::WebP => Some(PictureFormat::WebP),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            PictureFormat::Png => "png",
+            PictureFormat::Jpeg => "jpg",
+            PictureFormat::WebP => "webp",
+        }
+    }
+}
+
+/// A step of the edit stack, applied to the result of the previous steps
+#[derive(Debug, Serialize, Deserialize, TS, Clone, PartialEq)]
+#[ts(export)]
+#[serde(tag = "kind")]
+pub enum PictureEdit {
+    Crop { x: u32, y: u32, width: u32, height: u32 },
+    /// Clockwise, a multiple of 90
+    Rotate { degrees: u32 },
+    Flip { direction: FlipDirection },
+    /// Fit in the box, keeping the aspect ratio. Only shrinks, a smaller picture is kept as is.
+    Resize { width: u32, height: u32 },
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct PictureEdits {
+    pub steps: Vec<PictureEdit>,
+    /// Format of the render, the format of the original by default (or PNG for the other formats)
+    pub format: Option<PictureFormat>,
+    /// JPEG quality, from 1 to 100
+    pub quality: Option<u8>,
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidEdit(message)
+}
+
+impl PictureEdits {
+    /// Check the edits that don't depend on the picture (the crops are checked by `apply_edits`)
+    fn check(&self) -> Result<()> {
+        for step in self.steps.iter() {
+            match *step {
+                PictureEdit::Crop { width, height, .. } if width == 0 || height == 0 => {
+                    return Err(invalid("Empty crop rectangle".to_string()));
+                }
+                PictureEdit::Rotate { degrees } if degrees % 90 != 0 => {
+                    return Err(invalid(f!("Rotation of {degrees} degrees, only multiples of 90 are supported")));
+                }
+                PictureEdit::Resize { width, height } if width == 0 || height == 0 => {
+                    return Err(invalid(f!("Resize to {width}x{height}")));
+                }
+                PictureEdit::Resize { width, height } if width > MAX_RESIZE_SIDE || height > MAX_RESIZE_SIDE => {
+                    return Err(invalid(f!("Resize to {width}x{height}, the sides are limited to {MAX_RESIZE_SIDE}")));
+                }
+                _ => {}
+            }
+        }
+        match self.quality {
+            Some(quality) if !(1..=100).contains(&quality) => Err(invalid(f!("Quality {quality}, out of 1-100"))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Identifies the edits in the render file names
+    fn key(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        sha256_hex(json.as_bytes())[..16].to_string()
+    }
+}
+
+pub fn apply_edits(mut image: DynamicImage, edits: &PictureEdits) -> Result<DynamicImage> {
+    for step in edits.steps.iter() {
+        image = match *step {
+            PictureEdit::Crop { x, y, width, height } => {
+                if x.saturating_add(width) > image.width() || y.saturating_add(height) > image.height() {
+                    return Err(invalid(f!(
+                        "Crop {width}x{height} at ({x}, {y}) is out of the {}x{} picture",
+                        image.width(),
+                        image.height()
+                    )));
+                }
+                image.crop_imm(x, y, width, height)
+            }
+            PictureEdit::Rotate { degrees } => match degrees % 360 {
+                90 => image.rotate90(),
+                180 => image.rotate180(),
+                270 => image.rotate270(),
+                _ => image,
+            },
+            PictureEdit::Flip { direction: FlipDirection::Horizontal } => image.fliph(),
+            PictureEdit::Flip { direction: FlipDirection::Vertical } => image.flipv(),
+            // Note: Never upscaled, so the edits can't make a render bigger than its original
+            PictureEdit::Resize { width, height } if width >= image.width() && height >= image.height() => image,
+            PictureEdit::Resize { width, height } => image.resize(width, height, FilterType::Lanczos3),
+        };
+    }
+
+    Ok(image)
+}
+
+fn write_render(image: &DynamicImage, path: &Path, format: PictureFormat, quality: Option<u8>) -> Result<()> {
+    let other = |ex: image::ImageError| Error::Other(f!("{}: {ex}", path.display()));
+    match format {
+        PictureFormat::Jpeg => {
+            let mut file = fs::File::create(path).map_err(|ex| Error::Other(ex.to_string()))?;
+            let mut encoder = JpegEncoder::new_with_quality(&mut file, quality.unwrap_or(DEFAULT_JPEG_QUALITY));
+            encoder.encode_image(&image.to_rgb8()).map_err(other)
+        }
+        PictureFormat::Png => image.save_with_format(path, ImageFormat::Png).map_err(other),
+        PictureFormat::WebP => DynamicImage::ImageRgba8(image.to_rgba8())
+            .save_with_format(path, ImageFormat::WebP)
+            .map_err(other),
+    }
+}
+
+/// The start of the file names of the render of the edits and of its thumbnails
+fn render_prefix(hash: &str, edits: &PictureEdits) -> String {
+    f!("{hash}_{}", edits.key())
+}
+
+fn render_format(picture_path: &str, edits: &PictureEdits) -> PictureFormat {
+    edits.format.or_else(|| PictureFormat::of_file(Path::new(picture_path))).unwrap_or(PictureFormat::Png)
+}
+
+/// The render file of the edited picture (or of one of its thumbnails) and its ETag, it may not be rendered yet
+pub fn render_file(picture_path: &str, hash: &str, edits: &PictureEdits, thumbnail_size: Option<u32>) -> (PathBuf, String) {
+    let prefix = render_prefix(hash, edits);
+    match thumbnail_size {
+        Some(size) => (get_renders_path().join(f!("{prefix}_{size}.png")), f!("\"{hash}-{}-{size}\"", edits.key())),
+        None => {
+            let format = render_format(picture_path, edits);
+            (get_renders_path().join(f!("{prefix}.{}", format.extension())), f!("\"{hash}-{}\"", edits.key()))
+        }
+    }
+}
+
+/// Render the edited picture (or one of its thumbnails) if it's not cached yet, see `render_file`.
+/// Note: Blocking, the picture is decoded.
+pub fn render_picture(picture_path: &str, hash: &str, edits: &PictureEdits, thumbnail_size: Option<u32>) -> Result<PathBuf> {
+    let (render_path, _) = render_file(picture_path, hash, edits, None);
+    if !render_path.is_file() {
+        let image = image::open(picture_path).map_err(|ex| Error::Other(f!("{picture_path}: {ex}")))?;
+        let image = apply_edits(image, edits)?;
+
+        fs::create_dir_all(get_renders_path()).map_err(|ex| Error::Other(ex.to_string()))?;
+        // Note: written aside then renamed, so an interrupted render is never served
+        let partial = render_path.with_extension("partial");
+        write_render(&image, &partial, render_format(picture_path, edits), edits.quality)?;
+        fs::rename(&partial, &render_path).map_err(|ex| Error::Other(ex.to_string()))?;
+    }
+
+    let Some(size) = thumbnail_size else {
+        return Ok(render_path);
+    };
+    let (thumbnail_path, _) = render_file(picture_path, hash, edits, Some(size));
+    if !thumbnail_path.is_file() {
+        let image = image::open(&render_path).map_err(|ex| Error::Other(f!("{}: {ex}", render_path.display())))?;
+        write_render(&image.thumbnail(size, size), &thumbnail_path, PictureFormat::Png, None)?;
+    }
+    Ok(thumbnail_path)
+}
+
+/// The edit stack of a picture, `None` to reset it
+#[derive(Debug, Serialize, Deserialize)]
+struct PictureEditsUpdate(Option<PictureEdits>);
+
+impl From<PictureEditsUpdate> for Value {
+    fn from(val: PictureEditsUpdate) -> Self {
+        let edits = match val.0 {
+            Some(edits) => serde_json::to_string(&edits).unwrap_or_default().into(),
+            None => Value::None,
+        };
+        Value::Object(vmap!("edits".into() => edits).into())
+    }
+}
+
+impl Patchable for PictureEditsUpdate {}
+
+impl PictureBmc {
+    /// Replace the edit stack of the picture. The render is generated right away, to check the edits.
+    pub async fn edit(ctx: Arc<Ctx>, id: &str, edits: PictureEdits) -> Result<Picture> {
+        edits.check()?;
+        let picture = bmc_get::<PicturePrototype>(ctx.clone(), Self::ENTITY, id).await?;
+        if picture.hash.is_none() {
+            return Err(invalid(f!("The picture '{id}' is not in the media library, relink it first")));
+        }
+
+        let (path, hash, rendered) = (picture.path.clone(), picture.hash.clone().unwrap_or_default(), edits.clone());
+        run_blocking(move || {
+            for size in THUMBNAIL_SIZES.into_iter().map(Some).chain([None]) {
+                render_picture(&path, &hash, &rendered, size).map_err(crate::Error::Model)?;
+            }
+            Ok(())
+        })
+        .await?;
+
+        let prototype =
+            bmc_update::<PicturePrototype, _>(ctx.clone(), Self::ENTITY, id, PictureEditsUpdate(Some(edits))).await?;
+        register_picture_asset(&ctx, &prototype);
+        if picture.edits != prototype.edits {
+            Self::remove_unused_renders(ctx.clone(), &picture).await?;
+        }
+        Self::make_picture(ctx, prototype).await
+    }
+
+    /// Drop the edit stack (and the cached renders) of the picture, back to the original
+    pub async fn reset_edits(ctx: Arc<Ctx>, id: &str) -> Result<Picture> {
+        let picture = bmc_get::<PicturePrototype>(ctx.clone(), Self::ENTITY, id).await?;
+        let prototype =
+            bmc_update::<PicturePrototype, _>(ctx.clone(), Self::ENTITY, id, PictureEditsUpdate(None)).await?;
+        register_picture_asset(&ctx, &prototype);
+        Self::remove_unused_renders(ctx.clone(), &picture).await?;
+
+        Self::make_picture(ctx, prototype).await
+    }
+
+    /// Remove the renders of the previous edits of the picture, unless another picture (trashed included)
+    /// with the same content still has the same edits.
+    async fn remove_unused_renders(ctx: Arc<Ctx>, previous: &PicturePrototype) -> Result<()> {
+        let (Some(hash), Some(edits)) = (previous.hash.as_deref(), previous.edits.as_deref()) else {
+            return Ok(());
+        };
+        let sql = "SELECT id FROM picture WHERE hash = $hash AND edits = $edits AND id != $id LIMIT 1;";
+        let vars = vmap!(
+            "hash".into() => hash.into(),
+            "edits".into() => edits.into(),
+            "id".into() => thing(&previous.id).map_err(|ex| Error::Store(ex.into()))?.into(),
+        );
+        let users = ctx.get_model_manager().store().exec_custom_solo_query(sql, Some(vars.into())).await?;
+        if !users.is_empty() {
+            return Ok(());
+        }
+
+        let edits: PictureEdits = serde_json::from_str(edits).map_err(|ex| invalid(ex.to_string()))?;
+        let prefix = render_prefix(hash, &edits);
+        let renders = fs::read_dir(get_renders_path()).into_iter().flatten().filter_map(|entry| entry.ok());
+        for render in renders.filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix)) {
+            if let Err(ex) = fs::remove_file(render.path()) {
+                warn!("Failed to remove the render {}: {ex}", render.path().display());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edits(steps: Vec<PictureEdit>) -> PictureEdits {
+        PictureEdits {
+            steps,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let image = DynamicImage::new_rgb8(400, 200);
+        let steps = vec![
+            PictureEdit::Crop { x: 100, y: 0, width: 200, height: 100 },
+            PictureEdit::Rotate { degrees: 90 },
+            PictureEdit::Flip { direction: FlipDirection::Horizontal },
+            PictureEdit::Resize { width: 50, height: 50 },
+        ];
+
+        let edited = apply_edits(image.clone(), &edits(steps)).unwrap();
+        assert_eq!((edited.width(), edited.height()), (25, 50));
+
+        let upscale = vec![PictureEdit::Resize { width: 800, height: 800 }];
+        let kept = apply_edits(image.clone(), &edits(upscale)).unwrap();
+        assert_eq!((kept.width(), kept.height()), (400, 200));
+
+        let out_of_bounds = vec![PictureEdit::Crop { x: 300, y: 0, width: 200, height: 100 }];
+        assert!(apply_edits(image, &edits(out_of_bounds)).is_err());
+    }
+
+    #[test]
+    fn test_check_edits() {
+        assert!(edits(vec![PictureEdit::Rotate { degrees: 270 }]).check().is_ok());
+        assert!(edits(vec![PictureEdit::Rotate { degrees: 45 }]).check().is_err());
+        assert!(edits(vec![PictureEdit::Resize { width: 0, height: 10 }]).check().is_err());
+        assert!(edits(vec![PictureEdit::Resize { width: 16384, height: 16384 }]).check().is_ok());
+        assert!(edits(vec![PictureEdit::Resize { width: 60000, height: 60000 }]).check().is_err());
+        assert_ne!(edits(vec![]).key(), edits(vec![PictureEdit::Rotate { degrees: 90 }]).key());
+    }
+
+    #[test]
+    fn test_render_prefix() {
+        let rotated = edits(vec![PictureEdit::Rotate { degrees: 90 }]);
+        let prefix = render_prefix("abc", &rotated);
+        assert!(prefix.starts_with("abc_"));
+        assert_ne!(prefix, render_prefix("abc", &edits(vec![])));
+        assert_ne!(prefix, render_prefix("abd", &rotated));
+    }
+}
@@ -0,0 +1,408 @@
+//! Similar pictures, by the Hamming distance of their perceptual hashes (`dhash`, see `fs::media`).
+//!
+//! Notes:
+//!     - The BK-tree of the alive pictures is cached in the `ModelStore` (`PictureHashes`), built by the first
+//!       query, then patched when a picture is created, re-imported or trashed (dropped on restore and import).
+//!     - The pictures imported before the perceptual hashes get theirs on the first query. The files that can't
+//!       be decoded are marked with a `dhash_error`, they're not decoded again until relinked.
+
+use super::bmc_base::bmc_custom_solo_query;
+use super::picture::run_blocking;
+use super::store::x_take::XTake;
+use super::vmap;
+use crate::algo::bk_tree::{hamming_distance, BkTree};
+use crate::fs::media::{dhash_file, dhash_from_string, dhash_to_string};
+use crate::model::ctx::Ctx;
+use crate::model::{Error, Picture, PictureBmc, PicturePrototype, Result};
+use crate::prelude::f;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use surrealdb::sql::{thing, Object, Value};
+use ts_gen::TS;
+
+/// Max distance of the pictures grouped by `find_duplicate_groups` when none is given
+pub const DEFAULT_DUPLICATE_DISTANCE: u32 = 4;
+
+#[derive(Debug, Serialize, Clone, TS)]
+#[ts(export)]
+pub struct SimilarPicture {
+    pub picture: Picture,
+    /// Number of different bits of the hashes, out of 64
+    pub distance: u32,
+}
+
+#[derive(Debug, Serialize, Clone, TS)]
+#[ts(export)]
+pub struct DuplicatePictureGroup {
+    /// The oldest picture first
+    pub pictures: Vec<Picture>,
+    /// Largest distance between two pictures of the group
+    pub max_distance: u32,
+}
+
+/// Groups of the indices whose hashes are within `max_distance` of another hash of the group (transitively).
+/// The tree holds the indices of the hashes, the `None` hashes (removed from the tree) are left out.
+fn group_close_hashes(hashes: &[Option<u64>], tree: &BkTree<usize>, max_distance: u32) -> Vec<Vec<usize>> {
+    let mut parents: Vec<usize> = (0..hashes.len()).collect();
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+
+    for (index, hash) in hashes.iter().enumerate() {
+        let Some(hash) = *hash else {
+            continue;
+        };
+        for (&other, _) in tree.find(hash, max_distance) {
+            let (a, b) = (root(&mut parents, index), root(&mut parents, other));
+            parents[a.max(b)] = a.min(b);
+        }
+    }
+
+    let mut groups = HashMap::<usize, Vec<usize>>::new();
+    for index in (0..hashes.len()).filter(|&index| hashes[index].is_some()) {
+        let group = root(&mut parents, index);
+        groups.entry(group).or_default().push(index);
+    }
+
+    let mut groups: Vec<Vec<usize>> = groups.into_values().filter(|group| group.len() > 1).collect();
+    groups.sort_by_key(|group| group[0]);
+    groups
+}
+
+/// A picture of the `PictureHashIndex`
+#[derive(Debug, Clone)]
+struct HashedPicture {
+    id: String,
+    ctime: String,
+    dhash: u64,
+}
+
+impl TryFrom<Object> for HashedPicture {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<HashedPicture> {
+        let id: String = val.x_take_val("id")?;
+        let dhash: String = val.x_take_val("dhash")?;
+        let dhash = dhash_from_string(&dhash).ok_or_else(|| Error::Other(f!("Invalid perceptual hash of {id}")))?;
+        Ok(Self {
+            id,
+            ctime: val.x_take_val("ctime")?,
+            dhash,
+        })
+    }
+}
+
+/// The BK-tree of the perceptual hashes of the alive pictures.
+/// The pictures are kept in `ctime` order, the tree holds their position (`None` once removed).
+#[derive(Default)]
+pub struct PictureHashIndex {
+    pictures: Vec<Option<HashedPicture>>,
+    positions: HashMap<String, usize>,
+    tree: BkTree<usize>,
+}
+
+impl PictureHashIndex {
+    fn new(mut pictures: Vec<HashedPicture>) -> Self {
+        pictures.sort_by(|a, b| a.ctime.cmp(&b.ctime).then_with(|| a.id.cmp(&b.id)));
+        let mut index = PictureHashIndex::default();
+        for picture in pictures {
+            index.push(picture);
+        }
+        index
+    }
+
+    fn push(&mut self, picture: HashedPicture) {
+        let position = self.pictures.len();
+        self.tree.insert(picture.dhash, position);
+        self.positions.insert(picture.id.clone(), position);
+        self.pictures.push(Some(picture));
+    }
+
+    fn insert(&mut self, picture: HashedPicture) {
+        self.remove(&picture.id);
+        // Note: The new pictures are the most recent ones, the `ctime` order is kept
+        self.push(picture);
+    }
+
+    fn remove(&mut self, id: &str) {
+        let Some(position) = self.positions.remove(id) else {
+            return;
+        };
+        if let Some(picture) = self.pictures[position].take() {
+            self.tree.remove(picture.dhash, |value| *value == position);
+        }
+
+        // Note: The tree is rebuilt once the removed pictures outnumber the alive ones
+        if self.positions.len() * 2 < self.pictures.len() {
+            *self = Self::new(self.pictures.drain(..).flatten().collect());
+        }
+    }
+
+    fn get(&self, id: &str) -> Option<&HashedPicture> {
+        self.positions.get(id).and_then(|&position| self.pictures[position].as_ref())
+    }
+
+    /// The pictures within `max_distance` of the hash, with their distance
+    fn find(&self, dhash: u64, max_distance: u32) -> Vec<(&HashedPicture, u32)> {
+        self.tree
+            .find(dhash, max_distance)
+            .into_iter()
+            .filter_map(|(&position, distance)| Some((self.pictures[position].as_ref()?, distance)))
+            .collect()
+    }
+
+    /// The groups of close pictures, the oldest picture first in each group
+    fn groups(&self, max_distance: u32) -> Vec<Vec<&HashedPicture>> {
+        let hashes: Vec<Option<u64>> = self.pictures.iter().map(|picture| picture.as_ref().map(|p| p.dhash)).collect();
+        group_close_hashes(&hashes, &self.tree, max_distance)
+            .into_iter()
+            .map(|group| group.into_iter().filter_map(|position| self.pictures[position].as_ref()).collect())
+            .collect()
+    }
+}
+
+/// The `PictureHashIndex` cached by the `ModelStore`, none until the first similarity query
+#[derive(Default)]
+pub struct PictureHashes {
+    index: Option<PictureHashIndex>,
+    /// Bumped by every change, an index built meanwhile is stale and isn't cached
+    generation: u64,
+}
+
+fn update_picture_hashes(ctx: &Ctx, f: impl FnOnce(&mut PictureHashIndex)) {
+    let model_manager = ctx.get_model_manager();
+    let Ok(mut hashes) = model_manager.picture_hashes().lock() else {
+        return;
+    };
+    hashes.generation += 1;
+    if let Some(index) = hashes.index.as_mut() {
+        f(index);
+    }
+}
+
+/// Index the perceptual hash of the created (or re-imported) picture
+pub(super) fn index_picture_hash(ctx: &Ctx, picture: &PicturePrototype) {
+    let dhash = picture.dhash.as_deref().and_then(dhash_from_string);
+    update_picture_hashes(ctx, |index| match dhash {
+        Some(dhash) => index.insert(HashedPicture {
+            id: picture.id.clone(),
+            ctime: picture.ctime.clone(),
+            dhash,
+        }),
+        None => index.remove(&picture.id),
+    });
+}
+
+/// Drop the trashed pictures from the index
+pub(super) fn unindex_picture_hashes(ctx: &Ctx, ids: &[String]) {
+    update_picture_hashes(ctx, |index| ids.iter().for_each(|id| index.remove(id)));
+}
+
+/// Drop the whole index, rebuilt by the next query (e.g. after a restore or a vault import)
+pub(super) fn invalidate_picture_hashes(ctx: &Ctx) {
+    let model_manager = ctx.get_model_manager();
+    if let Ok(mut hashes) = model_manager.picture_hashes().lock() {
+        hashes.generation += 1;
+        hashes.index = None;
+    }
+}
+
+impl PictureBmc {
+    /// Compute the missing perceptual hashes.
+    /// The pictures whose file can't be decoded get a `dhash_error` instead, so they're skipped by the next calls.
+    async fn complete_dhashes(ctx: Arc<Ctx>) -> Result<()> {
+        let sql = "SELECT * FROM picture WHERE dtime IS NONE AND dhash IS NONE AND dhash_error IS NONE;";
+        let pictures = Self::custom_solo_query(ctx.clone(), sql, None).await?;
+        if pictures.is_empty() {
+            return Ok(());
+        }
+
+        let hashed = run_blocking(move || {
+            Ok(pictures
+                .into_par_iter()
+                .map(|p| {
+                    let dhash = dhash_file(&p.path).map_err(|err| {
+                        warn!("No perceptual hash for {}: {err}", p.id);
+                        err.to_string()
+                    });
+                    (p, dhash)
+                })
+                .collect::<Vec<_>>())
+        })
+        .await?;
+
+        for (mut picture, dhash) in hashed {
+            let id = thing(&picture.id).map_err(|ex| Error::Store(ex.into()))?;
+            let (sql, value): (&str, Value) = match &dhash {
+                Ok(dhash) => ("UPDATE $id SET dhash = $value;", dhash_to_string(*dhash).into()),
+                Err(err) => ("UPDATE $id SET dhash_error = $value;", err.as_str().into()),
+            };
+            let vars = vmap!("id".into() => id.into(), "value".into() => value);
+            Self::custom_solo_query(ctx.clone(), sql, Some(vars.into())).await?;
+
+            if let Ok(dhash) = dhash {
+                picture.dhash = Some(dhash_to_string(dhash));
+                index_picture_hash(&ctx, &picture);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run the query on the hash index, built (and cached) if needed
+    async fn with_hash_index<T>(ctx: Arc<Ctx>, query: impl FnOnce(&PictureHashIndex) -> T) -> Result<T> {
+        Self::complete_dhashes(ctx.clone()).await?;
+
+        let model_manager = ctx.get_model_manager();
+        let generation = {
+            let hashes = model_manager.picture_hashes().lock().map_err(|ex| Error::Other(ex.to_string()))?;
+            if let Some(index) = hashes.index.as_ref() {
+                return Ok(query(index));
+            }
+            hashes.generation
+        };
+
+        let sql = "SELECT id, ctime, dhash FROM picture WHERE dtime IS NONE AND dhash IS NOT NONE;";
+        let pictures = bmc_custom_solo_query::<HashedPicture>(ctx.clone(), Self::ENTITY, sql, None).await?;
+        let index = PictureHashIndex::new(pictures);
+        let result = query(&index);
+
+        let mut hashes = model_manager.picture_hashes().lock().map_err(|ex| Error::Other(ex.to_string()))?;
+        if hashes.generation == generation {
+            hashes.index = Some(index);
+        }
+        Ok(result)
+    }
+
+    /// The pictures of the ids, in the same order
+    async fn list_by_ids(ctx: Arc<Ctx>, ids: &[String]) -> Result<Vec<PicturePrototype>> {
+        let things = ids
+            .iter()
+            .map(|id| thing(id).map(Value::from).map_err(|ex| Error::Store(ex.into())))
+            .collect::<Result<Vec<_>>>()?;
+        let vars = vmap!("ids".into() => things.into());
+        let mut pictures: HashMap<String, PicturePrototype> =
+            Self::custom_solo_query(ctx, "SELECT * FROM $ids;", Some(vars.into()))
+                .await?
+                .into_iter()
+                .map(|picture| (picture.id.clone(), picture))
+                .collect();
+
+        Ok(ids.iter().filter_map(|id| pictures.remove(id)).collect())
+    }
+
+    /// The pictures within `max_distance` (0-64) of the picture, the closest first
+    pub async fn find_similar(ctx: Arc<Ctx>, id: &str, max_distance: u32) -> Result<Vec<SimilarPicture>> {
+        let found = Self::with_hash_index(ctx.clone(), |index| {
+            let target = index.get(id)?;
+            let mut found: Vec<(&HashedPicture, u32)> = index
+                .find(target.dhash, max_distance.min(64))
+                .into_iter()
+                .filter(|(picture, _)| picture.id != id)
+                .collect();
+            found.sort_by(|(a, da), (b, db)| da.cmp(db).then_with(|| a.ctime.cmp(&b.ctime)));
+            Some(found.into_iter().map(|(picture, distance)| (picture.id.clone(), distance)).collect::<Vec<_>>())
+        })
+        .await?
+        .ok_or_else(|| Error::Other(f!("The picture '{id}' has no perceptual hash")))?;
+
+        let ids: Vec<String> = found.iter().map(|(id, _)| id.clone()).collect();
+        let distances: HashMap<String, u32> = found.into_iter().collect();
+        let pictures = Self::make_pictures(ctx.clone(), Self::list_by_ids(ctx, &ids).await?).await?;
+        Ok(pictures
+            .into_iter()
+            .map(|picture| SimilarPicture {
+                distance: distances.get(&picture.id).copied().unwrap_or_default(),
+                picture,
+            })
+            .collect())
+    }
+
+    /// The groups of near-duplicate pictures of the whole library, the largest groups first
+    pub async fn find_duplicate_groups(
+        ctx: Arc<Ctx>,
+        max_distance: Option<u32>,
+    ) -> Result<Vec<DuplicatePictureGroup>> {
+        let max_distance = max_distance.unwrap_or(DEFAULT_DUPLICATE_DISTANCE).min(64);
+        let mut groups = Self::with_hash_index(ctx.clone(), |index| {
+            index
+                .groups(max_distance)
+                .into_iter()
+                .map(|group| {
+                    let max_distance = group
+                        .iter()
+                        .flat_map(|a| group.iter().map(move |b| hamming_distance(a.dhash, b.dhash)))
+                        .max()
+                        .unwrap_or_default();
+                    (group.into_iter().map(|picture| picture.id.clone()).collect::<Vec<_>>(), max_distance)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await?;
+        groups.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+
+        let ids: Vec<String> = groups.iter().flat_map(|(group, _)| group.iter().cloned()).collect();
+        let prototypes = Self::list_by_ids(ctx.clone(), &ids).await?;
+        let mut pictures: HashMap<String, Picture> = Self::make_pictures(ctx, prototypes)
+            .await?
+            .into_iter()
+            .map(|picture| (picture.id.clone(), picture))
+            .collect();
+
+        Ok(groups
+            .into_iter()
+            .map(|(group, max_distance)| DuplicatePictureGroup {
+                pictures: group.iter().filter_map(|id| pictures.remove(id)).collect(),
+                max_distance,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_close_hashes() {
+        let hashes = [0b0000_0000, 0b1111_0000, 0b0000_0001, 0b1111_0001, 0b0000_0011, 0b1010_1010].map(Some);
+        let mut tree = BkTree::new();
+        for (index, hash) in hashes.iter().enumerate() {
+            tree.insert(hash.unwrap(), index);
+        }
+        assert_eq!(group_close_hashes(&hashes, &tree, 1), vec![vec![0, 2, 4], vec![1, 3]]);
+        assert!(group_close_hashes(&hashes, &tree, 0).is_empty());
+    }
+
+    #[test]
+    fn test_picture_hash_index() {
+        let picture = |id: &str, ctime: &str, dhash: u64| HashedPicture {
+            id: id.to_string(),
+            ctime: ctime.to_string(),
+            dhash,
+        };
+        let mut index = PictureHashIndex::new(vec![
+            picture("picture:b", "2", 0b0001),
+            picture("picture:a", "1", 0b0000),
+            picture("picture:c", "3", 0b1111),
+        ]);
+
+        let ids = |groups: Vec<Vec<&HashedPicture>>| -> Vec<Vec<String>> {
+            groups.into_iter().map(|group| group.into_iter().map(|p| p.id.clone()).collect()).collect()
+        };
+        assert_eq!(ids(index.groups(1)), vec![vec!["picture:a".to_string(), "picture:b".to_string()]]);
+
+        index.remove("picture:a");
+        assert!(index.groups(1).is_empty());
+        assert!(index.get("picture:a").is_none());
+
+        index.insert(picture("picture:d", "4", 0b1110));
+        assert_eq!(index.find(0b1111, 1).len(), 2);
+        assert_eq!(ids(index.groups(1)), vec![vec!["picture:c".to_string(), "picture:d".to_string()]]);
+    }
+}
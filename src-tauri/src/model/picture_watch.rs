@@ -0,0 +1,212 @@
+//! Watched picture folders: the picture files of the `PicturesSettings::watched_dirs` are imported
+//! on startup, then kept in sync with the changes on disk while the app runs.
+//!
+//! Notes:
+//!     - A picture keeps its watched file as `source`, the picture itself is the media library copy.
+//!     - A removed file trashes its picture, a renamed (or moved) file updates the `source` of its picture.
+//!     - The progress is reported with `Pictures` hub events (see `PictureWatchProgress`).
+
+use super::picture::{is_picture_file, picture_files, run_blocking, SourceSync};
+use crate::event::HubEvent;
+use crate::model::ctx::Ctx;
+use crate::model::{Error, PictureBmc, Result, TrashBmc};
+use notify_debouncer_full::notify::event::{ModifyKind, RenameMode};
+use notify_debouncer_full::notify::{EventKind, RecursiveMode, Watcher};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, DebouncedEvent};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use ts_gen::TS;
+
+pub const PICTURES_HUB: &str = "Pictures";
+const WATCH_TOPIC: &str = "watch";
+
+/// The changes on disk are applied once the files are quiet for this long (e.g. a copy is done)
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Serialize, Clone, Default, TS)]
+#[ts(export)]
+pub struct PictureWatchProgress {
+    /// The scanned directory, none for the changes on disk
+    pub dir: Option<String>,
+    pub total: usize,
+    pub processed: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub failed: usize,
+}
+
+impl PictureWatchProgress {
+    fn add(&mut self, path: &Path, result: Result<SourceSync>) {
+        match result {
+            Ok(SourceSync::Created) => self.created += 1,
+            Ok(SourceSync::Updated) => self.updated += 1,
+            Ok(SourceSync::Unchanged) => {}
+            Err(err) => {
+                warn!("Failed to import the picture {}: {err}", path.display());
+                self.failed += 1;
+            }
+        }
+    }
+
+    fn has_changes(&self) -> bool {
+        self.created + self.updated + self.removed + self.failed > 0
+    }
+}
+
+fn emit_progress(ctx: &Ctx, label: &str, progress: &PictureWatchProgress) {
+    ctx.emit_hub_event(HubEvent {
+        hub: PICTURES_HUB.to_string(),
+        topic: WATCH_TOPIC.to_string(),
+        label: Some(label.to_string()),
+        data: Some(progress.clone()),
+    });
+}
+
+pub struct PictureWatchBmc;
+
+impl PictureWatchBmc {
+    /// (Re)start watching the directories of the settings, and import their files in the background
+    pub fn start(ctx: Arc<Ctx>) -> Result<()> {
+        let dirs: Vec<PathBuf> = ctx.get_settings().pictures.watched_dirs.iter().map(PathBuf::from).collect();
+
+        let fs_state = ctx.fs_state();
+        let mut fs_state = fs_state.lock().map_err(|ex| Error::Other(ex.to_string()))?;
+        // Note: dropping the previous watcher closes its channel, which ends its task
+        fs_state.picture_watcher = None;
+        if dirs.is_empty() {
+            return Ok(());
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher = new_debouncer(DEBOUNCE_TIMEOUT, None, move |result: DebounceEventResult| {
+            let _ = tx.send(result);
+        })
+        .map_err(|ex| Error::Other(ex.to_string()))?;
+
+        for dir in dirs.iter() {
+            if let Err(ex) = watcher.watcher().watch(dir, RecursiveMode::Recursive) {
+                warn!("Failed to watch the picture folder {}: {ex}", dir.display());
+                continue;
+            }
+            // Note: the file ids of the cache pair the two sides of a rename
+            watcher.cache().add_root(dir, RecursiveMode::Recursive);
+        }
+        fs_state.picture_watcher = Some(watcher);
+
+        tauri::async_runtime::spawn(async move {
+            for dir in dirs.iter() {
+                if let Err(err) = Self::scan(ctx.clone(), dir).await {
+                    error!("Failed to scan the picture folder {}: {err}", dir.display());
+                }
+            }
+
+            while let Some(result) = rx.recv().await {
+                match result {
+                    Ok(events) => Self::apply_events(ctx.clone(), events).await,
+                    Err(errors) => errors.iter().for_each(|ex| warn!("Picture folder watcher: {ex}")),
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Import the files of the directory, and trash the pictures of the files removed since the last scan
+    async fn scan(ctx: Arc<Ctx>, dir: &Path) -> Result<PictureWatchProgress> {
+        let root = dir.to_path_buf();
+        let files = run_blocking(move || Ok(picture_files(root))).await?;
+
+        let mut progress = PictureWatchProgress {
+            dir: Some(dir.display().to_string()),
+            total: files.len(),
+            ..Default::default()
+        };
+        for file in files {
+            progress.add(&file, PictureBmc::import_source(ctx.clone(), &file).await);
+            progress.processed += 1;
+            emit_progress(&ctx, "progress", &progress);
+        }
+
+        progress.removed = Self::trash_missing(ctx.clone(), dir).await?;
+        emit_progress(&ctx, "done", &progress);
+
+        Ok(progress)
+    }
+
+    /// Trash the pictures whose source file (under `path`) doesn't exist anymore
+    async fn trash_missing(ctx: Arc<Ctx>, path: &Path) -> Result<usize> {
+        let ids: Vec<String> = PictureBmc::list_by_source(ctx.clone(), path)
+            .await?
+            .into_iter()
+            .filter(|p| p.source.as_deref().map_or(false, |source| !Path::new(source).is_file()))
+            .map(|p| p.id)
+            .collect();
+
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        Ok(TrashBmc::trash_many(ctx, ids).await?.len())
+    }
+
+    /// Import the existing file (or the files of the directory), trash the pictures of a removed one
+    async fn sync_path(ctx: Arc<Ctx>, path: &Path, progress: &mut PictureWatchProgress) -> Result<()> {
+        if path.is_dir() {
+            let dir = path.to_path_buf();
+            for file in run_blocking(move || Ok(picture_files(dir))).await? {
+                progress.add(&file, PictureBmc::import_source(ctx.clone(), &file).await);
+            }
+        } else if path.is_file() {
+            if is_picture_file(path) {
+                progress.add(path, PictureBmc::import_source(ctx.clone(), path).await);
+            }
+        } else {
+            progress.removed += Self::trash_missing(ctx, path).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn apply_events(ctx: Arc<Ctx>, events: Vec<DebouncedEvent>) {
+        let mut progress = PictureWatchProgress {
+            total: events.len(),
+            ..Default::default()
+        };
+
+        for event in events {
+            let result = match (&event.kind, event.paths.as_slice()) {
+                (EventKind::Access(_), _) => Ok(()),
+                (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) => {
+                    match PictureBmc::move_source(ctx.clone(), from, to).await {
+                        // Note: a file moved in from an unwatched directory is a new file
+                        Ok(0) => Self::sync_path(ctx.clone(), to, &mut progress).await,
+                        Ok(moved) => {
+                            progress.updated += moved;
+                            Ok(())
+                        }
+                        Err(err) => Err(err),
+                    }
+                }
+                (_, paths) => {
+                    let mut result = Ok(());
+                    for path in paths {
+                        result = result.and(Self::sync_path(ctx.clone(), path, &mut progress).await);
+                    }
+                    result
+                }
+            };
+
+            if let Err(err) = result {
+                warn!("Failed to apply the picture folder change {:?}: {err}", event.paths);
+                progress.failed += 1;
+            }
+            progress.processed += 1;
+        }
+
+        if progress.has_changes() {
+            emit_progress(&ctx, "change", &progress);
+        }
+    }
+}
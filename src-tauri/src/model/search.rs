@@ -0,0 +1,116 @@
+//! Full-text search results shared by the searchable entities (documents and pictures).
+//!
+//! The analyzer and `SEARCH` indexes are defined by the `0001_search_indexes` store migration,
+//! the entity controllers (e.g. `DocumentBmc::search`) only run the ranked query.
+
+use super::store::x_take::XTake;
+use crate::model::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_with_macros::skip_serializing_none;
+use surrealdb::sql::Object;
+use ts_gen::TS;
+
+pub(super) const SEARCH_LIMIT_DEFAULT: i64 = 50;
+pub(super) const SEARCH_LIMIT_MAX: i64 = 500;
+
+pub(super) const HIGHLIGHT_OPEN: &str = "<mark>";
+pub(super) const HIGHLIGHT_CLOSE: &str = "</mark>";
+
+/// Number of characters kept around the first highlight when making a snippet
+const SNIPPET_RADIUS: usize = 80;
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct SearchHit {
+    pub id: String,
+    pub title: String,
+    pub score: f64,
+    /// Title with the matched terms wrapped in `<mark>` tags
+    pub title_highlight: Option<String>,
+    /// Part of the content around the first match, with the matched terms wrapped in `<mark>` tags
+    pub snippet: Option<String>,
+}
+
+impl TryFrom<Object> for SearchHit {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<SearchHit> {
+        let title: Option<String> = val.x_take("title")?;
+        let title_highlight: Option<String> = val.x_take("title_highlight")?;
+        let content_highlight: Option<String> = val.x_take("content_highlight")?;
+
+        Ok(SearchHit {
+            id: val.x_take_val("id")?,
+            title: title.unwrap_or_default(),
+            score: val.x_take::<f64>("score")?.unwrap_or_default(),
+            title_highlight: title_highlight.filter(|t| t.contains(HIGHLIGHT_OPEN)),
+            snippet: content_highlight.and_then(|c| make_snippet(&c, SNIPPET_RADIUS)),
+        })
+    }
+}
+
+pub(super) fn finalize_search_limit(limit: Option<i64>) -> Result<i64> {
+    match limit {
+        None => Ok(SEARCH_LIMIT_DEFAULT),
+        Some(limit) if limit > SEARCH_LIMIT_MAX => Err(Error::QB(surreal_qb::Error::ListLimitOverMax {
+            max: SEARCH_LIMIT_MAX,
+            actial: limit,
+        })),
+        Some(limit) => Ok(limit.max(1)),
+    }
+}
+
+/// Cut the highlighted text to `radius` characters around the first highlight.
+/// Returns `None` if the text doesn't contain any highlight.
+fn make_snippet(highlighted: &str, radius: usize) -> Option<String> {
+    let start = highlighted.find(HIGHLIGHT_OPEN)?;
+    let end = highlighted[start..]
+        .find(HIGHLIGHT_CLOSE)
+        .map(|i| start + i + HIGHLIGHT_CLOSE.len())
+        .unwrap_or(highlighted.len());
+
+    let before = &highlighted[..start];
+    let after = &highlighted[end..];
+
+    let before_start = before
+        .char_indices()
+        .rev()
+        .nth(radius.saturating_sub(1))
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    let after_end = after
+        .char_indices()
+        .nth(radius)
+        .map(|(i, _)| i)
+        .unwrap_or(after.len());
+
+    let mut snippet = String::new();
+    if before_start > 0 {
+        snippet.push('…');
+    }
+    snippet.push_str(&before[before_start..]);
+    snippet.push_str(&highlighted[start..end]);
+    snippet.push_str(&after[..after_end]);
+    if after_end < after.len() {
+        snippet.push('…');
+    }
+
+    Some(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make_snippet() {
+        let text = "The old <mark>empire</mark> fell after the war";
+
+        assert_eq!(
+            make_snippet(text, 4),
+            Some("…old <mark>empire</mark> fel…".to_string())
+        );
+        assert_eq!(make_snippet(text, 100), Some(text.to_string()));
+        assert_eq!(make_snippet("nothing matched", 10), None);
+    }
+}
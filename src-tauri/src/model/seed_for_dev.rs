@@ -15,6 +15,8 @@ pub async fn seed_store_for_dev(app_context: &ApplicationContext) -> Result<()>
             DocumentForCreate {
                 title: f!("Document {k}"),
                 r#type: DocumentType::default(),
+                folder: None,
+                body: None,
             },
         )
     });
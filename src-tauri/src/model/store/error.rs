@@ -6,6 +6,8 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub enum Error {
     #[error("{0}")]
     FailToCreateDb(String),
+    #[error("Migration {version} failed: {message}")]
+    MigrationFailed { version: u32, message: String },
 
     #[error("{0}")]
     UnresolvableResponse(&'static str),
@@ -0,0 +1,185 @@
+//! Numbered schema migrations applied by the `SurrealStore` at startup.
+//!
+//! Each migration is a `.surql` script of the `migrations` directory (`DEFINE TABLE/FIELD/INDEX`
+//! statements, or data updates when a field is renamed). The applied versions are recorded in
+//! the `_migrations` table, a migration and its record are committed in the same transaction.
+//!
+//! Notes:
+//!     - Never edit an applied migration, add a new one with the next version instead.
+//!     - The scripts are embedded with `include_str!`, `include_fn!` of `surreal_macros` only parses `DEFINE FUNCTION`.
+
+use crate::model::store::{Error, Result};
+use crate::model::vmap;
+use crate::prelude::f;
+use serde::Serialize;
+use surrealdb::engine::local::Db;
+use surrealdb::sql::Datetime;
+use surrealdb::Surreal;
+use ts_gen::TS;
+
+const MIGRATIONS_TABLE: &str = "_migrations";
+
+struct Migration {
+    version: u32,
+    name: &'static str,
+    script: &'static str,
+}
+
+/// Must be sorted by version
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "search_indexes",
+        script: include_str!("migrations/0001_search_indexes.surql"),
+    },
+    Migration {
+        version: 2,
+        name: "tables",
+        script: include_str!("migrations/0002_tables.surql"),
+    },
+    Migration {
+        version: 3,
+        name: "document_folder",
+        script: include_str!("migrations/0003_document_folder.surql"),
+    },
+    Migration {
+        version: 4,
+        name: "template_schema",
+        script: include_str!("migrations/0004_template_schema.surql"),
+    },
+    Migration {
+        version: 5,
+        name: "tag_links",
+        script: include_str!("migrations/0005_tag_links.surql"),
+    },
+    Migration {
+        version: 6,
+        name: "tag_namespaces",
+        script: include_str!("migrations/0006_tag_namespaces.surql"),
+    },
+    Migration {
+        version: 7,
+        name: "media_library",
+        script: include_str!("migrations/0007_media_library.surql"),
+    },
+    Migration {
+        version: 8,
+        name: "watched_pictures",
+        script: include_str!("migrations/0008_watched_pictures.surql"),
+    },
+    Migration {
+        version: 9,
+        name: "perceptual_hashes",
+        script: include_str!("migrations/0009_perceptual_hashes.surql"),
+    },
+    Migration {
+        version: 10,
+        name: "picture_edits",
+        script: include_str!("migrations/0010_picture_edits.surql"),
+    },
+];
+
+#[derive(Debug, Serialize, TS, Clone)]
+#[ts(export)]
+pub struct MigrationInfo {
+    pub version: u32,
+    pub name: String,
+}
+
+impl From<&Migration> for MigrationInfo {
+    fn from(migration: &Migration) -> Self {
+        MigrationInfo {
+            version: migration.version,
+            name: migration.name.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, TS, Clone)]
+#[ts(export)]
+pub struct MigrationReport {
+    /// Version of the store before the run
+    pub from_version: u32,
+    /// Version of the store after the run (unchanged for a dry run)
+    pub to_version: u32,
+    /// Migrations applied by the run, or that would be applied for a dry run
+    pub migrations: Vec<MigrationInfo>,
+    pub dry_run: bool,
+}
+
+/// Latest version recorded in the `_migrations` table, 0 if none was applied
+async fn current_version(db: &Surreal<Db>) -> Result<u32> {
+    let sql = f!("SELECT VALUE version FROM {MIGRATIONS_TABLE} ORDER BY version DESC LIMIT 1;");
+    let version: Option<i64> = db.query(sql).await?.take(0)?;
+
+    Ok(version.unwrap_or(0) as u32)
+}
+
+/// Apply the pending migrations in order.
+/// In dry-run mode, the pending scripts are only parsed and reported.
+pub(in crate::model) async fn migrate(db: &Surreal<Db>, dry_run: bool) -> Result<MigrationReport> {
+    let from_version = current_version(db).await?;
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > from_version).collect();
+
+    let mut report = MigrationReport {
+        from_version,
+        to_version: from_version,
+        migrations: vec![],
+        dry_run,
+    };
+
+    for migration in pending {
+        let failed = |message: String| Error::MigrationFailed {
+            version: migration.version,
+            message,
+        };
+
+        if dry_run {
+            surrealdb::sql::parse(migration.script).map_err(|ex| failed(ex.to_string()))?;
+        } else {
+            let sql = f!(
+                "BEGIN TRANSACTION;\n{}\nCREATE type::thing($tb, $version) SET version = $version, name = $name, ctime = $ctime;\nCOMMIT TRANSACTION;",
+                migration.script
+            );
+            let vars = vmap!(
+                "tb".into() => MIGRATIONS_TABLE.into(),
+                "version".into() => (migration.version as i64).into(),
+                "name".into() => migration.name.into(),
+                "ctime".into() => Datetime::default().to_string().into(),
+            );
+            db.query(sql)
+                .bind(vars)
+                .await
+                .and_then(|ress| ress.check())
+                .map_err(|ex| failed(ex.to_string()))?;
+
+            report.to_version = migration.version;
+        }
+
+        report.migrations.push(migration.into());
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MIGRATIONS;
+
+    #[test]
+    fn test_versions_are_increasing() {
+        assert!(MIGRATIONS.first().is_some_and(|m| m.version == 1));
+        for pair in MIGRATIONS.windows(2) {
+            assert!(pair[0].version < pair[1].version, "{} must be after {}", pair[1].name, pair[0].name);
+        }
+    }
+
+    #[test]
+    fn test_scripts_parse() {
+        for migration in MIGRATIONS {
+            if let Err(ex) = surrealdb::sql::parse(migration.script) {
+                panic!("Migration {} ({}) doesn't parse: {ex}", migration.version, migration.name);
+            }
+        }
+    }
+}
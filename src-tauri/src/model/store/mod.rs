@@ -3,7 +3,9 @@ use surreal_qb::filter::{FilterGroups, IntoFilterNodes};
 use surrealdb::sql::{Object, Value};
 use crate::utils::LabelValue;
 
+mod migrations;
 mod surreal_store;
+mod transaction;
 mod try_froms;
 mod x_take_impl;
 mod error;
@@ -11,7 +13,9 @@ pub(super) mod x_take;
 
 // --- Re-export
 pub use error::{Error, Result};
+pub use migrations::{MigrationInfo, MigrationReport};
 pub(super) use surreal_store::SurrealStore;
+pub(super) use transaction::{StoreTransaction, TxResults, RECORD_NOT_FOUND};
 use crate::prelude::W;
 
 // --- Marker traits for types that can be used for query.
@@ -70,3 +74,13 @@ pub fn vec_to_surreal_value<T: Into<Value>>(vec: Vec<T>) -> Value {
     let vec: Vec<Value> = vec.into_iter().map(|lv| lv.into()).collect();
     Value::Array(vec.into())
 }
+
+/// Array of record links. The invalid ids are skipped, the Bmc checks the ids before writing them.
+pub fn vec_to_surreal_links(ids: Vec<String>) -> Value {
+    let vec: Vec<Value> = ids
+        .iter()
+        .filter_map(|id| surrealdb::sql::thing(id).ok())
+        .map(Value::from)
+        .collect();
+    Value::Array(vec.into())
+}
@@ -5,14 +5,17 @@
 
 use std::fmt::Debug;
 use crate::model::store::{Error, Result};
+use surreal_qb::{Expr, SimpleExpr};
 use surreal_qb::filter::{FilterGroups, ListOptions};
-use crate::model::store::{Creatable, Patchable};
+use crate::model::store::{Creatable, Patchable, StoreTransaction, TxResults};
+use crate::model::store::migrations::{migrate, MigrationReport};
 use crate::prelude::*;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use surrealdb::{Surreal, engine::local::{Db, RocksDb}, Response};
 use surrealdb::sql::{Array, Datetime, Object, thing, Value};
-use surrealdb::opt::IntoQuery;
+use surrealdb::method::Stream;
+use surrealdb::opt::{IntoQuery, Resource};
 use crate::fs::{path_to_string, get_user_path};
 use crate::model::vmap;
 
@@ -30,9 +33,17 @@ impl SurrealStore {
         let db_path = format!("{}/loreapp.db", path_to_string(&get_user_path()));
         let surreal_db = Surreal::new::<RocksDb>(db_path).await.map_err(|ex| Error::FailToCreateDb(ex.to_string()))?;
         surreal_db.use_ns("loreapp_namespace").use_db("loreapp_database").await.map_err(|ex| Error::FailToCreateDb(ex.to_string()))?;
+        let report = migrate(&surreal_db, false).await?;
+        if !report.migrations.is_empty() {
+            info!("Store migrated from version {} to {}", report.from_version, report.to_version);
+        }
         Ok(Self { db: Box::new(surreal_db) })
     }
 
+    pub(in crate::model) async fn exec_migrate(&self, dry_run: bool) -> Result<MigrationReport> {
+        migrate(&self.db, dry_run).await
+    }
+
     pub(crate) fn db(self) -> Box<Surreal<Db>> {
         self.db
     }
@@ -81,11 +92,39 @@ impl SurrealStore {
         solo_response_to_object(ress)
     }
 
+    /// Note: Trashed records (with a `dtime`) are never selected
     pub(in crate::model) async fn exec_select<F: Into<FilterGroups>>(&self, tb: &str, filter_groups: Option<F>, list_options: ListOptions) -> Result<Vec<Object>> {
-        let (sql, vars) = surreal_qb::build_query::build_select_query(tb.to_string(), filter_groups, list_options);
+        let not_trashed = Expr::col("dtime".to_string()).is_none();
+        let (sql, vars) = surreal_qb::build_query::build_select_query_with_cond(tb.to_string(), filter_groups, Some(not_trashed), list_options);
 
         self.exec_custom_solo_query(sql, Some(vars)).await
     }
+
+    /// Same as `exec_select`, with `and_cond` (e.g. the keyset condition of a page). With `ListOptions::total`,
+    /// the records matching the filters (without `and_cond`) are counted as well.
+    pub(in crate::model) async fn exec_select_page<F: Into<FilterGroups>>(&self, tb: &str, filter_groups: Option<F>, and_cond: Option<SimpleExpr>, list_options: ListOptions) -> Result<(Vec<Object>, Option<i64>)> {
+        let not_trashed = || Expr::col("dtime".to_string()).is_none();
+        let filter_groups: Option<FilterGroups> = filter_groups.map(Into::into);
+
+        let mut total = None;
+        if list_options.total.unwrap_or(false) {
+            let (sql, vars) = surreal_qb::build_query::build_count_query_with_cond(tb.to_string(), filter_groups.clone(), Some(not_trashed()));
+            let counts = self.exec_custom_solo_query(sql, Some(vars)).await?;
+            total = Some(match counts.first().and_then(|count| count.get("total")) {
+                Some(Value::Number(count)) => count.as_int(),
+                _ => 0,
+            });
+        }
+
+        let cond = match and_cond {
+            Some(and_cond) => not_trashed().and(and_cond),
+            None => not_trashed(),
+        };
+        let (sql, vars) = surreal_qb::build_query::build_select_query_with_cond(tb.to_string(), filter_groups, Some(cond), list_options);
+        let objects = self.exec_custom_solo_query(sql, Some(vars)).await?;
+
+        Ok((objects, total))
+    }
     //#endregion ---------------------- SQL execs ----------------------
 
     //#region ---------------------- Graph execs ----------------------
@@ -134,12 +173,12 @@ impl SurrealStore {
                         self.exec_delete_edge(from_id, entity, id).await
                     }
                     Some(to_id) => {
-                        // let sql = f!("BEGIN; DELETE $fid->{entity} WHERE out = $id; RELATE $tid->{entity}->$id; COMMIT;");
-                        // let vars = vmap!("fid".into() => thing(from_id)?.into(), "id".into() => thing(id)?.into(), "tid".into() => thing(to_id)?.into());
-                        // let mut ress = self.db.query(sql).bind(vars).await?;
-                        // multi_response_to_object_vec(ress, 1)?.into_iter().next().ok_or_else(|| Error::ResponseIsEmpty).into()
-                        self.exec_delete_edge(from_id, entity, id).await?;
-                        self.exec_add_edge(to_id, entity, id).await
+                        // Note: The new edge is added first, so the record is never a root in between
+                        //       (the document titles are unique per folder, including the root)
+                        let mut tx = StoreTransaction::new();
+                        let edge = tx.relate(to_id, entity, id);
+                        tx.unrelate(from_id, entity, id);
+                        self.exec_transaction(tx).await?.take(edge).ok_or(Error::ResponseIsEmpty)
                     }
                 }
             }
@@ -147,6 +186,24 @@ impl SurrealStore {
     }
     //#endregion ---------------------- Graph execs ----------------------
 
+    //#region ---------------------- Transaction execs ----------------------
+    /// Apply all the operations of the transaction, or none of them
+    pub(in crate::model) async fn exec_transaction(&self, tx: StoreTransaction) -> Result<TxResults> {
+        let len = tx.len();
+        let (sql, vars) = tx.build()?;
+        let ress = self.db.query(sql).bind(vars).await?;
+
+        TxResults::from_response(ress, len)
+    }
+    //#endregion ---------------------- Transaction execs ----------------------
+
+    //#region ---------------------- Live execs ----------------------
+    /// `LIVE SELECT * FROM {tb}`, the notifications are streamed until the stream is dropped
+    pub(in crate::model) async fn exec_live_select(&self, tb: &str) -> Result<Stream<'_, Db, Value>> {
+        Ok(self.db.select(Resource::from(tb)).live().await?)
+    }
+    //#endregion ---------------------- Live execs ----------------------
+
     //#region ---------------------- Custom execs ----------------------
     pub(in crate::model) async fn exec_custom_solo_query<S: IntoQuery + Debug>(&self, sql: S, vars: Option<Object>) -> Result<Vec<Object>> {
         if let Some(vars) = vars {
@@ -0,0 +1,316 @@
+//! Batches of store operations committed in one SurrealQL transaction.
+//!
+//! A `StoreTransaction` gathers create/merge/delete/relate/unrelate operations, each builder method returns
+//! the index of its statement. `SurrealStore::exec_transaction` runs them between `BEGIN TRANSACTION` and
+//! `COMMIT TRANSACTION`: either all the operations are applied or none.
+//!
+//! Notes:
+//!     - The parameters of the statement `i` are bound as `$tb_i`, `$id_i`, `$data_i`, ... so they never collide.
+//!     - When the transaction fails, the error of the failing statement is returned (not the "not executed" ones).
+//!     - A merge fails the transaction if the record doesn't exist (`UPDATE` alone would create it).
+
+use crate::model::store::{Creatable, Error, Patchable, Result};
+use crate::prelude::{f, W};
+use surrealdb::sql::{thing, Object, Value};
+use surrealdb::Response;
+
+/// Prefix of the error thrown by a merge of a missing record (followed by the record id)
+pub(in crate::model) const RECORD_NOT_FOUND: &str = "Record not found: ";
+
+enum TxOp {
+    Create { tb: String, data: Value },
+    CreateWithId { id: String, data: Value },
+    Insert { id: String, data: Object },
+    Merge { id: String, data: Value },
+    Delete { id: String },
+    Relate { from: String, relation: &'static str, to: String },
+    Unrelate { from: String, relation: &'static str, to: String },
+    UnrelateAll { from: String, relation: &'static str },
+    RelateWith { from: String, relation: &'static str, to: String, data: Object },
+    DeleteAll { tb: &'static str },
+    DeleteWhere { tb: &'static str, field: &'static str, value: Value },
+}
+
+#[derive(Default)]
+pub(in crate::model) struct StoreTransaction {
+    ops: Vec<TxOp>,
+}
+
+impl StoreTransaction {
+    pub(in crate::model) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(in crate::model) fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub(in crate::model) fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    fn push(&mut self, op: TxOp) -> usize {
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+
+    /// Note: The `ctime` is set like `SurrealStore::exec_create`
+    pub(in crate::model) fn create<D: Creatable>(&mut self, tb: &str, data: D) -> usize {
+        self.push(TxOp::Create { tb: tb.to_string(), data: data.into() })
+    }
+
+    /// Like `create`, with the id of the record (e.g. to relate it in the same transaction)
+    pub(in crate::model) fn create_with_id<D: Creatable>(&mut self, id: &str, data: D) -> usize {
+        self.push(TxOp::CreateWithId { id: id.to_string(), data: data.into() })
+    }
+
+    /// Create the record with its id and fields as is (no `ctime` is set), e.g. the records of a vault archive
+    pub(in crate::model) fn insert(&mut self, id: &str, data: Object) -> usize {
+        self.push(TxOp::Insert { id: id.to_string(), data })
+    }
+
+    /// Note: The record must exist, see `TxOp::Merge`
+    pub(in crate::model) fn merge<D: Patchable>(&mut self, id: &str, data: D) -> usize {
+        self.push(TxOp::Merge { id: id.to_string(), data: data.into() })
+    }
+
+    /// Merge raw fields, e.g. the trash fields that no `Patchable` carries
+    pub(in crate::model) fn merge_object(&mut self, id: &str, data: Object) -> usize {
+        self.push(TxOp::Merge { id: id.to_string(), data: data.into() })
+    }
+
+    pub(in crate::model) fn delete(&mut self, id: &str) -> usize {
+        self.push(TxOp::Delete { id: id.to_string() })
+    }
+
+    pub(in crate::model) fn relate(&mut self, from: &str, relation: &'static str, to: &str) -> usize {
+        self.push(TxOp::Relate { from: from.to_string(), relation, to: to.to_string() })
+    }
+
+    pub(in crate::model) fn unrelate(&mut self, from: &str, relation: &'static str, to: &str) -> usize {
+        self.push(TxOp::Unrelate { from: from.to_string(), relation, to: to.to_string() })
+    }
+
+    /// Relate the records with an edge holding the fields
+    pub(in crate::model) fn relate_with(&mut self, from: &str, relation: &'static str, to: &str, data: Object) -> usize {
+        self.push(TxOp::RelateWith { from: from.to_string(), relation, to: to.to_string(), data })
+    }
+
+    /// Delete all the records of the table
+    pub(in crate::model) fn delete_all(&mut self, tb: &'static str) -> usize {
+        self.push(TxOp::DeleteAll { tb })
+    }
+
+    /// Delete the records of the table whose field equals the value
+    pub(in crate::model) fn delete_where(&mut self, tb: &'static str, field: &'static str, value: Value) -> usize {
+        self.push(TxOp::DeleteWhere { tb, field, value })
+    }
+
+    /// Delete every outgoing `relation` edge of the record
+    pub(in crate::model) fn unrelate_all(&mut self, from: &str, relation: &'static str) -> usize {
+        self.push(TxOp::UnrelateAll { from: from.to_string(), relation })
+    }
+
+    /// The SurrealQL of the transaction and its variables
+    pub(super) fn build(self) -> Result<(String, Object)> {
+        let mut sql = String::from("BEGIN TRANSACTION;\n");
+        let mut vars = Object::default();
+        let ctime: Value = surrealdb::sql::Datetime::default().to_string().into();
+
+        for (i, op) in self.ops.into_iter().enumerate() {
+            match op {
+                TxOp::Create { tb, data } => {
+                    let mut data: Object = W(data).try_into()?;
+                    data.insert("ctime".into(), ctime.clone());
+                    sql.push_str(&f!("CREATE type::table($tb_{i}) CONTENT $data_{i};\n"));
+                    vars.insert(f!("tb_{i}"), tb.into());
+                    vars.insert(f!("data_{i}"), data.into());
+                }
+                TxOp::CreateWithId { id, data } => {
+                    let mut data: Object = W(data).try_into()?;
+                    data.insert("ctime".into(), ctime.clone());
+                    sql.push_str(&f!("CREATE $id_{i} CONTENT $data_{i};\n"));
+                    vars.insert(f!("id_{i}"), thing(&id)?.into());
+                    vars.insert(f!("data_{i}"), data.into());
+                }
+                TxOp::Insert { id, data } => {
+                    sql.push_str(&f!("CREATE $id_{i} CONTENT $data_{i};\n"));
+                    vars.insert(f!("id_{i}"), thing(&id)?.into());
+                    vars.insert(f!("data_{i}"), data.into());
+                }
+                TxOp::Merge { id, data } => {
+                    sql.push_str(&f!(
+                        "IF $id_{i}.id = NONE {{ THROW \"{RECORD_NOT_FOUND}\" + <string> $id_{i} }} \
+                        ELSE {{ UPDATE $id_{i} MERGE $data_{i} }};\n"
+                    ));
+                    vars.insert(f!("id_{i}"), thing(&id)?.into());
+                    vars.insert(f!("data_{i}"), data);
+                }
+                TxOp::Delete { id } => {
+                    sql.push_str(&f!("DELETE $id_{i} RETURN BEFORE;\n"));
+                    vars.insert(f!("id_{i}"), thing(&id)?.into());
+                }
+                TxOp::Relate { from, relation, to } => {
+                    sql.push_str(&f!("RELATE $from_{i}->{relation}->$to_{i};\n"));
+                    vars.insert(f!("from_{i}"), thing(&from)?.into());
+                    vars.insert(f!("to_{i}"), thing(&to)?.into());
+                }
+                TxOp::Unrelate { from, relation, to } => {
+                    sql.push_str(&f!("DELETE $from_{i}->{relation} WHERE out = $to_{i} RETURN BEFORE;\n"));
+                    vars.insert(f!("from_{i}"), thing(&from)?.into());
+                    vars.insert(f!("to_{i}"), thing(&to)?.into());
+                }
+                TxOp::RelateWith { from, relation, to, data } => {
+                    sql.push_str(&f!("RELATE $from_{i}->{relation}->$to_{i} CONTENT $data_{i};\n"));
+                    vars.insert(f!("from_{i}"), thing(&from)?.into());
+                    vars.insert(f!("to_{i}"), thing(&to)?.into());
+                    vars.insert(f!("data_{i}"), data.into());
+                }
+                TxOp::DeleteAll { tb } => {
+                    sql.push_str(&f!("DELETE {tb};\n"));
+                }
+                TxOp::DeleteWhere { tb, field, value } => {
+                    sql.push_str(&f!("DELETE {tb} WHERE {field} = $value_{i};\n"));
+                    vars.insert(f!("value_{i}"), value);
+                }
+                TxOp::UnrelateAll { from, relation } => {
+                    sql.push_str(&f!("DELETE $from_{i}->{relation} RETURN BEFORE;\n"));
+                    vars.insert(f!("from_{i}"), thing(&from)?.into());
+                }
+            }
+        }
+        sql.push_str("COMMIT TRANSACTION;");
+
+        Ok((sql, vars))
+    }
+}
+
+/// Results of a committed `StoreTransaction`, by statement index
+pub(in crate::model) struct TxResults {
+    results: Vec<Option<Object>>,
+}
+
+impl TxResults {
+    pub(super) fn from_response(mut response: Response, len: usize) -> Result<Self> {
+        Self::from_values((0..len).map(|i| response.take::<Value>(i)))
+    }
+
+    /// The results of the statements, in order
+    fn from_values(statements: impl IntoIterator<Item = surrealdb::Result<Value>>) -> Result<Self> {
+        let mut values = vec![];
+        let mut not_executed: Option<surrealdb::Error> = None;
+
+        for statement in statements {
+            match statement {
+                Ok(value) => values.push(value),
+                Err(err) if matches!(err, surrealdb::Error::Db(surrealdb::error::Db::QueryNotExecuted)) => {
+                    not_executed.get_or_insert(err);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if let Some(err) = not_executed {
+            return Err(err.into());
+        }
+
+        let results = values
+            .into_iter()
+            .map(|value| match value {
+                Value::Array(array) => array.0.into_iter().next().map(|v| W(v).try_into()).transpose(),
+                Value::Object(object) => Ok(Some(object)),
+                _ => Ok(None),
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { results })
+    }
+
+    /// Record returned by the statement (the record before for the deletions), `None` if nothing matched
+    pub(in crate::model) fn take(&mut self, idx: usize) -> Option<Object> {
+        self.results.get_mut(idx).and_then(Option::take)
+    }
+
+    /// Like `take`, fails if the statement returned nothing
+    pub(in crate::model) fn take_val<E>(&mut self, idx: usize) -> core::result::Result<E, crate::model::Error>
+    where
+        E: TryFrom<Object, Error = crate::model::Error>,
+    {
+        self.take(idx)
+            .ok_or(crate::model::Error::Store(Error::ResponseIsEmpty))?
+            .try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::vmap;
+
+    struct Data(Object);
+
+    impl From<Data> for Value {
+        fn from(val: Data) -> Self {
+            val.0.into()
+        }
+    }
+
+    impl Creatable for Data {}
+    impl Patchable for Data {}
+
+    #[test]
+    fn test_build() -> Result<()> {
+        let mut tx = StoreTransaction::new();
+        assert_eq!(tx.create("document", Data(vmap!("title".into() => "A".into()).into())), 0);
+        assert_eq!(tx.merge("document:b", Data(vmap!("title".into() => "B".into()).into())), 1);
+        assert_eq!(tx.relate("documentsFolder:a", "documentsFolders", "document:b"), 2);
+        assert_eq!(tx.unrelate_all("document:b", "links"), 3);
+
+        let (sql, vars) = tx.build()?;
+        assert_eq!(
+            sql,
+            "BEGIN TRANSACTION;\n\
+            CREATE type::table($tb_0) CONTENT $data_0;\n\
+            IF $id_1.id = NONE { THROW \"Record not found: \" + <string> $id_1 } ELSE { UPDATE $id_1 MERGE $data_1 };\n\
+            RELATE $from_2->documentsFolders->$to_2;\n\
+            DELETE $from_3->links RETURN BEFORE;\n\
+            COMMIT TRANSACTION;"
+        );
+
+        let mut names: Vec<&str> = vars.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, vec!["data_0", "data_1", "from_2", "from_3", "id_1", "tb_0", "to_2"]);
+
+        let data: Object = W(vars.get("data_0").cloned().unwrap_or_default()).try_into()?;
+        assert!(data.contains_key("ctime"));
+        assert_eq!(vars.get("id_1"), Some(&Value::from(thing("document:b")?)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_values() -> Result<()> {
+        let record: Object = vmap!("id".into() => "document:a".into()).into();
+        let mut results = TxResults::from_values(vec![
+            Ok(Value::Array(vec![Value::Object(record.clone())].into())),
+            Ok(Value::Array(vec![].into())),
+            Ok(Value::Object(record.clone())),
+        ])?;
+        assert_eq!(results.take(0), Some(record.clone()));
+        assert_eq!(results.take(0), None);
+        assert_eq!(results.take(1), None);
+        assert_eq!(results.take(2), Some(record));
+        assert_eq!(results.take(3), None);
+
+        // The error of the failing statement, not the "not executed" ones before it
+        let failed = TxResults::from_values(vec![
+            Err(surrealdb::error::Db::QueryNotExecuted.into()),
+            Err(surrealdb::error::Db::Thrown("Record not found: document:b".into()).into()),
+        ]);
+        assert!(matches!(
+            failed,
+            Err(Error::Surreal(surrealdb::Error::Db(surrealdb::error::Db::Thrown(_))))
+        ));
+
+        Ok(())
+    }
+}
@@ -55,6 +55,16 @@ impl TryFrom<W<Value>> for i64 {
     }
 }
 
+impl TryFrom<W<Value>> for f64 {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<f64> {
+        match val.0 {
+            Value::Number(obj) => Ok(obj.as_float()),
+            _ => Err(Error::XValueNotOfType("f64")),
+        }
+    }
+}
+
 impl TryFrom<W<Value>> for bool {
     type Error = Error;
     fn try_from(val: W<Value>) -> Result<bool> {
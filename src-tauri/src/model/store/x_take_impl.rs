@@ -43,6 +43,17 @@ impl XTakeImpl<i64> for Object {
     }
 }
 
+impl XTakeImpl<f64> for Object {
+    fn x_take_impl(&mut self, k: &str) -> Result<Option<f64>> {
+        let v = self.remove(k).map(|v| W(v).try_into());
+        match v {
+            None => Ok(None),
+            Some(Ok(val)) => Ok(Some(val)),
+            Some(Err(ex)) => Err(ex),
+        }
+    }
+}
+
 impl XTakeImpl<bool> for Object {
     fn x_take_impl(&mut self, k: &str) -> Result<Option<bool>> {
         Ok(self.remove(k).map(|v| v.is_true()))
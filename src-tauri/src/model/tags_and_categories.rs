@@ -1,28 +1,33 @@
 //! All model and controller for the Tags and Categories type
 
 use super::bmc_base::{
-    bmc_create, bmc_custom_multi_query, bmc_custom_solo_query, bmc_delete, bmc_get, bmc_list,
-    bmc_update, Bmc,
+    bmc_create, bmc_custom_solo_query, bmc_get, bmc_list_page,
+    bmc_update, require_fields, Bmc,
 };
 use super::store::x_take::XTake;
-use super::store::{vec_to_surreal_value, Creatable, Filterable, Patchable};
-use crate::model::bmc_graph::{bmc_delete_edge, bmc_relate, bmc_rerelate_edge, GraphBmc};
+use super::store::{vec_to_surreal_links, vec_to_surreal_value, Creatable, Filterable, Patchable};
+use crate::model::bmc_graph::{
+    bmc_delete_edge, bmc_list_children, bmc_relate, bmc_rerelate_edge, subtree_of, GraphBmc,
+};
 use crate::model::ctx::Ctx;
+use crate::model::name_allocator::create_with_free_name;
+use crate::model::tree_index::update_tree;
 use crate::model::{
-    get_parent_id, vmap, Document, DocumentFilter, Error, PictureFilter, PictureForCreate,
-    PictureForUpdate, PicturePrototype, Result,
+    fire_model_event, get_parent_id, vmap, Document, DocumentBmc, DocumentFilter, Error,
+    ModelMutateResultData, Page, PictureBmc, PictureFilter, PictureForCreate, PictureForUpdate,
+    PicturePrototype, Result, TrashBmc, TreeDelta, TreeIndex, TreeIndexes, TreeNode,
 };
 use crate::prelude::f;
 use crate::utils::LabelValue;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_with_macros::skip_serializing_none;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
 use surreal_qb::filter::{
-    finalize_list_options, FilterNodes, ListOptions, OpValsArray, OpValsString,
+    finalize_list_options, FilterNodes, ListOptions, OpValArray, OpValsArray, OpValsString,
 };
-use surrealdb::sql::{Datetime, Object, Value};
+use surrealdb::sql::{thing, Array, Object, Value};
 use ts_gen::TS;
 
 //#region -------------------------------- Categories --------------------------------
@@ -88,69 +93,75 @@ pub struct CategoriesTree {
     nodes: Vec<CategoryNode>,
 }
 
-pub fn build_categories_tree(mut cwps: Vec<CategoryWithParent>) -> CategoriesTree {
-    fn recursive_loop<'a>(node: &'a mut CategoryNode, id: &String) -> Option<&'a mut CategoryNode> {
-        if (node.id.eq(id)) {
-            return Some(node);
+impl From<Category> for CategoryNode {
+    fn from(category: Category) -> Self {
+        CategoryNode {
+            id: category.id,
+            ctime: category.ctime,
+            name: category.name,
+            children: vec![],
         }
+    }
+}
 
-        for child in node.children.iter_mut() {
-            if let Some(find) = recursive_loop(child, id) {
-                return Some(find);
-            }
-        }
+impl TreeNode for CategoryNode {
+    const TOPIC: &'static str = CategoryBmc::RELATION_ENTITY;
 
-        None
-    };
+    fn id(&self) -> &str {
+        &self.id
+    }
 
-    let mut categories_map = HashMap::<String, CategoryNode>::new();
-    let mut root_ids = Vec::<String>::new();
-
-    for cwp in &cwps {
-        categories_map.insert(
-            cwp.category.id.clone(),
-            CategoryNode {
-                id: cwp.category.id.clone(),
-                name: cwp.category.name.clone(),
-                ctime: cwp.category.ctime.clone(),
-                children: vec![],
-            },
-        );
-        if cwp.parent.is_none() {
-            root_ids.push(cwp.category.id.clone());
-        }
+    fn children_mut(&mut self) -> Option<&mut Vec<Self>> {
+        Some(&mut self.children)
     }
 
-    for cwp in cwps {
-        if let Some(parent_id) = cwp.parent {
-            if let Some(child_node) = categories_map.remove(&cwp.category.id) {
-                if let Some(mut parent_node) = categories_map.remove(&parent_id) {
-                    parent_node.children.push(child_node.clone());
-                    categories_map.insert(parent_id, parent_node);
-                } else {
-                    for (_, node) in categories_map.iter_mut() {
-                        if let Some(parent_node) = recursive_loop(node, &parent_id) {
-                            parent_node.children.push(child_node.clone());
-                        }
-                    }
-                }
-            }
-        }
+    fn children(&self) -> &[Self] {
+        &self.children
     }
 
-    let mut root_nodes = Vec::<CategoryNode>::new();
+    fn slot(indexes: &mut TreeIndexes) -> &mut Option<TreeIndex<Self>> {
+        indexes.categories_slot()
+    }
+}
+
+/// The categories whose parent isn't listed (e.g. trashed) are left out
+pub fn build_categories_tree(cwps: Vec<CategoryWithParent>) -> CategoriesTree {
+    fn build_node(category: Category, children: &mut HashMap<String, Vec<Category>>) -> CategoryNode {
+        let nodes = children
+            .remove(&category.id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| build_node(child, children))
+            .collect();
 
-    for id in root_ids {
-        if let Some(node) = categories_map.remove(&id) {
-            root_nodes.push(node);
+        CategoryNode {
+            children: nodes,
+            ..category.into()
+        }
+    }
+
+    let mut roots = vec![];
+    let mut children = HashMap::<String, Vec<Category>>::new();
+    for cwp in cwps {
+        match cwp.parent {
+            Some(parent) => children.entry(parent).or_default().push(cwp.category),
+            None => roots.push(cwp.category),
         }
     }
 
-    CategoriesTree { nodes: root_nodes }
+    CategoriesTree {
+        nodes: roots
+            .into_iter()
+            .map(|category| build_node(category, &mut children))
+            .collect(),
+    }
 }
 //#endregion -------------------------------- Relation Table --------------------------------
 
 //#region -------------------------------- Category Table --------------------------------
+/// The fields a `Category` can't be built without, added to the projection of `CategoryBmc::list`
+const CATEGORY_REQUIRED_FIELDS: [&str; 2] = ["ctime", "name"];
+
 #[skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize, Default, TS, Clone)]
 #[ts(export)]
@@ -176,7 +187,7 @@ impl TryFrom<Object> for Category {
 #[derive(Debug, Serialize, Deserialize, Default, TS)]
 #[ts(export)]
 pub struct CategoryForCreate {
-    name: String,
+    pub name: String,
 }
 
 impl From<CategoryForCreate> for Value {
@@ -226,13 +237,17 @@ impl GraphBmc for CategoryBmc {
     const RELATION_ENTITY: &'static str = "categories";
 }
 
+const NEW_CATEGORY_PREFIX: &str = "New Category ";
+
 impl CategoryBmc {
     pub async fn get(ctx: Arc<Ctx>, id: &str) -> Result<Category> {
         bmc_get::<Category>(ctx, Self::ENTITY, id).await
     }
 
     pub async fn create(ctx: Arc<Ctx>, data: CategoryForCreate) -> Result<Category> {
-        bmc_create(ctx, Self::ENTITY, data).await
+        let category: Category = bmc_create(ctx.clone(), Self::ENTITY, data).await?;
+        update_tree::<CategoryNode, _>(&ctx, |index| index.insert(None, category.clone().into()));
+        Ok(category)
     }
 
     pub async fn update(ctx: Arc<Ctx>, id: &str, data: CategoryForUpdate) -> Result<Category> {
@@ -240,57 +255,58 @@ impl CategoryBmc {
     }
 
     pub async fn delete(ctx: Arc<Ctx>, id: &str) -> Result<Category> {
-        bmc_delete(ctx, Self::ENTITY, id).await
+        TrashBmc::trash(ctx, Self::ENTITY, id).await
     }
 
     pub async fn list(
         ctx: Arc<Ctx>,
         filters: Option<Vec<CategoryFilter>>,
-    ) -> Result<Vec<Category>> {
-        let list_options = ListOptions {
-            limit: None,
-            offset: None,
-            order_bys: Some("ctime".into()),
-        };
-        bmc_list::<Category, _>(ctx, Self::ENTITY, filters, list_options).await
+        list_options: Option<ListOptions>,
+    ) -> Result<Page<Category>> {
+        let mut list_options = finalize_list_options(list_options)?;
+        require_fields(&mut list_options, &CATEGORY_REQUIRED_FIELDS);
+        bmc_list_page(ctx, Self::ENTITY, filters, list_options).await
     }
 
+    /// Create a category named with the lowest free `New Category N`
     pub async fn create_new_category(ctx: Arc<Ctx>) -> Result<Category> {
-        let sql = "LET $count = (SELECT count(string::startsWith(name,\"New Category\")) FROM category GROUP ALL)[0].count;\
-        IF $count = None THEN \
-        (CREATE category SET name = \"New Category 1\", ctime = $ctime) \
-        ELSE \
-        (CREATE category SET name = string::concat(\"New Category \", <string>($count+1)), ctime = $ctime) \
-        END";
-
-        let now = Datetime::default().to_string();
-        let vars = vmap!("ctime".into() => now.into());
-        let result =
-            bmc_custom_multi_query::<Category>(ctx, Self::ENTITY, sql, Some(vars.into())).await?;
-
-        result
-            .into_iter()
-            .next()
-            .ok_or(Error::Store(crate::model::store::Error::ResponseIsEmpty))
+        let category: Category = create_with_free_name(
+            ctx.clone(),
+            Self::ENTITY,
+            "name",
+            NEW_CATEGORY_PREFIX,
+            "dtime IS NONE",
+            |name| CategoryForCreate { name },
+        )
+        .await?;
+        update_tree::<CategoryNode, _>(&ctx, |index| index.insert(None, category.clone().into()));
+        Ok(category)
     }
 
+    /// The tree mutations return (and broadcast) the patch of the tree, see `tree_index`
     pub async fn attach_subcategory(
         ctx: Arc<Ctx>,
         id: &str,
         sub_id: &str,
-    ) -> Result<CategoriesTree> {
+    ) -> Result<TreeDelta<CategoryNode>> {
         // FIXME: check if subcategory already attached to this category
+        Self::list_tree(ctx.clone()).await?;
         bmc_relate::<Categories>(ctx.clone(), Self::RELATION_ENTITY, id, sub_id).await?;
-        Self::list_tree(ctx).await
+        Ok(update_tree(&ctx, |index| index.move_node(sub_id, Some(id))))
     }
 
+    /// The detached category goes back to the root
     pub async fn detach_subcategory(
         ctx: Arc<Ctx>,
         id: &str,
         sub_id: &str,
-    ) -> Result<CategoriesTree> {
+    ) -> Result<TreeDelta<CategoryNode>> {
+        Self::list_tree(ctx.clone()).await?;
         bmc_delete_edge::<Categories>(ctx.clone(), Self::RELATION_ENTITY, id, sub_id).await?;
-        Self::list_tree(ctx).await
+        Ok(update_tree(&ctx, |index| {
+            let attached = index.parent(sub_id) == Some(Some(id));
+            attached.then(|| index.move_node(sub_id, None)).flatten()
+        }))
     }
 
     pub async fn reattach_subcategory(
@@ -298,21 +314,30 @@ impl CategoryBmc {
         id: &str,
         from_id: Option<&str>,
         to_id: Option<&str>,
-    ) -> Result<CategoriesTree> {
+    ) -> Result<TreeDelta<CategoryNode>> {
+        Self::list_tree(ctx.clone()).await?;
         bmc_rerelate_edge::<Categories>(ctx.clone(), Self::RELATION_ENTITY, id, from_id, to_id)
             .await?;
-        Self::list_tree(ctx).await
+        Ok(update_tree(&ctx, |index| index.move_node(id, to_id)))
     }
 
+    /// The cached tree, built on the first call (see `tree_index`)
     pub async fn list_tree(ctx: Arc<Ctx>) -> Result<CategoriesTree> {
+        let model_manager = ctx.get_model_manager();
+        if let Some(nodes) = model_manager.cached_tree() {
+            return Ok(CategoriesTree { nodes });
+        }
+        let generation = model_manager.tree_generation();
+
         let sql = f!(
-            "SELECT *, <-{}<-category.id AS parent FROM category ORDER BY id ASC;",
+            "SELECT *, <-{}<-category.id AS parent FROM category WHERE dtime IS NONE ORDER BY id ASC;",
             Self::RELATION_ENTITY
         )
         .into_boxed_str();
         let cwps =
             bmc_custom_solo_query::<CategoryWithParent>(ctx, Self::ENTITY, &sql, None).await?;
         let tree = build_categories_tree(cwps);
+        model_manager.cache_tree(tree.nodes.clone(), generation);
         Ok(tree)
     }
 }
@@ -320,14 +345,183 @@ impl CategoryBmc {
 //#endregion -------------------------------- Categories --------------------------------
 
 //#region -------------------------------- Tags --------------------------------
-//#region -------------------------------- Tag Table --------------------------------
+//#region -------------------------------- Relation Table --------------------------------
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+struct Tags {
+    id: String,
+    r#in: String,
+    out: String,
+}
+
+impl TryFrom<Object> for Tags {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<Tags> {
+        Ok(Self {
+            id: val.x_take_val("id")?,
+            r#in: val.x_take_val("in")?,
+            out: val.x_take_val("out")?,
+        })
+    }
+}
+
 #[skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize, Default, TS, Clone)]
 #[ts(export)]
-pub struct Tag {
+pub struct TagWithParent {
+    pub tag: Tag,
+    pub parent: Option<String>,
+}
+
+impl TryFrom<Object> for TagWithParent {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<TagWithParent> {
+        let tag = Tag {
+            id: val.x_take_val("id")?,
+            ctime: val.x_take_val("ctime")?,
+            name: val.x_take_val("name")?,
+            color: val.x_take("color")?,
+            icon: val.x_take("icon")?,
+            description: val.x_take("description")?,
+        };
+
+        Ok(TagWithParent {
+            tag,
+            parent: get_parent_id(val),
+        })
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct TagNode {
     id: String,
     ctime: String,
     name: String,
+    /// Namespaced name, e.g. `faction/empire/legion`
+    path: String,
+    color: Option<String>,
+    icon: Option<String>,
+    description: Option<String>,
+    #[ts(type = "TagNode[]")]
+    children: Vec<TagNode>,
+}
+
+#[derive(Debug, Serialize, Default, TS, PartialEq)]
+#[ts(export)]
+pub struct TagsTree {
+    nodes: Vec<TagNode>,
+}
+
+/// Build the namespaces tree, the siblings are sorted by name.
+/// Note: The tags whose parent isn't listed (e.g. trashed) are roots.
+pub fn build_tags_tree(twps: Vec<TagWithParent>) -> TagsTree {
+    fn build_node(tag: Tag, parent_path: Option<&str>, children: &mut HashMap<String, Vec<Tag>>) -> TagNode {
+        let path = match parent_path {
+            Some(parent_path) => f!("{parent_path}{TAG_PATH_SEPARATOR}{}", tag.name),
+            None => tag.name.clone(),
+        };
+        let mut child_tags = children.remove(&tag.id).unwrap_or_default();
+        child_tags.sort_by(|a, b| a.name.cmp(&b.name));
+        let nodes = child_tags
+            .into_iter()
+            .map(|child| build_node(child, Some(&path), children))
+            .collect();
+
+        TagNode {
+            id: tag.id,
+            ctime: tag.ctime,
+            name: tag.name,
+            path,
+            color: tag.color,
+            icon: tag.icon,
+            description: tag.description,
+            children: nodes,
+        }
+    }
+
+    let ids: HashSet<String> = twps.iter().map(|twp| twp.tag.id.clone()).collect();
+    let mut roots = vec![];
+    let mut children = HashMap::<String, Vec<Tag>>::new();
+    for twp in twps {
+        match twp.parent.filter(|parent| ids.contains(parent)) {
+            Some(parent) => children.entry(parent).or_default().push(twp.tag),
+            None => roots.push(twp.tag),
+        }
+    }
+    roots.sort_by(|a, b| a.name.cmp(&b.name));
+
+    TagsTree {
+        nodes: roots
+            .into_iter()
+            .map(|tag| build_node(tag, None, &mut children))
+            .collect(),
+    }
+}
+//#endregion -------------------------------- Relation Table --------------------------------
+
+//#region -------------------------------- Tag Table --------------------------------
+/// Separator of the namespaces in the tag paths (e.g. `faction/empire/legion`), not allowed in the names
+pub const TAG_PATH_SEPARATOR: char = '/';
+
+/// The fields a `Tag` can't be built without, added to the projection of `TagBmc::list`
+const TAG_REQUIRED_FIELDS: [&str; 2] = ["ctime", "name"];
+
+/// The name of a tag is a single segment of its path
+fn check_tag_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() {
+        return Err(Error::Other("The tag name can't be empty".to_string()));
+    }
+    if name.contains(TAG_PATH_SEPARATOR) {
+        return Err(Error::Other(f!("The tag name can't contain '{TAG_PATH_SEPARATOR}': {name}")));
+    }
+
+    Ok(())
+}
+
+/// The tag colors are hex colors, `#rgb` or `#rrggbb`
+fn check_tag_color(color: &str) -> Result<()> {
+    let hex = color.strip_prefix('#').unwrap_or_default();
+    if matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(Error::Other(f!("Invalid tag color: {color}")))
+    }
+}
+
+/// The sub-tags of the merged tags are moved under `into_id`: it can't be one of their descendants (a cycle)
+fn check_merge_into(twps: &[TagWithParent], ids: &[String], into_id: &str) -> Result<()> {
+    let parents: HashMap<&str, &str> = twps
+        .iter()
+        .filter_map(|twp| twp.parent.as_deref().map(|parent| (twp.tag.id.as_str(), parent)))
+        .collect();
+
+    // Note: The tags are a tree, the depth bound only guards against a corrupted hierarchy
+    let mut ancestor = parents.get(into_id).copied();
+    for _ in 0..parents.len() {
+        let Some(id) = ancestor else {
+            break;
+        };
+        if ids.iter().any(|merged| merged == id) {
+            return Err(Error::InvalidHierarchy(f!("'{into_id}' is a descendant of the merged tag '{id}'")));
+        }
+        ancestor = parents.get(id).copied();
+    }
+
+    Ok(())
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone)]
+#[ts(export)]
+pub struct Tag {
+    pub id: String,
+    pub ctime: String,
+    pub name: String,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub description: Option<String>,
 }
 
 impl TryFrom<Object> for Tag {
@@ -337,21 +531,43 @@ impl TryFrom<Object> for Tag {
             id: val.x_take_val("id")?,
             ctime: val.x_take_val("ctime")?,
             name: val.x_take_val("name")?,
+            color: val.x_take("color")?,
+            icon: val.x_take("icon")?,
+            description: val.x_take("description")?,
         };
 
         Ok(tag)
     }
 }
 
+#[skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize, Default, TS)]
 #[ts(export)]
 pub struct TagForCreate {
-    name: String,
+    pub name: String,
+    /// Parent namespace, the tag is created at the root if not set
+    #[ts(optional)]
+    pub parent: Option<String>,
+    #[ts(optional)]
+    pub color: Option<String>,
+    #[ts(optional)]
+    pub icon: Option<String>,
+    #[ts(optional)]
+    pub description: Option<String>,
 }
 
 impl From<TagForCreate> for Value {
     fn from(val: TagForCreate) -> Self {
         let mut data = vmap!["name".into() => val.name.into()];
+        if let Some(color) = val.color {
+            data.insert("color".into(), color.into());
+        }
+        if let Some(icon) = val.icon {
+            data.insert("icon".into(), icon.into());
+        }
+        if let Some(description) = val.description {
+            data.insert("description".into(), description.into());
+        }
         Value::Object(data.into())
     }
 }
@@ -363,6 +579,9 @@ impl Creatable for TagForCreate {}
 #[ts(export)]
 pub struct TagForUpdate {
     name: Option<String>,
+    color: Option<String>,
+    icon: Option<String>,
+    description: Option<String>,
 }
 
 impl From<TagForUpdate> for Value {
@@ -371,6 +590,15 @@ impl From<TagForUpdate> for Value {
         if let Some(name) = val.name {
             data.insert("name".into(), name.into());
         }
+        if let Some(color) = val.color {
+            data.insert("color".into(), color.into());
+        }
+        if let Some(icon) = val.icon {
+            data.insert("icon".into(), icon.into());
+        }
+        if let Some(description) = val.description {
+            data.insert("description".into(), description.into());
+        }
         Value::Object(data.into())
     }
 }
@@ -386,42 +614,380 @@ pub struct TagFilter {
 
 impl Filterable for TagFilter {}
 
+#[derive(Debug, Serialize, TS, Clone)]
+#[ts(export)]
+pub struct TagWithUsage {
+    pub tag: Tag,
+    /// Number of alive documents tagged with the tag
+    pub documents: i64,
+    /// Number of alive pictures tagged with the tag
+    pub pictures: i64,
+}
+
 pub struct TagBmc;
 
 impl Bmc for TagBmc {
     const ENTITY: &'static str = "tag";
 }
 
+impl GraphBmc for TagBmc {
+    const RELATION_ENTITY: &'static str = "tags";
+}
+
 impl TagBmc {
     pub async fn get(ctx: Arc<Ctx>, id: &str) -> Result<Tag> {
         bmc_get::<Tag>(ctx, Self::ENTITY, id).await
     }
 
-    // Returns the created Picture struct
+    /// Create the tag in the `data.parent` namespace
     pub async fn create(ctx: Arc<Ctx>, data: TagForCreate) -> Result<Tag> {
-        bmc_create(ctx, Self::ENTITY, data).await
+        check_tag_name(&data.name)?;
+        if let Some(color) = &data.color {
+            check_tag_color(color)?;
+        }
+        let parent = data.parent.clone();
+        if let Some(parent) = &parent {
+            check_links(ctx.clone(), Self::ENTITY, std::slice::from_ref(parent)).await?;
+        }
+
+        let tag: Tag = bmc_create(ctx.clone(), Self::ENTITY, data).await?;
+        if let Some(parent) = parent {
+            bmc_relate::<Tags>(ctx, Self::RELATION_ENTITY, &parent, &tag.id).await?;
+        }
+
+        Ok(tag)
+    }
+
+    /// Returns the tag at the path (e.g. `faction/empire/legion`), the missing namespaces are created
+    pub async fn create_path(ctx: Arc<Ctx>, path: &str) -> Result<Tag> {
+        let tree = Self::list_tree(ctx.clone()).await?;
+
+        let mut nodes: &[TagNode] = &tree.nodes;
+        let mut parent: Option<String> = None;
+        for name in path.split(TAG_PATH_SEPARATOR).map(str::trim).filter(|name| !name.is_empty()) {
+            match nodes.iter().find(|node| node.name == name) {
+                Some(node) => {
+                    parent = Some(node.id.clone());
+                    nodes = &node.children;
+                }
+                None => {
+                    let data = TagForCreate {
+                        name: name.to_string(),
+                        parent,
+                        ..Default::default()
+                    };
+                    parent = Some(Self::create(ctx.clone(), data).await?.id);
+                    nodes = &[];
+                }
+            }
+        }
+
+        match parent {
+            Some(id) => Self::get(ctx, &id).await,
+            None => Err(Error::Other(f!("Invalid tag path: '{path}'"))),
+        }
     }
 
     pub async fn update(ctx: Arc<Ctx>, id: &str, data: TagForUpdate) -> Result<Tag> {
+        if let Some(name) = &data.name {
+            check_tag_name(name)?;
+        }
+        if let Some(color) = &data.color {
+            check_tag_color(color)?;
+        }
         bmc_update(ctx, Self::ENTITY, id, data).await
     }
 
+    /// Note: The tag is trashed with its whole namespace
     pub async fn delete(ctx: Arc<Ctx>, id: &str) -> Result<Tag> {
-        bmc_delete(ctx, Self::ENTITY, id).await
+        TrashBmc::trash(ctx, Self::ENTITY, id).await
     }
 
     pub async fn list(
         ctx: Arc<Ctx>,
         filters: Option<Vec<TagFilter>>,
         list_options: Option<ListOptions>,
-    ) -> Result<Vec<Tag>> {
-        let list_options = finalize_list_options(list_options)?;
-        bmc_list(ctx, Self::ENTITY, filters, list_options).await
+    ) -> Result<Page<Tag>> {
+        let mut list_options = finalize_list_options(list_options)?;
+        require_fields(&mut list_options, &TAG_REQUIRED_FIELDS);
+        bmc_list_page(ctx, Self::ENTITY, filters, list_options).await
+    }
+
+    /// Like `list`, with the number of documents and pictures using each tag
+    pub async fn list_with_usage(
+        ctx: Arc<Ctx>,
+        filters: Option<Vec<TagFilter>>,
+        list_options: Option<ListOptions>,
+    ) -> Result<Page<TagWithUsage>> {
+        let tags = Self::list(ctx.clone(), filters, list_options).await?;
+        let documents = count_links(ctx.clone(), DocumentBmc::ENTITY, "tags").await?;
+        let pictures = count_links(ctx, PictureBmc::ENTITY, "tags").await?;
+
+        Ok(tags.map(|tag| TagWithUsage {
+            documents: documents.get(&tag.id).copied().unwrap_or_default(),
+            pictures: pictures.get(&tag.id).copied().unwrap_or_default(),
+            tag,
+        }))
+    }
+
+    pub async fn reattach_subtag(
+        ctx: Arc<Ctx>,
+        id: &str,
+        from_id: Option<&str>,
+        to_id: Option<&str>,
+    ) -> Result<TagsTree> {
+        bmc_rerelate_edge::<Tags>(ctx.clone(), Self::RELATION_ENTITY, id, from_id, to_id).await?;
+        Self::list_tree(ctx).await
+    }
+
+    async fn list_with_parents(ctx: Arc<Ctx>) -> Result<Vec<TagWithParent>> {
+        let sql = f!(
+            "SELECT *, <-{}<-tag.id AS parent FROM tag WHERE dtime IS NONE ORDER BY id ASC;",
+            Self::RELATION_ENTITY
+        );
+        bmc_custom_solo_query::<TagWithParent>(ctx, Self::ENTITY, &sql, None).await
+    }
+
+    pub async fn list_tree(ctx: Arc<Ctx>) -> Result<TagsTree> {
+        let twps = Self::list_with_parents(ctx).await?;
+        Ok(build_tags_tree(twps))
+    }
+
+    /// Replace the tags by `into_id` on every document and picture, then delete them, in one transaction.
+    /// The sub-tags of the merged tags are moved under `into_id`, so it can't be a descendant of a merged tag.
+    /// Note: The merged tags are deleted for good, they don't go to the trash.
+    pub async fn merge(ctx: Arc<Ctx>, ids: Vec<String>, into_id: &str) -> Result<Tag> {
+        let into = Self::get(ctx.clone(), into_id).await?;
+        let ids: Vec<String> = ids.into_iter().filter(|id| id != &into.id).collect();
+        if ids.is_empty() {
+            return Ok(into);
+        }
+        check_links(ctx.clone(), Self::ENTITY, &ids).await?;
+        check_merge_into(&Self::list_with_parents(ctx.clone()).await?, &ids, &into.id)?;
+
+        let relation = Self::RELATION_ENTITY;
+        let mut sql = String::from("BEGIN TRANSACTION;");
+        for entity in [DocumentBmc::ENTITY, PictureBmc::ENTITY] {
+            sql.push_str(&f!(
+                "UPDATE {entity} SET tags = array::union(array::complement(tags, $ids), [$into]) \
+                WHERE tags CONTAINSANY $ids;"
+            ));
+        }
+        sql.push_str(&f!(
+            "FOR $child IN (SELECT VALUE out FROM {relation} WHERE in INSIDE $ids AND out != $into) {{ \
+            RELATE $into->{relation}->$child; }};"
+        ));
+        sql.push_str("DELETE $ids; COMMIT TRANSACTION;");
+
+        let vars = vmap!(
+            "ids".into() => vec_to_surreal_links(ids.clone()),
+            "into".into() => thing(&into.id).map_err(|ex| Error::Store(ex.into()))?.into(),
+        );
+        ctx.get_model_manager()
+            .store()
+            .exec_custom_multi_query(&sql, Some(vars.into()), 0)
+            .await?;
+
+        fire_model_event(&ctx, Self::ENTITY, "merge", ids);
+
+        Ok(into)
+    }
+
+    /// Rename the tag. If an alive sibling already has the name, the tag is merged into it (see `merge`).
+    /// Note: The documents and pictures link the tag record, they don't have to be rewritten on rename.
+    pub async fn rename(ctx: Arc<Ctx>, id: &str, name: &str) -> Result<Tag> {
+        let name = name.trim();
+        check_tag_name(name)?;
+
+        let twps = Self::list_with_parents(ctx.clone()).await?;
+        let parent = twps
+            .iter()
+            .find(|twp| twp.tag.id == id)
+            .ok_or_else(|| Error::Other(f!("Tag '{id}' not found")))?
+            .parent
+            .clone();
+        let sibling = twps
+            .into_iter()
+            .find(|twp| twp.tag.id != id && twp.tag.name == name && twp.parent == parent);
+
+        match sibling {
+            Some(sibling) => Self::merge(ctx, vec![id.to_string()], &sibling.tag.id).await,
+            None => {
+                let data = TagForUpdate {
+                    name: Some(name.to_string()),
+                    ..Default::default()
+                };
+                Self::update(ctx, id, data).await
+            }
+        }
+    }
+
+    /// Match the sub-tags of the filtered tags as well, e.g. `faction` matches `faction/empire/legion`.
+    /// Note: Only the "contains" operators are expanded, the other ones keep matching the exact tags.
+    pub(super) async fn expand_filter(ctx: Arc<Ctx>, ops: &mut Option<OpValsArray>) -> Result<()> {
+        let Some(ops) = ops else {
+            return Ok(());
+        };
+        let children = bmc_list_children(ctx, Self::RELATION_ENTITY).await?;
+        let expand = |values: Vec<Value>| -> Array {
+            let mut expanded: Vec<Value> = vec![];
+            for value in values {
+                match &value {
+                    Value::Strand(id) => {
+                        for id in subtree_of(&children, id.as_str()) {
+                            let id = Value::from(id);
+                            if !expanded.contains(&id) {
+                                expanded.push(id);
+                            }
+                        }
+                    }
+                    _ => expanded.push(value),
+                }
+            }
+            expanded.into()
+        };
+
+        for op in ops.0.iter_mut() {
+            *op = match op.clone() {
+                OpValArray::Contains(value) | OpValArray::EqAny(value) => OpValArray::ContainsAny(expand(vec![value])),
+                OpValArray::NotContains(value) => OpValArray::ContainsNone(expand(vec![value])),
+                OpValArray::ContainsAny(values) => OpValArray::ContainsAny(expand(values.0)),
+                OpValArray::ContainsNone(values) => OpValArray::ContainsNone(expand(values.0)),
+                op => op,
+            };
+        }
+
+        Ok(())
     }
 }
 //#endregion -------------------------------- Tag Table --------------------------------
 //#endregion -------------------------------- Tags --------------------------------
 
+//#region -------------------------------- Links --------------------------------
+// The `tags` and `categories` of the documents and pictures are arrays of record links
+// (see the `0005_tag_links` migration): the purged tags and categories are unlinked by the store events,
+// the Bmcs check the ids before writing them.
+
+/// The alive records of the table among the ids
+async fn alive_links(ctx: Arc<Ctx>, entity: &'static str, ids: &[String]) -> Result<HashSet<String>> {
+    let records: Vec<Value> = ids
+        .iter()
+        .filter_map(|id| thing(id).ok())
+        .filter(|record| record.tb == entity)
+        .map(Value::from)
+        .collect();
+    if records.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let sql = "SELECT id FROM $ids WHERE dtime IS NONE;";
+    let vars = vmap!("ids".into() => records.into());
+    let alive =
+        bmc_custom_solo_query::<ModelMutateResultData>(ctx, entity, sql, Some(vars.into())).await?;
+
+    Ok(alive.into_iter().map(|record| record.id).collect())
+}
+
+/// Fails with `Error::UnknownReference` if an id isn't an alive record of the table
+pub(super) async fn check_links(ctx: Arc<Ctx>, entity: &'static str, ids: &[String]) -> Result<()> {
+    let alive = alive_links(ctx, entity, ids).await?;
+    match ids.iter().find(|id| !alive.contains(*id)) {
+        Some(id) => Err(Error::UnknownReference(id.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Check the tags and categories about to be written on a document or a picture
+pub(super) async fn check_tags_and_categories(
+    ctx: Arc<Ctx>,
+    tags: Option<&[String]>,
+    categories: Option<&[String]>,
+) -> Result<()> {
+    if let Some(tags) = tags {
+        check_links(ctx.clone(), TagBmc::ENTITY, tags).await?;
+    }
+    if let Some(categories) = categories {
+        check_links(ctx, CategoryBmc::ENTITY, categories).await?;
+    }
+
+    Ok(())
+}
+
+/// Drop the ids that are no longer alive records of the table, e.g. the tags of an old revision
+pub(super) async fn retain_alive_links(
+    ctx: Arc<Ctx>,
+    entity: &'static str,
+    ids: Option<Vec<String>>,
+) -> Result<Option<Vec<String>>> {
+    let Some(mut ids) = ids else {
+        return Ok(None);
+    };
+    let alive = alive_links(ctx, entity, &ids).await?;
+    ids.retain(|id| alive.contains(id));
+
+    Ok(Some(ids))
+}
+
+/// Number of alive records of the table linking each id in the field
+async fn count_links(ctx: Arc<Ctx>, entity: &'static str, field: &str) -> Result<HashMap<String, i64>> {
+    let sql = f!("SELECT {field} AS links FROM {entity} WHERE {field} IS NOT NONE AND dtime IS NONE;");
+    let linking = bmc_custom_solo_query::<Linking>(ctx, entity, &sql, None).await?;
+
+    let mut counts = HashMap::new();
+    for id in linking.into_iter().flat_map(|linking| linking.links) {
+        *counts.entry(id).or_default() += 1;
+    }
+
+    Ok(counts)
+}
+
+struct Linking {
+    links: Vec<String>,
+}
+
+impl TryFrom<Object> for Linking {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<Linking> {
+        Ok(Self {
+            links: val.x_take("links")?.unwrap_or_default(),
+        })
+    }
+}
+
+/// The JSON filters carry the ids as strings, they must be records to match the record links
+pub(super) fn link_filter_ids(ops: &mut Option<OpValsArray>) {
+    fn link(value: &mut Value) {
+        match value {
+            Value::Strand(id) => {
+                if let Ok(record) = thing(id.as_str()) {
+                    *value = record.into();
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(link),
+            _ => {}
+        }
+    }
+
+    for op in ops.iter_mut().flat_map(|ops| ops.0.iter_mut()) {
+        match op {
+            OpValArray::Eq(values)
+            | OpValArray::Not(values)
+            | OpValArray::ContainsAll(values)
+            | OpValArray::ContainsAny(values)
+            | OpValArray::ContainsNone(values)
+            | OpValArray::AllInside(values)
+            | OpValArray::AnyInside(values)
+            | OpValArray::NoneInside(values) => values.iter_mut().for_each(link),
+            OpValArray::EqAny(value)
+            | OpValArray::EqAll(value)
+            | OpValArray::Contains(value)
+            | OpValArray::NotContains(value) => link(value),
+            OpValArray::Empty(_) | OpValArray::Null(_) => {}
+        }
+    }
+}
+//#endregion -------------------------------- Links --------------------------------
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -478,6 +1044,76 @@ mod tests {
         assert_eq!(result_tree, expected_result);
     }
 
+    fn tag(id: &str, name: &str) -> Tag {
+        Tag {
+            id: id.into(),
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tags_tree_build() {
+        let twps = vec![
+            TagWithParent { tag: tag("tag:legion", "legion"), parent: Some("tag:empire".into()) },
+            TagWithParent { tag: tag("tag:faction", "faction"), parent: None },
+            TagWithParent { tag: tag("tag:empire", "empire"), parent: Some("tag:faction".into()) },
+            TagWithParent { tag: tag("tag:magic", "magic"), parent: Some("tag:trashed".into()) },
+        ];
+
+        let tree = build_tags_tree(twps);
+        assert_eq!(tree.nodes.iter().map(|node| node.path.as_str()).collect::<Vec<_>>(), vec!["faction", "magic"]);
+        let legion = &tree.nodes[0].children[0].children[0];
+        assert_eq!(legion.path, "faction/empire/legion");
+        assert!(legion.children.is_empty());
+    }
+
+    #[test]
+    fn test_check_merge_into() {
+        let twps = vec![
+            TagWithParent { tag: tag("tag:faction", "faction"), parent: None },
+            TagWithParent { tag: tag("tag:empire", "empire"), parent: Some("tag:faction".into()) },
+            TagWithParent { tag: tag("tag:legion", "legion"), parent: Some("tag:empire".into()) },
+            TagWithParent { tag: tag("tag:magic", "magic"), parent: None },
+        ];
+
+        assert!(check_merge_into(&twps, &["tag:legion".into()], "tag:faction").is_ok());
+        assert!(check_merge_into(&twps, &["tag:magic".into()], "tag:legion").is_ok());
+        assert!(matches!(
+            check_merge_into(&twps, &["tag:magic".into(), "tag:faction".into()], "tag:legion"),
+            Err(Error::InvalidHierarchy(_))
+        ));
+        assert!(check_merge_into(&twps, &["tag:empire".into()], "tag:empire").is_ok());
+    }
+
+    #[test]
+    fn test_tag_name_and_color() {
+        assert!(check_tag_name("empire").is_ok());
+        assert!(check_tag_name("faction/empire").is_err());
+        assert!(check_tag_name(" ").is_err());
+        assert!(check_tag_color("#a0F").is_ok());
+        assert!(check_tag_color("#a0b1c2").is_ok());
+        assert!(check_tag_color("red").is_err());
+        assert!(check_tag_color("#a0b1c").is_err());
+    }
+
+    #[test]
+    fn test_link_filter_ids() {
+        let mut ops = Some(OpValsArray(vec![
+            OpValArray::Contains("tag:elves".into()),
+            OpValArray::ContainsAny(vec![Value::from("tag:men"), Value::from("dwarves")].into()),
+        ]));
+        link_filter_ids(&mut ops);
+
+        let ops = ops.unwrap().0;
+        assert!(matches!(&ops[0], OpValArray::Contains(Value::Thing(record)) if record.to_raw() == "tag:elves"));
+        let OpValArray::ContainsAny(values) = &ops[1] else {
+            panic!("ContainsAny expected");
+        };
+        assert!(matches!(&values[0], Value::Thing(_)));
+        assert!(matches!(&values[1], Value::Strand(_)));
+    }
+
     // #[test]
     // fn test_tree_build_2() {
     //     let cwps = vec![
@@ -0,0 +1,292 @@
+//! Typed fields of the documents templates.
+//!
+//! `DocumentsTemplate::data` keeps the layout of the template editor, the `TemplateSchema`
+//! describes the values of the `Templated` documents (see `TemplatedDocument`) created from the template.
+//! The `__body` field is the free Markdown part of the instances (e.g. the story of a character sheet).
+//!
+//! Notes:
+//!     - The values are JSON: a string for text, date, references and pictures, a number, or an array for lists.
+//!     - The references are checked against the store by `DocumentsTemplateBmc`, the schema only checks their table.
+
+use crate::model::bmc_base::Bmc;
+use crate::model::{CategoryBmc, DocumentBmc, Error, PictureBmc, Result, TagBmc};
+use crate::prelude::f;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use serde_with_macros::skip_serializing_none;
+use std::collections::{BTreeMap, HashSet};
+use surrealdb::sql::thing;
+use ts_gen::TS;
+
+/// Id of the body slot of the templates
+pub const TEMPLATE_BODY_FIELD: &str = "__body";
+
+#[derive(Debug, Serialize, Deserialize, TS, Clone, Copy, PartialEq)]
+#[ts(export)]
+pub enum TemplateReferenceTarget {
+    Document,
+    Tag,
+    Category,
+}
+
+impl TemplateReferenceTarget {
+    fn entity(&self) -> &'static str {
+        match self {
+            TemplateReferenceTarget::Document => DocumentBmc::ENTITY,
+            TemplateReferenceTarget::Tag => TagBmc::ENTITY,
+            TemplateReferenceTarget::Category => CategoryBmc::ENTITY,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, TS, Clone, PartialEq)]
+#[ts(export)]
+#[serde(tag = "kind")]
+pub enum TemplateFieldKind {
+    Text,
+    Number { min: Option<f64>, max: Option<f64> },
+    /// `YYYY-MM-DD` or RFC 3339 date-time
+    Date,
+    Reference { target: TemplateReferenceTarget },
+    Picture,
+    List { item: Box<TemplateFieldKind> },
+    /// Markdown body slot, only for the `__body` field
+    Body,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct TemplateField {
+    pub id: String,
+    pub name: String,
+    pub kind: TemplateFieldKind,
+    #[serde(default)]
+    pub required: bool,
+    #[ts(type = "unknown")]
+    pub default: Option<JsonValue>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone, PartialEq)]
+#[ts(export)]
+pub struct TemplateSchema {
+    pub fields: Vec<TemplateField>,
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidBody(message)
+}
+
+impl TemplateFieldKind {
+    /// Check the value, and collect the record ids it references (to be checked against the store)
+    fn check(&self, value: &JsonValue, references: &mut Vec<String>) -> core::result::Result<(), String> {
+        match (self, value) {
+            (TemplateFieldKind::Text | TemplateFieldKind::Body, JsonValue::String(_)) => Ok(()),
+            (TemplateFieldKind::Number { min, max }, JsonValue::Number(number)) => {
+                let number = number.as_f64().unwrap_or_default();
+                if min.map_or(false, |min| number < min) || max.map_or(false, |max| number > max) {
+                    return Err(f!("{number} is out of range"));
+                }
+                Ok(())
+            }
+            (TemplateFieldKind::Date, JsonValue::String(date)) => {
+                let valid = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok()
+                    || chrono::DateTime::parse_from_rfc3339(date).is_ok();
+                valid.then_some(()).ok_or_else(|| f!("'{date}' is not a date"))
+            }
+            (TemplateFieldKind::Reference { target }, JsonValue::String(id)) => {
+                check_record(id, target.entity(), references)
+            }
+            (TemplateFieldKind::Picture, JsonValue::String(id)) => check_record(id, PictureBmc::ENTITY, references),
+            (TemplateFieldKind::List { item }, JsonValue::Array(items)) => {
+                items.iter().try_for_each(|value| item.check(value, references))
+            }
+            (kind, value) => Err(f!("{value} is not a valid {}", kind.name())),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            TemplateFieldKind::Text => "text",
+            TemplateFieldKind::Number { .. } => "number",
+            TemplateFieldKind::Date => "date",
+            TemplateFieldKind::Reference { .. } => "reference",
+            TemplateFieldKind::Picture => "picture",
+            TemplateFieldKind::List { .. } => "list",
+            TemplateFieldKind::Body => "body",
+        }
+    }
+
+    /// Best effort conversion of a value of another kind (used when the kind of a field changes)
+    fn coerce(&self, value: &JsonValue) -> Option<JsonValue> {
+        let mut references = vec![];
+        if self.check(value, &mut references).is_ok() {
+            return Some(value.clone());
+        }
+
+        let coerced = match (self, value) {
+            (TemplateFieldKind::Text | TemplateFieldKind::Body, JsonValue::Number(number)) => {
+                JsonValue::String(number.to_string())
+            }
+            (TemplateFieldKind::Number { .. }, JsonValue::String(text)) => {
+                serde_json::Number::from_f64(text.trim().parse::<f64>().ok()?).map(JsonValue::Number)?
+            }
+            (TemplateFieldKind::List { .. }, JsonValue::Array(_)) => return None,
+            (TemplateFieldKind::List { item }, value) => JsonValue::Array(vec![item.coerce(value)?]),
+            (_, JsonValue::Array(items)) if items.len() == 1 => return self.coerce(&items[0]),
+            _ => return None,
+        };
+
+        self.check(&coerced, &mut references).is_ok().then_some(coerced)
+    }
+}
+
+fn check_record(id: &str, entity: &str, references: &mut Vec<String>) -> core::result::Result<(), String> {
+    match thing(id) {
+        Ok(record) if record.tb == entity => {
+            references.push(id.to_string());
+            Ok(())
+        }
+        _ => Err(f!("'{id}' is not a {entity} id")),
+    }
+}
+
+impl TemplateSchema {
+    /// Check the field ids and the `__body` slot
+    pub fn validate(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        for field in self.fields.iter() {
+            if field.id.trim().is_empty() || !ids.insert(field.id.as_str()) {
+                return Err(Error::Other(f!("Template field id '{}' is empty or duplicated", field.id)));
+            }
+            let is_body = field.kind == TemplateFieldKind::Body;
+            if is_body != (field.id == TEMPLATE_BODY_FIELD) {
+                return Err(Error::Other(f!(
+                    "Only the '{TEMPLATE_BODY_FIELD}' field is the body slot of the template"
+                )));
+            }
+            if let Some(default) = &field.default {
+                field
+                    .kind
+                    .check(default, &mut vec![])
+                    .map_err(|ex| Error::Other(f!("Invalid default of the template field '{}': {ex}", field.id)))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn field(&self, id: &str) -> Option<&TemplateField> {
+        self.fields.iter().find(|field| field.id == id)
+    }
+
+    /// Validate the values of an instance and fill the defaults.
+    /// Returns the record ids referenced by the values.
+    pub fn check_values(&self, values: &mut BTreeMap<String, JsonValue>) -> Result<Vec<String>> {
+        if let Some(unknown) = values.keys().find(|id| self.field(id).is_none()) {
+            return Err(invalid(f!("the template has no field '{unknown}'")));
+        }
+
+        let mut references = vec![];
+        for field in self.fields.iter() {
+            match values.get(&field.id) {
+                Some(value) if !value.is_null() => field
+                    .kind
+                    .check(value, &mut references)
+                    .map_err(|ex| invalid(f!("field '{}': {ex}", field.name)))?,
+                _ => match &field.default {
+                    Some(default) => {
+                        values.insert(field.id.clone(), default.clone());
+                    }
+                    None if field.required => {
+                        return Err(invalid(f!("the field '{}' is required", field.name)));
+                    }
+                    None => {
+                        values.remove(&field.id);
+                    }
+                },
+            }
+        }
+
+        Ok(references)
+    }
+
+    /// Conform the values of an instance to the schema.
+    /// The values of the removed fields, and those that can't be converted to the new kind of their field, are
+    /// removed and returned. The missing values get the field default.
+    pub fn migrate_values(&self, values: &mut BTreeMap<String, JsonValue>) -> BTreeMap<String, JsonValue> {
+        let mut dropped = BTreeMap::new();
+
+        for (id, value) in std::mem::take(values) {
+            match self.field(&id).map(|field| field.kind.coerce(&value)) {
+                Some(Some(value)) => {
+                    values.insert(id, value);
+                }
+                _ => {
+                    dropped.insert(id, value);
+                }
+            }
+        }
+
+        for field in self.fields.iter() {
+            if let (false, Some(default)) = (values.contains_key(&field.id), &field.default) {
+                values.insert(field.id.clone(), default.clone());
+            }
+        }
+
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn character_sheet() -> TemplateSchema {
+        serde_json::from_value(json!({
+            "fields": [
+                { "id": "name", "name": "Name", "kind": { "kind": "Text" }, "required": true },
+                { "id": "age", "name": "Age", "kind": { "kind": "Number", "min": 0.0 } },
+                { "id": "born", "name": "Born", "kind": { "kind": "Date" } },
+                { "id": "home", "name": "Home", "kind": { "kind": "Reference", "target": "Document" } },
+                { "id": "aliases", "name": "Aliases", "kind": { "kind": "List", "item": { "kind": "Text" } }, "default": [] },
+                { "id": "__body", "name": "Story", "kind": { "kind": "Body" } },
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn checks_the_values() {
+        let schema = character_sheet();
+        assert!(schema.validate().is_ok());
+
+        let mut values: BTreeMap<String, JsonValue> = serde_json::from_value(json!({
+            "name": "Arwen", "age": 2778, "born": "0241-01-01", "home": "document:rivendell",
+        }))
+        .unwrap();
+        assert_eq!(schema.check_values(&mut values).unwrap(), vec!["document:rivendell".to_string()]);
+        assert_eq!(values["aliases"], json!([]));
+
+        let mut values = serde_json::from_value(json!({ "name": "Arwen", "age": -1 })).unwrap();
+        assert!(schema.check_values(&mut values).is_err());
+        let mut values = serde_json::from_value(json!({ "age": 1 })).unwrap();
+        assert!(schema.check_values(&mut values).is_err());
+        let mut values = serde_json::from_value(json!({ "name": "Arwen", "home": "tag:elves" })).unwrap();
+        assert!(schema.check_values(&mut values).is_err());
+    }
+
+    #[test]
+    fn migrates_the_values() {
+        let schema = character_sheet();
+        let mut values: BTreeMap<String, JsonValue> = serde_json::from_value(json!({
+            "name": 42, "age": "12", "aliases": "Undómiel", "born": "someday", "race": "Elf",
+        }))
+        .unwrap();
+
+        let dropped = schema.migrate_values(&mut values);
+        assert_eq!(values, serde_json::from_value(json!({ "name": "42", "age": 12.0, "aliases": ["Undómiel"] })).unwrap());
+        assert_eq!(dropped.keys().collect::<Vec<_>>(), vec!["born", "race"]);
+    }
+}
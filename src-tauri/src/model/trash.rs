@@ -0,0 +1,468 @@
+//! Trash bin shared by all the trashable entities.
+//!
+//! Deleting a document, folder, picture, template, tag or category moves it to the trash:
+//! the record gets a `dtime`, its parent edge is removed and the original parent is kept in `trash_parent`.
+//! Folders, categories and tags are trashed with their whole subtree. Every record of the subtree
+//! keeps the trashed record in `trash_root`, so the subtree is restored or purged together with it.
+//!
+//! Notes:
+//!     - Trashed records are hidden by `SurrealStore::exec_select` and `exec_select_page` (so by all the listings)
+//!       and the tree builders.
+//!     - Purged records are deleted for good, SurrealDB removes their relation edges as well.
+//!       The media files of the purged pictures are removed unless another picture has the same content.
+
+use super::bmc_base::{bmc_custom_solo_query, Bmc};
+use super::bmc_graph::{bmc_list_subtree, Edge, GraphBmc};
+use super::store::x_take::XTake;
+use super::store::StoreTransaction;
+use super::picture::run_blocking;
+use super::picture_asset::{reload_picture_assets, unregister_picture_assets};
+use super::picture_similarity::{invalidate_picture_hashes, unindex_picture_hashes};
+use super::tree_index::remove_trashed;
+use super::{fire_model_event, vmap};
+use crate::fs::media::remove_media;
+use crate::model::ctx::Ctx;
+use crate::model::{
+    CategoryBmc, DocumentBmc, DocumentsFolderBmc, DocumentsTemplateBmc, Error, PictureBmc, Result,
+    TagBmc,
+};
+use crate::prelude::f;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_with_macros::skip_serializing_none;
+use std::sync::Arc;
+use surrealdb::sql::{thing, Datetime, Object, Value};
+use ts_gen::TS;
+
+/// Trashable entities with the relation table that holds their parent (if any)
+fn trashables() -> [(&'static str, Option<&'static str>); 6] {
+    [
+        (DocumentBmc::ENTITY, Some(DocumentsFolderBmc::RELATION_ENTITY)),
+        (DocumentsFolderBmc::ENTITY, Some(DocumentsFolderBmc::RELATION_ENTITY)),
+        (PictureBmc::ENTITY, None),
+        (DocumentsTemplateBmc::ENTITY, None),
+        (TagBmc::ENTITY, Some(TagBmc::RELATION_ENTITY)),
+        (CategoryBmc::ENTITY, Some(CategoryBmc::RELATION_ENTITY)),
+    ]
+}
+
+fn relation_of(entity: &str) -> Result<Option<&'static str>> {
+    trashables()
+        .into_iter()
+        .find(|(e, _)| e.eq(&entity))
+        .map(|(_, relation)| relation)
+        .ok_or_else(|| Error::Other(f!("'{entity}' records can't be moved to the trash")))
+}
+
+fn to_thing(id: &str) -> Result<Value> {
+    Ok(thing(id).map_err(|ex| Error::Store(ex.into()))?.into())
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Default, TS, Clone)]
+#[ts(export)]
+pub struct TrashItem {
+    pub id: String,
+    pub entity: String,
+    pub name: Option<String>,
+    pub dtime: String,
+    /// Folder or category the item was attached to before being trashed
+    pub parent: Option<String>,
+}
+
+impl TryFrom<Object> for TrashItem {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<TrashItem> {
+        Ok(Self {
+            id: val.x_take_val("id")?,
+            entity: val.x_take_val("entity")?,
+            name: val.x_take("name")?,
+            dtime: val.x_take_val("dtime")?,
+            parent: val.x_take("parent")?,
+        })
+    }
+}
+
+const TRASH_ITEM_FIELDS: &str =
+    "id, meta::tb(id) AS entity, title ?? name ?? path AS name, dtime, trash_parent AS parent";
+
+/// The record is in the subtree of another one of the roots
+fn is_nested(id: &str, roots: &[(&'static str, Vec<String>, Option<String>)]) -> bool {
+    roots
+        .iter()
+        .any(|(_, subtree, _)| subtree[0] != id && subtree.iter().any(|sub_id| sub_id == id))
+}
+
+/// The transaction restoring the `$root` subtree, attached to `$parent` with the relation if any.
+/// Returns the query and the index of the `document` update (to check the title conflicts).
+fn restore_query(relation: Option<&str>) -> (String, usize) {
+    // Note: The parent edge is added first, so the restored documents and folders are checked against the names
+    //       of their parent (a conflict fails the whole transaction with `Error::TitleConflict`/`NameConflict`).
+    //       `to_take` is the `document` update, the first of the trashables.
+    let mut sql = String::from("BEGIN TRANSACTION;");
+    let mut to_take = 0;
+    if let Some(relation) = relation {
+        sql.push_str(&f!("RELATE $parent->{relation}->$root;"));
+        to_take += 1;
+    }
+    for (entity, _) in trashables().iter() {
+        sql.push_str(&f!("UPDATE {entity} SET dtime = NONE, trash_root = NONE WHERE trash_root = $root;"));
+    }
+    sql.push_str("UPDATE $root SET trash_parent = NONE; COMMIT TRANSACTION;");
+
+    (sql, to_take)
+}
+
+fn purge_cond(expired_only: bool) -> &'static str {
+    match expired_only {
+        true => "dtime IS NOT NONE AND dtime < $min_dtime",
+        false => "dtime IS NOT NONE",
+    }
+}
+
+/// The transaction deleting the trashed records (those trashed before `$min_dtime` only if `expired_only`),
+/// then selecting the `$hashes` still used by a picture.
+/// Returns the query and the index of the last select.
+fn purge_query(expired_only: bool) -> (String, usize) {
+    let cond = purge_cond(expired_only);
+    let mut sql = String::from("BEGIN TRANSACTION;");
+    for (entity, _) in trashables().iter() {
+        sql.push_str(&f!("DELETE {entity} WHERE {cond};"));
+    }
+    sql.push_str(&f!(
+        "SELECT hash FROM {} WHERE hash IN $hashes GROUP BY hash; COMMIT TRANSACTION;",
+        PictureBmc::ENTITY
+    ));
+
+    (sql, trashables().len())
+}
+
+pub struct TrashBmc;
+
+impl TrashBmc {
+    async fn get_parent(ctx: Arc<Ctx>, relation: &'static str, id: &str) -> Result<Option<String>> {
+        let sql = f!("SELECT in, out FROM {relation} WHERE out = $id;");
+        let vars = vmap!("id".into() => to_thing(id)?);
+        let edges = bmc_custom_solo_query::<Edge>(ctx, relation, &sql, Some(vars.into())).await?;
+
+        Ok(edges.into_iter().next().map(|edge| edge.r#in))
+    }
+
+    /// Subtree of the record (itself first) and its parent.
+    /// Note: The record must exist and not be in the trash already (a trashed subtree keeps its `trash_root`).
+    async fn get_subtree(ctx: Arc<Ctx>, entity: &str, id: &str) -> Result<(Vec<String>, Option<String>)> {
+        let vars = vmap!("id".into() => to_thing(id)?);
+        let mut found = ctx
+            .get_model_manager()
+            .store()
+            .exec_custom_solo_query("SELECT id, dtime FROM $id;", Some(vars.into()))
+            .await?;
+        match found.pop() {
+            None => return Err(Error::RecordNotFound(id.to_string())),
+            Some(mut record) if record.x_take::<String>("dtime")?.is_some() => {
+                return Err(Error::AlreadyTrashed(id.to_string()))
+            }
+            Some(_) => {}
+        }
+
+        match relation_of(entity)? {
+            Some(relation) => Ok((
+                bmc_list_subtree(ctx.clone(), relation, id).await?,
+                Self::get_parent(ctx, relation, id).await?,
+            )),
+            None => Ok((vec![id.to_string()], None)),
+        }
+    }
+
+    /// Add the operations moving the subtree to the trash to the transaction.
+    /// Returns the index of the operation on the root record.
+    fn push_trash_ops(
+        tx: &mut StoreTransaction,
+        entity: &str,
+        subtree: &[String],
+        parent: Option<&str>,
+        dtime: &str,
+    ) -> Result<usize> {
+        let root = &subtree[0];
+        let mut root_idx = 0;
+        for sub_id in subtree.iter() {
+            let mut data = vmap!(
+                "dtime".into() => dtime.into(),
+                "trash_root".into() => to_thing(root)?,
+            );
+            if sub_id == root {
+                data.insert("trash_parent".into(), parent.map(to_thing).transpose()?.unwrap_or(Value::None));
+                root_idx = tx.merge_object(sub_id, data.into());
+            } else {
+                tx.merge_object(sub_id, data.into());
+            }
+        }
+
+        if let (Some(relation), Some(parent)) = (relation_of(entity)?, parent) {
+            tx.unrelate(parent, relation, root);
+        }
+
+        Ok(root_idx)
+    }
+
+    /// Move the record (with its subtree for folders, categories and tags) to the trash.
+    /// Returns the trashed record.
+    pub(super) async fn trash<E>(ctx: Arc<Ctx>, entity: &'static str, id: &str) -> Result<E>
+    where
+        E: TryFrom<Object, Error = Error>,
+    {
+        let (subtree, parent) = Self::get_subtree(ctx.clone(), entity, id).await?;
+
+        let mut tx = StoreTransaction::new();
+        let dtime = Datetime::default().to_string();
+        let root_idx = Self::push_trash_ops(&mut tx, entity, &subtree, parent.as_deref(), &dtime)?;
+
+        let model_manager = ctx.get_model_manager();
+        let ress = model_manager
+            .store()
+            .exec_transaction(tx)
+            .await?
+            .take(root_idx)
+            .ok_or(Error::Store(crate::model::store::Error::ResponseIsEmpty))?;
+
+        fire_model_event(&ctx, entity, "trash", ress.clone());
+        remove_trashed(&ctx, &[id.to_string()]);
+        if entity == PictureBmc::ENTITY {
+            unindex_picture_hashes(&ctx, &subtree);
+            unregister_picture_assets(&ctx, &subtree);
+        }
+
+        ress.try_into()
+    }
+
+    /// Move the records of any trashable entities to the trash, in one transaction.
+    /// The records inside the subtree of another one of the list are trashed with it.
+    /// Returns the ids of the trashed records.
+    pub async fn trash_many(ctx: Arc<Ctx>, ids: Vec<String>) -> Result<Vec<String>> {
+        let mut roots: Vec<(&'static str, Vec<String>, Option<String>)> = vec![];
+        for id in ids.iter() {
+            let tb = thing(id).map_err(|ex| Error::Store(ex.into()))?.tb;
+            let entity = trashables()
+                .into_iter()
+                .map(|(entity, _)| entity)
+                .find(|entity| *entity == tb)
+                .ok_or_else(|| Error::Other(f!("'{tb}' records can't be moved to the trash")))?;
+            let (subtree, parent) = Self::get_subtree(ctx.clone(), entity, id).await?;
+            roots.push((entity, subtree, parent));
+        }
+
+        let roots: Vec<_> = roots.iter().filter(|(_, subtree, _)| !is_nested(&subtree[0], &roots)).collect();
+
+        let mut tx = StoreTransaction::new();
+        let dtime = Datetime::default().to_string();
+        let mut root_idxs = vec![];
+        for (entity, subtree, parent) in roots.iter() {
+            root_idxs.push(Self::push_trash_ops(&mut tx, entity, subtree, parent.as_deref(), &dtime)?);
+        }
+        if tx.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let model_manager = ctx.get_model_manager();
+        let mut results = model_manager.store().exec_transaction(tx).await?;
+
+        let mut trashed = vec![];
+        let mut pictures = vec![];
+        for ((entity, subtree, _), idx) in roots.iter().zip(root_idxs) {
+            if let Some(record) = results.take(idx) {
+                fire_model_event(&ctx, entity, "trash", record);
+            }
+            if *entity == PictureBmc::ENTITY {
+                pictures.extend(subtree.iter().cloned());
+            }
+            trashed.push(subtree[0].clone());
+        }
+        remove_trashed(&ctx, &trashed);
+        unindex_picture_hashes(&ctx, &pictures);
+        unregister_picture_assets(&ctx, &pictures);
+
+        Ok(trashed)
+    }
+
+    /// Top level trashed items (the subtree records are restored/purged with them), newest first
+    pub async fn list(ctx: Arc<Ctx>) -> Result<Vec<TrashItem>> {
+        let tables = trashables().iter().map(|(entity, _)| *entity).join(", ");
+        let sql = f!("SELECT {TRASH_ITEM_FIELDS} FROM {tables} \
+        WHERE dtime IS NOT NONE AND trash_root = id ORDER BY dtime DESC;");
+
+        bmc_custom_solo_query::<TrashItem>(ctx, "", &sql, None).await
+    }
+
+    /// Restore a top level trashed item with its subtree.
+    /// It's attached back to its original parent if the parent is still alive, otherwise it becomes a root.
+    pub async fn restore(ctx: Arc<Ctx>, id: &str) -> Result<TrashItem> {
+        let sql = f!("SELECT {TRASH_ITEM_FIELDS}, trash_root FROM $root;");
+        let vars = vmap!("root".into() => to_thing(id)?);
+        let mut obj = ctx
+            .get_model_manager()
+            .store()
+            .exec_custom_solo_query(sql.as_str(), Some(vars.into()))
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(Error::Store(crate::model::store::Error::ResponseIsEmpty))?;
+
+        let trash_root: Option<String> = obj.x_take("trash_root")?;
+        if trash_root.as_deref() != Some(id) {
+            return Err(Error::Other(f!(
+                "'{id}' is not a top level item of the trash"
+            )));
+        }
+        let item: TrashItem = obj.try_into()?;
+
+        let relation = relation_of(&item.entity)?;
+        let mut alive_parent: Option<String> = None;
+        if let (Some(_), Some(parent)) = (relation, &item.parent) {
+            let sql = "SELECT id FROM $parent WHERE dtime IS NONE;";
+            let vars = vmap!("parent".into() => to_thing(parent)?);
+            let found = ctx
+                .get_model_manager()
+                .store()
+                .exec_custom_solo_query(sql, Some(vars.into()))
+                .await?;
+            if !found.is_empty() {
+                alive_parent = Some(parent.clone());
+            }
+        }
+
+        let (sql, to_take) = restore_query(relation.filter(|_| alive_parent.is_some()));
+
+        let vars = vmap!(
+            "root".into() => to_thing(id)?,
+            "parent".into() => match &alive_parent {
+                Some(parent) => to_thing(parent)?,
+                None => Value::None,
+            },
+        );
+        ctx.get_model_manager()
+            .store()
+            .exec_custom_multi_query(&sql, Some(vars.into()), to_take)
+            .await?;
+
+        fire_model_event(&ctx, &item.entity, "restore", item.clone());
+        if item.entity == PictureBmc::ENTITY {
+            invalidate_picture_hashes(&ctx);
+            reload_picture_assets(&ctx).await?;
+        }
+
+        Ok(TrashItem {
+            parent: alive_parent,
+            ..item
+        })
+    }
+
+    async fn purge(ctx: Arc<Ctx>, min_dtime: Option<String>) -> Result<Vec<TrashItem>> {
+        let items: Vec<TrashItem> = Self::list(ctx.clone())
+            .await?
+            .into_iter()
+            .filter(|item| min_dtime.as_ref().map_or(true, |min| item.dtime.lt(min)))
+            .collect();
+
+        if items.is_empty() {
+            return Ok(items);
+        }
+
+        let expired_only = min_dtime.is_some();
+        let cond = purge_cond(expired_only);
+        let min_dtime = min_dtime.map(Value::from).unwrap_or(Value::None);
+        let model_manager = ctx.get_model_manager();
+        let store = model_manager.store();
+
+        // -- The content hashes of the purged pictures, their media files are removed when no other picture has them
+        let sql = f!("SELECT hash FROM {} WHERE {cond} AND hash IS NOT NONE GROUP BY hash;", PictureBmc::ENTITY);
+        let vars = vmap!("min_dtime".into() => min_dtime.clone());
+        let mut hashes = vec![];
+        for mut obj in store.exec_custom_solo_query(sql.as_str(), Some(vars.into())).await? {
+            hashes.push(obj.x_take_val::<String>("hash")?);
+        }
+
+        let (sql, to_take) = purge_query(expired_only);
+        let vars = vmap!(
+            "min_dtime".into() => min_dtime,
+            "hashes".into() => hashes.iter().map(|hash| Value::from(hash.as_str())).collect::<Vec<_>>().into(),
+        );
+        let mut used_hashes = vec![];
+        for mut obj in store.exec_custom_multi_query(&sql, Some(vars.into()), to_take).await? {
+            used_hashes.push(obj.x_take_val::<String>("hash")?);
+        }
+
+        let unused: Vec<String> = hashes.into_iter().filter(|hash| !used_hashes.contains(hash)).collect();
+        if !unused.is_empty() {
+            run_blocking(move || {
+                unused.iter().for_each(|hash| remove_media(hash));
+                Ok(())
+            })
+            .await?;
+        }
+
+        for item in items.iter() {
+            fire_model_event(&ctx, &item.entity, "delete", item.clone());
+        }
+
+        Ok(items)
+    }
+
+    /// Delete all the trashed records for good. Returns the purged top level items.
+    pub async fn empty(ctx: Arc<Ctx>) -> Result<Vec<TrashItem>> {
+        Self::purge(ctx, None).await
+    }
+
+    /// Delete the records trashed for longer than the `TrashSettings::purge_after_days`
+    pub async fn purge_expired(ctx: Arc<Ctx>) -> Result<Vec<TrashItem>> {
+        let days = ctx.get_settings().trash.purge_after_days;
+        if days == 0 {
+            return Ok(vec![]);
+        }
+
+        let min_dtime = Datetime::from(chrono::Utc::now() - chrono::Duration::days(days as i64));
+        Self::purge(ctx, Some(min_dtime.to_string())).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_nested() {
+        let roots = vec![
+            ("documentsFolder", vec!["documentsFolder:a".to_string(), "document:b".to_string()], None),
+            ("document", vec!["document:b".to_string()], Some("documentsFolder:a".to_string())),
+            ("picture", vec!["picture:c".to_string()], None),
+        ];
+
+        assert!(!is_nested("documentsFolder:a", &roots));
+        assert!(is_nested("document:b", &roots));
+        assert!(!is_nested("picture:c", &roots));
+    }
+
+    #[test]
+    fn test_restore_query() {
+        let (sql, to_take) = restore_query(Some("documentsFolders"));
+        assert!(sql.starts_with("BEGIN TRANSACTION;RELATE $parent->documentsFolders->$root;UPDATE document "));
+        assert!(sql.ends_with("UPDATE $root SET trash_parent = NONE; COMMIT TRANSACTION;"));
+        assert_eq!(to_take, 1);
+
+        let (sql, to_take) = restore_query(None);
+        assert!(!sql.contains("RELATE"));
+        assert_eq!(sql.matches("WHERE trash_root = $root;").count(), trashables().len());
+        assert_eq!(to_take, 0);
+    }
+
+    #[test]
+    fn test_purge_query() {
+        let (sql, to_take) = purge_query(true);
+        assert_eq!(sql.matches("WHERE dtime IS NOT NONE AND dtime < $min_dtime;").count(), trashables().len());
+        assert!(sql.contains("SELECT hash FROM picture WHERE hash IN $hashes GROUP BY hash;"));
+        assert_eq!(to_take, trashables().len());
+
+        let (sql, _) = purge_query(false);
+        assert!(!sql.contains("$min_dtime"));
+        assert!(sql.contains("DELETE picture WHERE dtime IS NOT NONE;"));
+    }
+}
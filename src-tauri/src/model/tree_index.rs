@@ -0,0 +1,421 @@
+//! In-memory index of the trees (documents folders, categories), cached in the `ModelStore`.
+//!
+//! The full tree is only built on the first `list_tree` (or after an invalidation), then the tree
+//! mutations patch the index and broadcast the change as a `TreeDelta` ("Trees" hub, topic is the
+//! relation table of the tree). The windows apply the deltas to the tree they loaded.
+//!
+//! Notes:
+//!     - `fire_model_event` drops the index of the trees touched by the event, except for the "create",
+//!       "move" and "trash" actions which are patched with a delta (see `invalidate_trees`), and the "update"
+//!       of the documents and folders whose node is replaced (see `patch_updated_record`).
+//!     - The writes made without model event (e.g. raw `RELATE`) must call `invalidate_tree` themselves.
+//!     - A `TreeDelta::Reset` is sent when the index can't be patched: the tree must be reloaded.
+//!     - New and moved nodes are appended to the children of their new parent.
+//!     - Every change bumps the generation of the trees, a tree built meanwhile is stale and isn't cached.
+
+use crate::event::HubEvent;
+use crate::model::ctx::Ctx;
+use crate::model::{
+    CategoryBmc, CategoryNode, DocumentBmc, DocumentSummary, DocumentsFolder, DocumentsFolderBmc,
+    DocumentsFolderChild, ModelStore, TagBmc,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use surrealdb::sql::Object;
+use ts_gen::TS;
+
+use super::bmc_base::Bmc;
+use super::bmc_graph::GraphBmc;
+
+pub const TREES_HUB: &str = "Trees";
+
+#[derive(Debug, Serialize, TS, Clone, PartialEq)]
+#[ts(export)]
+#[serde(tag = "kind")]
+pub enum TreeDelta<N: Serialize + Clone> {
+    /// A new node, at `position` in the children of `parent` (in the roots if none)
+    Inserted {
+        parent: Option<String>,
+        position: usize,
+        node: N,
+    },
+    /// A node moved with its subtree, at `position` in the children of `to`
+    Moved {
+        id: String,
+        from: Option<String>,
+        to: Option<String>,
+        position: usize,
+    },
+    /// A node removed with its subtree, it was at `position` in the children of `parent`
+    Removed {
+        id: String,
+        parent: Option<String>,
+        position: usize,
+    },
+    /// A node replaced in place (e.g. a renamed document), with its unchanged subtree
+    Updated { node: N },
+    /// The change couldn't be patched, the whole tree must be reloaded
+    Reset,
+}
+
+pub trait TreeNode: Clone + Serialize + Sized {
+    /// Topic of the deltas, the relation table of the tree
+    const TOPIC: &'static str;
+
+    fn id(&self) -> &str;
+
+    /// None for the leaves that can't have children (e.g. a document)
+    fn children_mut(&mut self) -> Option<&mut Vec<Self>>;
+
+    fn children(&self) -> &[Self];
+
+    fn slot(indexes: &mut TreeIndexes) -> &mut Option<TreeIndex<Self>>;
+}
+
+/// The trees cached by the `ModelStore`, none until first loaded
+#[derive(Default)]
+pub struct TreeIndexes {
+    folders: Option<TreeIndex<DocumentsFolderChild>>,
+    categories: Option<TreeIndex<CategoryNode>>,
+    /// Bumped by every change of the trees, see `ModelStore::cache_tree`
+    generation: u64,
+}
+
+impl TreeIndexes {
+    pub(super) fn folders_slot(&mut self) -> &mut Option<TreeIndex<DocumentsFolderChild>> {
+        &mut self.folders
+    }
+
+    pub(super) fn categories_slot(&mut self) -> &mut Option<TreeIndex<CategoryNode>> {
+        &mut self.categories
+    }
+}
+
+/// A tree with the parent of each node, to reach any node without searching the whole tree
+pub struct TreeIndex<N: TreeNode> {
+    roots: Vec<N>,
+    parents: HashMap<String, Option<String>>,
+}
+
+impl<N: TreeNode> TreeIndex<N> {
+    pub fn new(roots: Vec<N>) -> Self {
+        let mut parents = HashMap::new();
+        for root in roots.iter() {
+            Self::index_subtree(&mut parents, root, None);
+        }
+        TreeIndex { roots, parents }
+    }
+
+    pub fn roots(&self) -> &[N] {
+        &self.roots
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.parents.contains_key(id)
+    }
+
+    /// The parent of the node (none for a root), none if the node isn't in the tree
+    pub fn parent(&self, id: &str) -> Option<Option<&str>> {
+        self.parents.get(id).map(Option::as_deref)
+    }
+
+    /// The children of the node (the roots if none)
+    fn children_of(&mut self, parent: Option<&str>) -> Option<&mut Vec<N>> {
+        let Some(parent) = parent else {
+            return Some(&mut self.roots);
+        };
+
+        let mut path = vec![parent.to_string()];
+        while let Some(Some(ancestor)) = self.parents.get(path.last()?) {
+            path.push(ancestor.clone());
+        }
+
+        let mut children = &mut self.roots;
+        for id in path.iter().rev() {
+            let node = children.iter_mut().find(|node| node.id() == id)?;
+            children = node.children_mut()?;
+        }
+        Some(children)
+    }
+
+    fn forget(&mut self, node: &N) {
+        self.parents.remove(node.id());
+        for child in node.children() {
+            self.forget(child);
+        }
+    }
+
+    fn take(&mut self, id: &str) -> Option<(N, Option<String>, usize)> {
+        let parent = self.parents.get(id)?.clone();
+        let children = self.children_of(parent.as_deref())?;
+        let position = children.iter().position(|node| node.id() == id)?;
+        Some((children.remove(position), parent, position))
+    }
+
+    pub fn insert(&mut self, parent: Option<&str>, node: N) -> Option<TreeDelta<N>> {
+        if self.contains(node.id()) || parent.map_or(false, |parent| !self.contains(parent)) {
+            return None;
+        }
+
+        let children = self.children_of(parent)?;
+        children.push(node.clone());
+        let position = children.len() - 1;
+        Self::index_subtree(&mut self.parents, &node, parent);
+
+        Some(TreeDelta::Inserted {
+            parent: parent.map(String::from),
+            position,
+            node,
+        })
+    }
+
+    pub fn move_node(&mut self, id: &str, to: Option<&str>) -> Option<TreeDelta<N>> {
+        if to.map_or(false, |to| !self.contains(to)) {
+            return None;
+        }
+
+        let (node, from, from_position) = self.take(id)?;
+        let Some(children) = self.children_of(to) else {
+            // Note: `to` is in the subtree of the node, put it back
+            self.children_of(from.as_deref())?.insert(from_position, node);
+            return None;
+        };
+        children.push(node);
+        let position = children.len() - 1;
+        self.parents.insert(id.to_string(), to.map(String::from));
+
+        Some(TreeDelta::Moved {
+            id: id.to_string(),
+            from,
+            to: to.map(String::from),
+            position,
+        })
+    }
+
+    /// Replace the node with the same id, its children are kept
+    pub fn replace(&mut self, mut node: N) -> Option<TreeDelta<N>> {
+        let parent = self.parents.get(node.id())?.clone();
+        let children = self.children_of(parent.as_deref())?;
+        let current = children.iter_mut().find(|current| current.id() == node.id())?;
+        if let (Some(children), Some(current_children)) = (node.children_mut(), current.children_mut()) {
+            *children = std::mem::take(current_children);
+        }
+        *current = node.clone();
+
+        Some(TreeDelta::Updated { node })
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<TreeDelta<N>> {
+        let (node, parent, position) = self.take(id)?;
+        self.forget(&node);
+
+        Some(TreeDelta::Removed {
+            id: id.to_string(),
+            parent,
+            position,
+        })
+    }
+
+    fn index_subtree(parents: &mut HashMap<String, Option<String>>, node: &N, parent: Option<&str>) {
+        parents.insert(node.id().to_string(), parent.map(String::from));
+        for child in node.children() {
+            Self::index_subtree(parents, child, Some(node.id()));
+        }
+    }
+}
+
+impl ModelStore {
+    /// The roots of the cached tree, none if not loaded
+    pub(in crate::model) fn cached_tree<N: TreeNode>(&self) -> Option<Vec<N>> {
+        let mut indexes = self.trees().lock().ok()?;
+        N::slot(&mut indexes).as_ref().map(|index| index.roots().to_vec())
+    }
+
+    /// To read before querying a tree, for `cache_tree`
+    pub(in crate::model) fn tree_generation(&self) -> u64 {
+        self.trees().lock().map_or(0, |indexes| indexes.generation)
+    }
+
+    /// Cache the tree queried at the `generation`, unless the trees changed since
+    pub(in crate::model) fn cache_tree<N: TreeNode>(&self, roots: Vec<N>, generation: u64) {
+        if let Ok(mut indexes) = self.trees().lock() {
+            if indexes.generation == generation {
+                *N::slot(&mut indexes) = Some(TreeIndex::new(roots));
+            }
+        }
+    }
+}
+
+/// Patch the cached tree and broadcast the delta. Returns `Reset` when the tree isn't loaded, or when
+/// the patch doesn't apply (the index is dropped, and the `Reset` broadcast).
+pub(super) fn update_tree<N, P>(ctx: &Ctx, patch: P) -> TreeDelta<N>
+where
+    N: TreeNode,
+    P: FnOnce(&mut TreeIndex<N>) -> Option<TreeDelta<N>>,
+{
+    let model_manager = ctx.get_model_manager();
+    let Ok(mut indexes) = model_manager.trees().lock() else {
+        return TreeDelta::Reset;
+    };
+    indexes.generation += 1;
+    let slot = N::slot(&mut indexes);
+    // Note: nothing to patch, the tree will be built by the next `list_tree`
+    let Some(index) = slot.as_mut() else {
+        return TreeDelta::Reset;
+    };
+
+    let delta = patch(index).unwrap_or(TreeDelta::Reset);
+    if matches!(delta, TreeDelta::Reset) {
+        *slot = None;
+    }
+    drop(indexes);
+
+    ctx.emit_hub_event(HubEvent {
+        hub: TREES_HUB.to_string(),
+        topic: N::TOPIC.to_string(),
+        label: Some("delta".to_string()),
+        data: Some(delta.clone()),
+    });
+    delta
+}
+
+/// Remove the trashed nodes (with their subtree) from the cached trees
+pub(super) fn remove_trashed(ctx: &Ctx, ids: &[String]) {
+    fn remove<N: TreeNode>(ctx: &Ctx, id: &str) {
+        let model_manager = ctx.get_model_manager();
+        let cached = model_manager.trees().lock().map_or(false, |mut indexes| {
+            N::slot(&mut indexes).as_ref().map_or(false, |index| index.contains(id))
+        });
+        if cached {
+            update_tree::<N, _>(ctx, |index| index.remove(id));
+        }
+    }
+
+    for id in ids {
+        remove::<DocumentsFolderChild>(ctx, id);
+        remove::<CategoryNode>(ctx, id);
+    }
+}
+
+/// Drop the cached trees holding the records of the entity (or relation table)
+pub(super) fn invalidate_tree(ctx: &Ctx, entity: &str) {
+    let model_manager = ctx.get_model_manager();
+    let Ok(mut indexes) = model_manager.trees().lock() else {
+        return;
+    };
+    indexes.generation += 1;
+
+    let folders = [DocumentBmc::ENTITY, DocumentsFolderBmc::ENTITY, DocumentsFolderBmc::RELATION_ENTITY];
+    let categories = [CategoryBmc::ENTITY, CategoryBmc::RELATION_ENTITY];
+    // Note: the events of the whole store (e.g. the vault import, the tree repair) drop all the trees
+    let global = entity.starts_with('_') || entity == "vault";
+
+    if global || folders.contains(&entity) {
+        indexes.folders = None;
+    }
+    if global || categories.contains(&entity) {
+        indexes.categories = None;
+    }
+}
+
+/// Replace the node of the updated document or folder (see `TreeIndex::replace`), called by `bmc_update`.
+/// The folders tree holds the `DocumentSummary` of the documents, the changes of the body are ignored.
+pub(super) fn patch_updated_record(ctx: &Ctx, entity: &str, record: &Object) {
+    let node: Option<DocumentsFolderChild> = if entity == DocumentBmc::ENTITY {
+        DocumentSummary::try_from(record.clone()).ok().map(DocumentsFolderChild::DocumentSummary)
+    } else if entity == DocumentsFolderBmc::ENTITY {
+        DocumentsFolder::try_from(record.clone()).ok().map(DocumentsFolderChild::from)
+    } else {
+        return;
+    };
+
+    match node {
+        Some(node) => patch_folders_tree_node(ctx, node),
+        None => invalidate_tree(ctx, entity),
+    }
+}
+
+/// Replace the node of the folders tree, if it's loaded
+pub(super) fn patch_folders_tree_node(ctx: &Ctx, node: DocumentsFolderChild) {
+    let model_manager = ctx.get_model_manager();
+    let cached = model_manager.trees().lock().map_or(false, |mut indexes| {
+        DocumentsFolderChild::slot(&mut indexes).as_ref().map_or(false, |index| index.contains(node.id()))
+    });
+    if cached {
+        update_tree::<DocumentsFolderChild, _>(ctx, |index| index.replace(node));
+    } else {
+        invalidate_tree(ctx, DocumentBmc::ENTITY);
+    }
+}
+
+/// Called for each model event. The created, moved and trashed nodes are patched by their controllers,
+/// the updated documents and folders by `patch_updated_record`.
+pub(super) fn invalidate_trees(ctx: &Ctx, entity: &str, action: &str) {
+    let patched = ["create", "move", "trash"].contains(&action)
+        || (action == "update" && [DocumentBmc::ENTITY, DocumentsFolderBmc::ENTITY].contains(&entity));
+    if !patched {
+        invalidate_tree(ctx, entity);
+    }
+    // Note: the merged tags are replaced in the tags of the document summaries
+    if entity == TagBmc::ENTITY && action == "merge" {
+        invalidate_tree(ctx, DocumentBmc::ENTITY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Serialize, PartialEq)]
+    struct Node(String, Vec<Node>);
+
+    impl TreeNode for Node {
+        const TOPIC: &'static str = "nodes";
+
+        fn id(&self) -> &str {
+            &self.0
+        }
+
+        fn children_mut(&mut self) -> Option<&mut Vec<Self>> {
+            Some(&mut self.1)
+        }
+
+        fn children(&self) -> &[Self] {
+            &self.1
+        }
+
+        fn slot(_: &mut TreeIndexes) -> &mut Option<TreeIndex<Self>> {
+            unreachable!()
+        }
+    }
+
+    fn node(id: &str, children: Vec<Node>) -> Node {
+        Node(id.to_string(), children)
+    }
+
+    #[test]
+    fn test_tree_index_deltas() {
+        let mut index = TreeIndex::new(vec![node("a", vec![node("b", vec![node("c", vec![])])]), node("d", vec![])]);
+
+        let delta = index.move_node("c", Some("d"));
+        assert_eq!(
+            delta,
+            Some(TreeDelta::Moved { id: "c".into(), from: Some("b".into()), to: Some("d".into()), position: 0 })
+        );
+        // -- Into its own subtree
+        assert_eq!(index.move_node("a", Some("b")), None);
+        assert_eq!(index.roots()[0], node("a", vec![node("b", vec![])]));
+
+        let delta = index.insert(Some("b"), node("e", vec![]));
+        assert_eq!(delta, Some(TreeDelta::Inserted { parent: Some("b".into()), position: 0, node: node("e", vec![]) }));
+
+        let delta = index.replace(node("b", vec![node("f", vec![])]));
+        assert_eq!(delta, Some(TreeDelta::Updated { node: node("b", vec![node("e", vec![])]) }));
+        assert_eq!(index.replace(node("x", vec![])), None);
+
+        let delta = index.remove("a");
+        assert_eq!(delta, Some(TreeDelta::Removed { id: "a".into(), parent: None, position: 0 }));
+        assert!(!index.contains("e"));
+        assert_eq!(index.roots(), &[node("d", vec![node("c", vec![])])]);
+    }
+}
@@ -0,0 +1,213 @@
+//! Detection and repair of the broken hierarchies (folders, categories and tags trees).
+//!
+//! `bmc_relate` and `bmc_rerelate_edge` reject the cycles and the second parents (see `bmc_check_tree_edge`),
+//! but the stores written before, or by an import, may hold such edges. The tree builders then lose nodes.
+//! The repair removes the faulty edges, the detached nodes become roots of their tree.
+//!
+//! Notes:
+//!     - A node with many parents keeps its first edge (by edge id).
+//!     - A cycle is broken at the edge to its smallest node, so the repair is deterministic.
+//!     - An alive node attached to a trashed parent is detached as well (it's hidden from the trees otherwise).
+
+use super::bmc_base::bmc_custom_solo_query;
+use super::bmc_graph::GraphBmc;
+use super::fire_model_event;
+use super::store::x_take::XTake;
+use super::store::StoreTransaction;
+use crate::algo::graph::topological_sort;
+use crate::model::ctx::Ctx;
+use crate::model::{CategoryBmc, DocumentsFolderBmc, Error, Result, TagBmc};
+use crate::prelude::f;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use surrealdb::sql::Object;
+use ts_gen::TS;
+
+/// Relation tables holding a tree
+fn tree_relations() -> [&'static str; 3] {
+    [
+        DocumentsFolderBmc::RELATION_ENTITY,
+        CategoryBmc::RELATION_ENTITY,
+        TagBmc::RELATION_ENTITY,
+    ]
+}
+
+#[derive(Debug, Serialize, TS, Clone, Copy, PartialEq)]
+#[ts(export)]
+pub enum TreeDefect {
+    /// The child has another parent
+    MultiParent,
+    /// The edge closes a cycle
+    Cycle,
+    /// The child is alive, the parent is in the trash
+    TrashedParent,
+}
+
+#[derive(Debug, Serialize, TS, Clone)]
+#[ts(export)]
+pub struct TreeRepair {
+    pub relation: String,
+    /// Id of the removed edge
+    pub edge: String,
+    pub parent: String,
+    pub child: String,
+    pub defect: TreeDefect,
+}
+
+#[derive(Debug, Clone)]
+struct TreeEdge {
+    id: String,
+    parent: String,
+    child: String,
+    trashed_parent: bool,
+}
+
+impl TryFrom<Object> for TreeEdge {
+    type Error = Error;
+    fn try_from(mut val: Object) -> Result<TreeEdge> {
+        Ok(Self {
+            id: val.x_take_val("id")?,
+            parent: val.x_take_val("in")?,
+            child: val.x_take_val("out")?,
+            trashed_parent: val.x_take("trashed_parent")?.unwrap_or_default(),
+        })
+    }
+}
+
+/// The faulty edges, by index in `edges`
+fn find_defects(edges: &[TreeEdge]) -> Vec<(usize, TreeDefect)> {
+    let mut defects = vec![];
+
+    // The first edge of each child is its parent, the other ones are faulty
+    let mut parents = HashMap::<&str, (usize, &str)>::new();
+    for (idx, edge) in edges.iter().enumerate() {
+        if edge.trashed_parent {
+            defects.push((idx, TreeDefect::TrashedParent));
+        } else if parents.contains_key(edge.child.as_str()) {
+            defects.push((idx, TreeDefect::MultiParent));
+        } else {
+            parents.insert(&edge.child, (idx, &edge.parent));
+        }
+    }
+
+    // Every node has one parent at most now, each unresolved node is in a cycle or below one
+    loop {
+        let pairs: Vec<(&str, &str)> = parents.iter().map(|(child, (_, parent))| (*parent, *child)).collect();
+        let Err(unresolved) = topological_sort(&pairs[..]) else {
+            break;
+        };
+        let Some(start) = unresolved.into_iter().min() else {
+            break;
+        };
+
+        // Walk up from the node to find its cycle
+        let mut path = vec![start];
+        let mut node = start;
+        while let Some(&(_, parent)) = parents.get(node) {
+            if let Some(position) = path.iter().position(|n| *n == parent) {
+                let cycle = &path[position..];
+                let smallest = *cycle.iter().min().unwrap_or(&start);
+                if let Some((idx, _)) = parents.remove(smallest) {
+                    defects.push((idx, TreeDefect::Cycle));
+                }
+                break;
+            }
+            path.push(parent);
+            node = parent;
+        }
+    }
+
+    defects.sort_by_key(|(idx, _)| *idx);
+    defects
+}
+
+pub struct TreeRepairBmc;
+
+impl TreeRepairBmc {
+    async fn list_edges(ctx: Arc<Ctx>, relation: &'static str) -> Result<Vec<TreeEdge>> {
+        let sql = f!(
+            "SELECT id, in, out, (in.dtime IS NOT NONE AND out.dtime IS NONE) AS trashed_parent \
+            FROM {relation} ORDER BY id;"
+        );
+        bmc_custom_solo_query::<TreeEdge>(ctx, relation, &sql, None).await
+    }
+
+    /// Remove the faulty edges of all the trees in one transaction, or only report them if `dry_run` is set
+    pub async fn repair(ctx: Arc<Ctx>, dry_run: bool) -> Result<Vec<TreeRepair>> {
+        let mut repairs = vec![];
+        for relation in tree_relations() {
+            let edges = Self::list_edges(ctx.clone(), relation).await?;
+            for (idx, defect) in find_defects(&edges) {
+                let edge = &edges[idx];
+                repairs.push(TreeRepair {
+                    relation: relation.to_string(),
+                    edge: edge.id.clone(),
+                    parent: edge.parent.clone(),
+                    child: edge.child.clone(),
+                    defect,
+                });
+            }
+        }
+
+        if dry_run || repairs.is_empty() {
+            return Ok(repairs);
+        }
+
+        let mut tx = StoreTransaction::new();
+        let removed: HashSet<&str> = repairs.iter().map(|repair| repair.edge.as_str()).collect();
+        for edge in removed {
+            tx.delete(edge);
+        }
+        ctx.get_model_manager().store().exec_transaction(tx).await?;
+
+        fire_model_event(&ctx, "_trees", "repair", repairs.clone());
+
+        Ok(repairs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(id: &str, parent: &str, child: &str) -> TreeEdge {
+        TreeEdge {
+            id: id.into(),
+            parent: parent.into(),
+            child: child.into(),
+            trashed_parent: false,
+        }
+    }
+
+    #[test]
+    fn finds_the_defects() {
+        let mut edges = vec![
+            edge("e:1", "c:root", "c:a"),
+            edge("e:2", "c:a", "c:b"),
+            edge("e:3", "c:b", "c:a"),
+            edge("e:4", "c:root", "c:b"),
+            edge("e:5", "c:x", "c:y"),
+            edge("e:6", "c:y", "c:x"),
+            edge("e:7", "c:trash", "c:z"),
+        ];
+        edges[6].trashed_parent = true;
+
+        let defects = find_defects(&edges);
+        assert_eq!(
+            defects,
+            vec![
+                (2, TreeDefect::MultiParent),
+                (3, TreeDefect::MultiParent),
+                (5, TreeDefect::Cycle),
+                (6, TreeDefect::TrashedParent),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_sound_trees() {
+        let edges = vec![edge("e:1", "c:root", "c:a"), edge("e:2", "c:a", "c:b"), edge("e:3", "c:root", "c:c")];
+        assert!(find_defects(&edges).is_empty());
+    }
+}
@@ -0,0 +1,575 @@
+//! Whole-vault export and import as a portable archive.
+//!
+//! The archive is a zstd-compressed stream: a MessagePack `VaultManifest` holding the schema version of the archive,
+//! then the named entries (a MessagePack `VaultEntryHeader` followed by the bytes of the entry), in this order:
+//!     - `tables/<table>.json`: all the records of a table (trashed records included)
+//!     - `edges/<relation>.json`: all the edges of a relation table (folders, categories, links)
+//!     - `files/<n>-<file name>`: the picture files referenced by the `picture` records
+//!
+//! Notes:
+//!     - Record ids are exported as raw strings (e.g. `document:abc`), references are remapped on import
+//!       by replacing the strings that exactly match a remapped id (plus the `[[document:id]]` wiki-links).
+//!     - The picture files are streamed from and to disk on a blocking thread, one at a time:
+//!       the archive is never held in memory, only the records.
+//!     - The import is atomic: the whole archive is read before any write, the records are written in one transaction.
+//!     - The imported picture files are stored in the media library (or in `get_pictures_path()` for the
+//!       formats it can't decode, e.g. SVG).
+
+use super::bmc_base::Bmc;
+use super::bmc_graph::GraphBmc;
+use super::document_link::PENDING_LINK_ENTITY;
+use super::picture_asset::reload_picture_assets;
+use super::picture_similarity::invalidate_picture_hashes;
+use super::store::x_take::XTake;
+use super::store::StoreTransaction;
+use super::{fire_model_event, vmap};
+use crate::fs::media::{dhash_to_string, store_media, MediaFile};
+use crate::fs::{free_file_path, get_pictures_path, path_to_string};
+use crate::model::ctx::Ctx;
+use crate::model::{
+    CategoryBmc, DocumentBmc, DocumentLinkBmc, DocumentRevisionBmc, DocumentsFolderBmc,
+    DocumentsTemplateBmc, Error, PictureBmc, Result, TagBmc,
+};
+use crate::prelude::f;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use surrealdb::sql::{thing, Datetime, Id, Object, Thing, Value};
+use ts_gen::TS;
+
+pub const VAULT_FORMAT: &str = "loreapp-vault";
+/// Bump when the layout of the exported records changes
+pub const VAULT_SCHEMA_VERSION: u32 = 1;
+
+/// Record fields holding a record id that must be stored as a record link
+const RECORD_LINK_FIELDS: [&str; 7] = ["id", "in", "out", "folder", "parent", "trash_root", "trash_parent"];
+
+/// Record fields holding an array of record ids that must be stored as record links
+const RECORD_LINKS_FIELDS: [&str; 2] = ["tags", "categories"];
+
+fn vault_tables() -> [&'static str; 8] {
+    [
+        DocumentBmc::ENTITY,
+        PENDING_LINK_ENTITY,
+        DocumentsFolderBmc::ENTITY,
+        DocumentsTemplateBmc::ENTITY,
+        DocumentRevisionBmc::ENTITY,
+        PictureBmc::ENTITY,
+        TagBmc::ENTITY,
+        CategoryBmc::ENTITY,
+    ]
+}
+
+fn vault_relations() -> [&'static str; 4] {
+    [
+        DocumentsFolderBmc::RELATION_ENTITY,
+        CategoryBmc::RELATION_ENTITY,
+        TagBmc::RELATION_ENTITY,
+        DocumentLinkBmc::RELATION_ENTITY,
+    ]
+}
+
+#[derive(Debug, Serialize, Deserialize, TS, Clone, Copy, PartialEq, Eq)]
+#[ts(export)]
+pub enum VaultImportMode {
+    /// Keep the current vault and add the archive records, conflicting ids are remapped
+    Merge,
+    /// Wipe the current vault before importing the archive
+    Replace,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS, Clone)]
+#[ts(export)]
+pub struct VaultTableInfo {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS, Clone)]
+#[ts(export)]
+pub struct VaultManifest {
+    pub format: String,
+    pub schema_version: u32,
+    pub app_version: String,
+    pub ctime: String,
+    pub tables: Vec<VaultTableInfo>,
+    pub edges: Vec<VaultTableInfo>,
+    /// Picture id -> name of the archive entry holding its file
+    pub picture_files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, TS, Clone, Default)]
+#[ts(export)]
+pub struct VaultImportReport {
+    pub records: usize,
+    pub edges: usize,
+    pub files: usize,
+    /// Number of records imported under a new id because their id was already used
+    pub remapped: usize,
+}
+
+/// Header of an archive entry, followed by its `len` bytes
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultEntryHeader {
+    name: String,
+    len: u64,
+}
+
+type ArchiveReader = zstd::Decoder<'static, BufReader<fs::File>>;
+
+/// The manifest and the records of an archive, `reader` is positioned on the first picture file
+struct VaultArchive {
+    manifest: VaultManifest,
+    json_entries: HashMap<String, Vec<JsonValue>>,
+    reader: ArchiveReader,
+}
+
+impl VaultArchive {
+    fn json_entry(&mut self, name: &str) -> Vec<JsonValue> {
+        self.json_entries.remove(name).unwrap_or_default()
+    }
+}
+
+fn write_entry(writer: &mut impl Write, name: &str, len: u64, data: &mut impl Read) -> std::io::Result<()> {
+    let header = VaultEntryHeader { name: name.to_string(), len };
+    rmp_serde::encode::write_named(writer, &header).map_err(|ex| std::io::Error::new(ErrorKind::InvalidData, ex))?;
+    let copied = std::io::copy(&mut data.take(len), writer)?;
+    if copied != len {
+        return Err(std::io::Error::new(ErrorKind::UnexpectedEof, f!("'{name}' was truncated while exporting")));
+    }
+
+    Ok(())
+}
+
+fn read_entry(reader: &mut impl Read, path: &str) -> Result<(String, Vec<u8>)> {
+    let header: VaultEntryHeader = rmp_serde::decode::from_read(&mut *reader)
+        .map_err(|ex| Error::Other(f!("Invalid vault archive '{path}': {ex}")))?;
+
+    let mut data = vec![];
+    reader
+        .take(header.len)
+        .read_to_end(&mut data)
+        .map_err(|ex| io_error(Path::new(path), ex))?;
+    if data.len() as u64 != header.len {
+        return Err(Error::Other(f!("Vault archive entry '{}' is truncated", header.name)));
+    }
+
+    Ok((header.name, data))
+}
+
+/// Write the manifest, the `tables/` and `edges/` entries, then the picture files (streamed from disk).
+/// Note: Blocking. The pictures that aren't a file on disk (e.g. data URLs) are kept as is in their record.
+fn write_archive(
+    path: &Path,
+    mut manifest: VaultManifest,
+    entries: Vec<(String, Vec<u8>)>,
+    pictures: Vec<(String, String)>,
+) -> Result<VaultManifest> {
+    let mut files = Vec::<(String, PathBuf)>::new();
+    for (id, file_path) in pictures {
+        let file_path = PathBuf::from(file_path);
+        if !file_path.is_file() {
+            continue;
+        }
+
+        let file_name = file_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let name = f!("files/{}-{file_name}", files.len());
+        manifest.picture_files.insert(id, name.clone());
+        files.push((name, file_path));
+    }
+
+    let file = fs::File::create(path).map_err(|ex| io_error(path, ex))?;
+    let mut encoder = zstd::Encoder::new(BufWriter::new(file), zstd::DEFAULT_COMPRESSION_LEVEL)
+        .map_err(|ex| io_error(path, ex))?;
+    rmp_serde::encode::write_named(&mut encoder, &manifest).map_err(|ex| Error::Other(ex.to_string()))?;
+    for (name, data) in entries {
+        write_entry(&mut encoder, &name, data.len() as u64, &mut data.as_slice()).map_err(|ex| io_error(path, ex))?;
+    }
+    for (name, file_path) in files {
+        let mut file = fs::File::open(&file_path).map_err(|ex| io_error(&file_path, ex))?;
+        let len = file.metadata().map_err(|ex| io_error(&file_path, ex))?.len();
+        write_entry(&mut encoder, &name, len, &mut file).map_err(|ex| io_error(&file_path, ex))?;
+    }
+    encoder
+        .finish()
+        .and_then(|mut writer| writer.flush())
+        .map_err(|ex| io_error(path, ex))?;
+
+    Ok(manifest)
+}
+
+/// Read the picture file entries and store them in the media library (or in `get_pictures_path()`).
+/// Returns the files by picture id.
+/// Note: Blocking. One file is in memory at a time (it's decoded for its thumbnails and hashes).
+fn read_picture_files(
+    mut reader: ArchiveReader,
+    path: &str,
+    picture_files: BTreeMap<String, String>,
+) -> Result<HashMap<String, PictureFile>> {
+    let mut files = HashMap::<String, PictureFile>::new();
+    if picture_files.is_empty() {
+        return Ok(files);
+    }
+
+    let pictures_dir = get_pictures_path();
+    fs::create_dir_all(&pictures_dir).map_err(|ex| io_error(&pictures_dir, ex))?;
+
+    let ids: HashMap<String, String> = picture_files.into_iter().map(|(id, name)| (name, id)).collect();
+    for _ in 0..ids.len() {
+        let (name, data) = read_entry(&mut reader, path)?;
+        let id = ids
+            .get(&name)
+            .ok_or(Error::Other(f!("Unexpected vault archive entry '{name}'")))?;
+
+        let file = match store_media(&data) {
+            Ok(media) => PictureFile::Media(media),
+            Err(ex) => {
+                warn!("Vault archive entry '{name}' kept out of the media library: {ex}");
+                let file_name = name.split_once('-').map(|(_, n)| n).unwrap_or(&name);
+                let file_path = free_file_path(&pictures_dir, file_name);
+                fs::write(&file_path, &data).map_err(|ex| io_error(&file_path, ex))?;
+                PictureFile::Path(path_to_string(&file_path))
+            }
+        };
+        files.insert(id.clone(), file);
+    }
+
+    Ok(files)
+}
+
+/// Replace every string matching a remapped id, and the ids of the `[[document:id]]` wiki-links
+fn remap_ids(value: &mut JsonValue, remap: &HashMap<String, String>) {
+    match value {
+        JsonValue::String(s) => {
+            if let Some(new_id) = remap.get(s.as_str()) {
+                *s = new_id.clone();
+            } else if s.contains("[[") {
+                for (old_id, new_id) in remap.iter() {
+                    for end in ["]]", "|", "#"] {
+                        *s = s.replace(&f!("[[{old_id}{end}"), &f!("[[{new_id}{end}"));
+                    }
+                }
+            }
+        }
+        JsonValue::Array(values) => values.iter_mut().for_each(|v| remap_ids(v, remap)),
+        JsonValue::Object(fields) => fields.values_mut().for_each(|v| remap_ids(v, remap)),
+        _ => {}
+    }
+}
+
+/// Convert an exported JSON record back to a SurrealDB object, with the record links restored
+fn json_to_object(record: &JsonValue) -> Result<Object> {
+    let value = surrealdb::sql::json(&record.to_string()).map_err(|ex| Error::Store(ex.into()))?;
+    let Value::Object(mut obj) = value else {
+        return Err(Error::Other(f!("Invalid vault record: {record}")));
+    };
+
+    for field in RECORD_LINK_FIELDS {
+        if let Some(Value::Strand(raw)) = obj.get(field) {
+            let tid = thing(raw.as_str()).map_err(|ex| Error::Store(ex.into()))?;
+            obj.insert(field.into(), tid.into());
+        }
+    }
+    // Note: The revisions keep their tags and categories as strings, see `retain_alive_links`
+    let is_revision = matches!(obj.get("id"), Some(Value::Thing(id)) if id.tb == DocumentRevisionBmc::ENTITY);
+    if !is_revision {
+        for field in RECORD_LINKS_FIELDS {
+            if let Some(Value::Array(ids)) = obj.get_mut(field) {
+                for id in ids.iter_mut() {
+                    if let Value::Strand(raw) = id {
+                        *id = thing(raw.as_str()).map_err(|ex| Error::Store(ex.into()))?.into();
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(obj)
+}
+
+/// Where an imported picture file was written
+enum PictureFile {
+    Media(MediaFile),
+    Path(String),
+}
+
+/// The raw id of the record link field (converted by `json_to_object`)
+fn take_record_id(obj: &mut Object, field: &str) -> Option<String> {
+    match obj.remove(field)? {
+        Value::Thing(tid) => Some(tid.to_raw()),
+        _ => None,
+    }
+}
+
+fn io_error(path: &Path, ex: std::io::Error) -> Error {
+    Error::Other(f!("{}: {ex}", path.display()))
+}
+
+pub struct VaultBmc;
+
+impl VaultBmc {
+    async fn select_all(ctx: Arc<Ctx>, tb: &str) -> Result<Vec<JsonValue>> {
+        let sql = "SELECT * FROM type::table($tb);";
+        let vars = vmap!("tb".into() => tb.into());
+        let objects = ctx
+            .get_model_manager()
+            .store()
+            .exec_custom_solo_query(sql, Some(vars.into()))
+            .await?;
+
+        Ok(objects
+            .into_iter()
+            .map(|obj| Value::Object(obj).into_json())
+            .collect())
+    }
+
+    async fn select_ids(ctx: Arc<Ctx>, tb: &str) -> Result<HashSet<String>> {
+        let sql = "SELECT id FROM type::table($tb);";
+        let vars = vmap!("tb".into() => tb.into());
+        let objects = ctx
+            .get_model_manager()
+            .store()
+            .exec_custom_solo_query(sql, Some(vars.into()))
+            .await?;
+
+        objects
+            .into_iter()
+            .map(|mut obj| obj.x_take_val::<String>("id"))
+            .collect::<std::result::Result<_, _>>()
+            .map_err(Error::Store)
+    }
+
+    /// Write the whole vault to a zstd-compressed archive at `path`
+    pub async fn export(ctx: Arc<Ctx>, path: &str) -> Result<VaultManifest> {
+        let mut entries = Vec::<(String, Vec<u8>)>::new();
+        let mut tables = Vec::<VaultTableInfo>::new();
+        let mut edges = Vec::<VaultTableInfo>::new();
+        let mut pictures = Vec::<(String, String)>::new();
+
+        for tb in vault_tables() {
+            let records = Self::select_all(ctx.clone(), tb).await?;
+
+            if tb.eq(PictureBmc::ENTITY) {
+                for record in records.iter() {
+                    if let (Some(id), Some(file_path)) = (record["id"].as_str(), record["path"].as_str()) {
+                        pictures.push((id.to_string(), file_path.to_string()));
+                    }
+                }
+            }
+
+            tables.push(VaultTableInfo { name: tb.to_string(), count: records.len() });
+            let data = serde_json::to_vec(&records).map_err(|ex| Error::Other(ex.to_string()))?;
+            entries.push((f!("tables/{tb}.json"), data));
+        }
+
+        for relation in vault_relations() {
+            let records = Self::select_all(ctx.clone(), relation).await?;
+            edges.push(VaultTableInfo { name: relation.to_string(), count: records.len() });
+            let data = serde_json::to_vec(&records).map_err(|ex| Error::Other(ex.to_string()))?;
+            entries.push((f!("edges/{relation}.json"), data));
+        }
+
+        let manifest = VaultManifest {
+            format: VAULT_FORMAT.to_string(),
+            schema_version: VAULT_SCHEMA_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            ctime: Datetime::default().to_string(),
+            tables,
+            edges,
+            picture_files: BTreeMap::new(),
+        };
+
+        let archive_path = PathBuf::from(path);
+        tauri::async_runtime::spawn_blocking(move || {
+            let written = write_archive(&archive_path, manifest, entries, pictures);
+            if written.is_err() {
+                // Note: No partial archive is left behind
+                let _ = fs::remove_file(&archive_path);
+            }
+            written
+        })
+        .await
+        .map_err(|ex| Error::Other(ex.to_string()))?
+    }
+
+    /// Read the manifest and the records of the archive (blocking), the picture files are read by `read_picture_files`
+    fn read_archive(path: &str) -> Result<VaultArchive> {
+        let file = fs::File::open(path).map_err(|ex| io_error(Path::new(path), ex))?;
+        let mut reader = zstd::Decoder::new(file).map_err(|ex| io_error(Path::new(path), ex))?;
+        let manifest: VaultManifest = rmp_serde::decode::from_read(&mut reader)
+            .map_err(|ex| Error::Other(f!("'{path}' is not a vault archive: {ex}")))?;
+
+        if manifest.format != VAULT_FORMAT {
+            return Err(Error::Other(f!("'{path}' is not a vault archive")));
+        }
+        if manifest.schema_version > VAULT_SCHEMA_VERSION {
+            return Err(Error::Other(f!(
+                "The vault archive schema version {} is newer than the supported one ({VAULT_SCHEMA_VERSION})",
+                manifest.schema_version
+            )));
+        }
+
+        let mut json_entries = HashMap::<String, Vec<JsonValue>>::new();
+        for _ in 0..manifest.tables.len() + manifest.edges.len() {
+            let (name, data) = read_entry(&mut reader, path)?;
+            let records = serde_json::from_slice(&data)
+                .map_err(|ex| Error::Other(f!("Invalid vault entry '{name}': {ex}")))?;
+            json_entries.insert(name, records);
+        }
+
+        Ok(VaultArchive { manifest, json_entries, reader })
+    }
+
+    /// Import the archive at `path` into the vault.
+    /// Note: The whole archive is read first, then the records and edges are written in one transaction
+    ///       (after wiping the vault in `Replace` mode): an invalid archive leaves the vault untouched.
+    pub async fn import(ctx: Arc<Ctx>, path: &str, mode: VaultImportMode) -> Result<VaultImportReport> {
+        let archive_path = path.to_string();
+        let mut archive = tauri::async_runtime::spawn_blocking(move || Self::read_archive(&archive_path))
+            .await
+            .map_err(|ex| Error::Other(ex.to_string()))??;
+        let mut report = VaultImportReport::default();
+
+        let mut records = Vec::<(&'static str, Vec<JsonValue>)>::new();
+        for tb in vault_tables() {
+            records.push((tb, archive.json_entry(&f!("tables/{tb}.json"))));
+        }
+        let mut edges = Vec::<(&'static str, Vec<JsonValue>)>::new();
+        for relation in vault_relations() {
+            edges.push((relation, archive.json_entry(&f!("edges/{relation}.json"))));
+        }
+
+        // -- Remap the ids already used by the vault
+        let mut remap = HashMap::<String, String>::new();
+        if mode == VaultImportMode::Merge {
+            for (tb, tb_records) in records.iter() {
+                let existing = Self::select_ids(ctx.clone(), tb).await?;
+                for id in tb_records.iter().filter_map(|r| r["id"].as_str()) {
+                    if existing.contains(id) {
+                        remap.insert(id.to_string(), Thing::from((tb.to_string(), Id::rand())).to_raw());
+                    }
+                }
+            }
+        }
+        report.remapped = remap.len();
+
+        // -- Copy the picture files
+        // Note: The media library is stored by content, the copied files are harmless if the import fails
+        let archive_path = path.to_string();
+        let VaultArchive { manifest, reader, .. } = archive;
+        let picture_files = tauri::async_runtime::spawn_blocking(move || {
+            read_picture_files(reader, &archive_path, manifest.picture_files)
+        })
+        .await
+        .map_err(|ex| Error::Other(ex.to_string()))??;
+        report.files = picture_files.len();
+
+        let mut tx = StoreTransaction::new();
+        if mode == VaultImportMode::Replace {
+            for tb in vault_relations().into_iter().chain(vault_tables()) {
+                tx.delete_all(tb);
+            }
+        }
+
+        // -- Records
+        for (tb, tb_records) in records.iter_mut() {
+            for record in tb_records.iter_mut() {
+                if *tb == PictureBmc::ENTITY {
+                    match record["id"].as_str().and_then(|id| picture_files.get(id)) {
+                        Some(PictureFile::Media(media)) => {
+                            record["path"] = media.path.clone().into();
+                            record["hash"] = media.hash.clone().into();
+                            record["width"] = media.width.into();
+                            record["height"] = media.height.into();
+                            record["dhash"] = dhash_to_string(media.dhash).into();
+                        }
+                        Some(PictureFile::Path(path)) => record["path"] = path.clone().into(),
+                        None => {}
+                    }
+                }
+                remap_ids(record, &remap);
+
+                let mut data = json_to_object(record)?;
+                let id = take_record_id(&mut data, "id").ok_or(Error::Other(f!("Vault record without id in '{tb}'")))?;
+                tx.insert(&id, data);
+                report.records += 1;
+            }
+        }
+
+        // -- Edges
+        for (relation, relation_edges) in edges.iter_mut() {
+            for edge in relation_edges.iter_mut() {
+                remap_ids(edge, &remap);
+
+                let mut data = json_to_object(edge)?;
+                data.remove("id");
+                let (Some(r#in), Some(out)) = (take_record_id(&mut data, "in"), take_record_id(&mut data, "out")) else {
+                    continue;
+                };
+                tx.relate_with(&r#in, *relation, &out, data);
+                report.edges += 1;
+            }
+        }
+
+        if !tx.is_empty() {
+            ctx.get_model_manager().store().exec_transaction(tx).await?;
+        }
+
+        fire_model_event(&ctx, "vault", "import", report.clone());
+        invalidate_picture_hashes(&ctx);
+        reload_picture_assets(&ctx).await?;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_remap_ids() {
+        let remap = HashMap::from([("document:a".to_string(), "document:b".to_string())]);
+        let mut record = json!({
+            "id": "document:a",
+            "used_by": ["document:a", "document:ab"],
+            "body": "See [[document:a|A]], [[document:ab]] and [[document:a]]",
+        });
+
+        remap_ids(&mut record, &remap);
+
+        assert_eq!(
+            record,
+            json!({
+                "id": "document:b",
+                "used_by": ["document:b", "document:ab"],
+                "body": "See [[document:b|A]], [[document:ab]] and [[document:b]]",
+            })
+        );
+    }
+
+    #[test]
+    fn test_entries() -> Result<()> {
+        let mut archive = Vec::<u8>::new();
+        write_entry(&mut archive, "tables/document.json", 2, &mut b"[]".as_slice()).unwrap();
+        write_entry(&mut archive, "files/0-a.png", 4, &mut b"\x89PNG".as_slice()).unwrap();
+
+        let mut reader = archive.as_slice();
+        assert_eq!(read_entry(&mut reader, "vault")?, ("tables/document.json".to_string(), b"[]".to_vec()));
+        assert_eq!(read_entry(&mut reader, "vault")?, ("files/0-a.png".to_string(), b"\x89PNG".to_vec()));
+        assert!(reader.is_empty());
+
+        // A file shorter than its header fails the export, and a truncated entry fails the import
+        assert!(write_entry(&mut vec![], "files/1-b.png", 8, &mut b"\x89PNG".as_slice()).is_err());
+        let mut reader = &archive[..archive.len() - 1];
+        read_entry(&mut reader, "vault")?;
+        assert!(read_entry(&mut reader, "vault").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+//! `loreapp://` URI scheme: streams the picture files and thumbnails of the vault to the webviews,
+//! instead of sending them as base64 data URLs through IPC.
+//!
+//! URLs (see `picture_url` and `thumbnail_url`):
+//!     - `loreapp://localhost/picture/<key>`: the picture file
+//!     - `loreapp://localhost/picture/<key>/thumbnail/<size>`: one of the `THUMBNAIL_SIZES` thumbnails
+//! where `<key>` is the percent-encoded record key of the picture (`picture:<key>`).
+//! On Windows the webview reaches the scheme as `https://loreapp.localhost/...`.
+//!
+//! Notes:
+//!     - Only the files of the media library (and of the legacy `get_pictures_path()`) are served. The pictures
+//!       created before the media library are served once imported to it (see `PictureBmc::import_legacy_pictures`).
+//!     - The handler runs on the webview thread: the files are resolved from the `PictureAsset`s cached by the
+//!       model, the store is never queried and no picture is decoded here.
+//!     - A single byte range is supported (`Range: bytes=a-b`), other requests get the whole file.
+//!     - The responses carry an `ETag` (content hash) and `If-None-Match` gets a 304.
+//!     - The edited pictures are served rendered (see `model::picture_edit`). A missing render (e.g. after
+//!       a vault import) is generated in the background, the original is served meanwhile.
+
+use crate::fs::media::{thumbnail_path, THUMBNAIL_SIZES};
+use crate::fs::{get_media_path, get_pictures_path};
+use crate::model::ctx::Ctx;
+use crate::model::{render_file, render_picture, PictureEdits};
+use crate::prelude::f;
+use image::ImageFormat;
+use once_cell::sync::Lazy;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::http::{Request, Response, ResponseBuilder};
+use tauri::{AppHandle, Wry};
+
+pub const SCHEME: &str = "loreapp";
+
+#[cfg(windows)]
+const BASE_URL: &str = "https://loreapp.localhost";
+#[cfg(not(windows))]
+const BASE_URL: &str = "loreapp://localhost";
+
+pub fn picture_url(id: &str) -> String {
+    f!("{BASE_URL}/picture/{}", encode_key(id))
+}
+
+pub fn thumbnail_url(id: &str, size: u32) -> String {
+    f!("{BASE_URL}/picture/{}/thumbnail/{size}", encode_key(id))
+}
+
+fn encode_key(id: &str) -> String {
+    let key = id.split_once(':').map_or(id, |(_, key)| key);
+    utf8_percent_encode(key, NON_ALPHANUMERIC).to_string()
+}
+
+#[derive(Debug, PartialEq)]
+enum Asset {
+    Picture(String),
+    Thumbnail(String, u32),
+}
+
+/// The requested asset of an URL (with or without the scheme and host)
+fn parse_asset(uri: &str) -> Option<Asset> {
+    let path = uri.split_once("://").map_or(uri, |(_, rest)| rest.find('/').map_or("", |i| &rest[i..]));
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let id = |key: &str| percent_decode_str(key).decode_utf8().ok().map(|key| f!("picture:{key}"));
+    match segments.as_slice() {
+        ["picture", key] => id(key).map(Asset::Picture),
+        ["picture", key, "thumbnail", size] => {
+            let size = size.parse().ok().filter(|size| THUMBNAIL_SIZES.contains(size))?;
+            id(key).map(|id| Asset::Thumbnail(id, size))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Whole,
+    Part(u64, u64),
+    Unsatisfiable,
+}
+
+/// Parse a `Range` header for a file of `len` bytes, into an inclusive range
+fn parse_range(header: Option<&str>, len: u64) -> ByteRange {
+    let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Whole;
+    };
+    // Note: multiple ranges may be ignored, the whole file is a valid response
+    let Some((start, end)) = spec.split_once('-').filter(|_| !spec.contains(',')) else {
+        return ByteRange::Whole;
+    };
+
+    let range = match (start.trim().parse::<u64>(), end.trim().parse::<u64>()) {
+        (Ok(start), Ok(end)) => Some((start, end.min(len.saturating_sub(1)))),
+        (Ok(start), Err(_)) if end.trim().is_empty() => Some((start, len.saturating_sub(1))),
+        (Err(_), Ok(suffix)) if start.trim().is_empty() && suffix > 0 => Some((len.saturating_sub(suffix), len.saturating_sub(1))),
+        _ => None,
+    };
+
+    match range {
+        Some((start, end)) if start <= end && start < len => ByteRange::Part(start, end),
+        _ => ByteRange::Unsatisfiable,
+    }
+}
+
+/// The renders generated in the background, so a render isn't started twice
+static RENDERING: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(Mutex::default);
+
+/// Generate the missing render of an edited picture, out of the webview thread
+fn render_in_background(picture_path: String, hash: String, edits: PictureEdits, thumbnail_size: Option<u32>) {
+    let (render_path, _) = render_file(&picture_path, &hash, &edits, thumbnail_size);
+    {
+        let Ok(mut rendering) = RENDERING.lock() else {
+            return;
+        };
+        if !rendering.insert(render_path.clone()) {
+            return;
+        }
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(err) = render_picture(&picture_path, &hash, &edits, thumbnail_size) {
+            error!("{SCHEME}: failed to render {picture_path}: {err}");
+        }
+        if let Ok(mut rendering) = RENDERING.lock() {
+            rendering.remove(&render_path);
+        }
+    });
+}
+
+/// The file of an asset
+struct AssetFile {
+    path: PathBuf,
+    etag: String,
+}
+
+/// The file of the asset, none for the unknown (or trashed) pictures and the pictures not in the media library
+fn resolve_asset(app: &AppHandle<Wry>, asset: &Asset) -> Option<AssetFile> {
+    let (id, thumbnail_size) = match asset {
+        Asset::Picture(id) => (id, None),
+        Asset::Thumbnail(id, size) => (id, Some(*size)),
+    };
+    let picture = Ctx::from_app(app.clone()).ok()?.get_model_manager().picture_asset(id)?;
+    let hash = picture.hash?;
+
+    if let Some(edits) = picture.edits {
+        let (path, etag) = render_file(&picture.path, &hash, &edits, thumbnail_size);
+        if path.is_file() {
+            return Some(AssetFile { path, etag });
+        }
+        render_in_background(picture.path.clone(), hash.clone(), edits, thumbnail_size);
+    }
+
+    match thumbnail_size {
+        Some(size) => Some(AssetFile {
+            path: thumbnail_path(&hash, size),
+            etag: f!("\"{hash}-{size}\""),
+        }),
+        None => Some(AssetFile {
+            path: PathBuf::from(picture.path),
+            etag: f!("\"{hash}\""),
+        }),
+    }
+}
+
+fn is_vault_media(path: &Path) -> bool {
+    let Ok(path) = path.canonicalize() else {
+        return false;
+    };
+    [get_media_path(), get_pictures_path()]
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .any(|dir| path.starts_with(dir))
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().map(|ext| ext.to_string_lossy().to_lowercase()) {
+        Some(ext) if ext == "svg" => "image/svg+xml",
+        _ => ImageFormat::from_path(path).map_or("application/octet-stream", |format| format.to_mime_type()),
+    }
+}
+
+fn status(code: u16) -> Result<Response, Box<dyn std::error::Error>> {
+    ResponseBuilder::new().status(code).body(Vec::new())
+}
+
+pub fn handle(app: &AppHandle<Wry>, request: &Request) -> Result<Response, Box<dyn std::error::Error>> {
+    let method = request.method().as_str();
+    if method != "GET" && method != "HEAD" {
+        return status(405);
+    }
+    let Some(asset) = parse_asset(request.uri()) else {
+        return status(404);
+    };
+    let Some(AssetFile { path, etag }) = resolve_asset(app, &asset) else {
+        return status(404);
+    };
+    if !is_vault_media(&path) {
+        warn!("{SCHEME}: refused to serve {}, outside of the vault", path.display());
+        return status(403);
+    }
+
+    let Ok(metadata) = fs::metadata(&path) else {
+        return status(404);
+    };
+    let len = metadata.len();
+
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+    let response = ResponseBuilder::new()
+        .header("Accept-Ranges", "bytes")
+        .header("Cache-Control", "no-cache")
+        .header("ETag", &etag)
+        .mimetype(mime_type(&path));
+
+    if header("If-None-Match").map_or(false, |tags| tags.split(',').any(|tag| tag.trim() == etag)) {
+        return response.status(304).body(Vec::new());
+    }
+
+    match parse_range(header("Range"), len) {
+        ByteRange::Whole => {
+            let data = if method == "HEAD" { Vec::new() } else { fs::read(&path)? };
+            response.header("Content-Length", len).status(200).body(data)
+        }
+        ByteRange::Part(start, end) => {
+            let mut data = vec![0; (end - start + 1) as usize];
+            if method == "GET" {
+                let mut file = File::open(&path)?;
+                file.seek(SeekFrom::Start(start))?;
+                file.read_exact(&mut data)?;
+            }
+            response
+                .header("Content-Range", f!("bytes {start}-{end}/{len}"))
+                .header("Content-Length", end - start + 1)
+                .status(206)
+                .body(data)
+        }
+        ByteRange::Unsatisfiable => response.header("Content-Range", f!("bytes */{len}")).status(416).body(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_asset() {
+        let url = picture_url("picture:⟨a b⟩");
+        assert_eq!(parse_asset(&url), Some(Asset::Picture("picture:⟨a b⟩".to_string())));
+        assert_eq!(
+            parse_asset("https://loreapp.localhost/picture/abc/thumbnail/256?v=1"),
+            Some(Asset::Thumbnail("picture:abc".to_string(), 256))
+        );
+        assert_eq!(parse_asset("loreapp://localhost/picture/abc/thumbnail/100"), None);
+        assert_eq!(parse_asset("loreapp://localhost/document/abc"), None);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range(None, 100), ByteRange::Whole);
+        assert_eq!(parse_range(Some("bytes=0-9"), 100), ByteRange::Part(0, 9));
+        assert_eq!(parse_range(Some("bytes=90-"), 100), ByteRange::Part(90, 99));
+        assert_eq!(parse_range(Some("bytes=-10"), 100), ByteRange::Part(90, 99));
+        assert_eq!(parse_range(Some("bytes=50-500"), 100), ByteRange::Part(50, 99));
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 100), ByteRange::Whole);
+        assert_eq!(parse_range(Some("bytes=100-"), 100), ByteRange::Unsatisfiable);
+    }
+}
@@ -33,6 +33,40 @@ pub enum SortBy {
     UpdateDate,
 }
 
+#[derive(TS, Serialize, Deserialize, PartialEq, SerdeDiff, Clone)]
+#[ts(export, rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct RevisionsSettings {
+    /// Maximum number of revisions kept per document, `0` keeps all of them
+    pub max_per_document: u32,
+    /// Revisions older than this number of days are pruned, `0` keeps them forever
+    pub max_age_days: u32,
+}
+
+#[derive(TS, Serialize, Deserialize, PartialEq, SerdeDiff, Clone)]
+#[ts(export, rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct TrashSettings {
+    /// Trashed items older than this number of days are purged on startup, `0` keeps them forever
+    pub purge_after_days: u32,
+}
+
+#[derive(TS, Serialize, Deserialize, PartialEq, SerdeDiff, Clone, Default)]
+#[ts(export, rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct PicturesSettings {
+    /// Directories whose picture files are imported automatically, and kept in sync while the app runs
+    pub watched_dirs: Vec<String>,
+}
+
+#[derive(TS, Serialize, Deserialize, PartialEq, SerdeDiff, Clone)]
+#[ts(export, rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct CacheSettings {
+    /// Records kept in memory per cached entity (tags, categories, ...), `0` disables the cache
+    pub entity_cache_size: u32,
+}
+
 #[derive(TS, Serialize, Deserialize, PartialEq, SerdeDiff, Clone)]
 #[ts(export, rename_all = "camelCase")]
 #[serde(rename_all = "camelCase")]
@@ -40,6 +74,14 @@ pub struct AppSettings {
     pub editor_mode: EditorMode,
     pub editor: EditorSettings,
     pub sort_by: SortBy,
+    #[serde(default)]
+    pub revisions: RevisionsSettings,
+    #[serde(default)]
+    pub trash: TrashSettings,
+    #[serde(default)]
+    pub pictures: PicturesSettings,
+    #[serde(default)]
+    pub cache: CacheSettings,
 }
 
 pub type AppSettingsState = Arc<AppSettings>;
@@ -54,6 +96,35 @@ impl Default for AppSettings {
                 cursor_position: false,
             },
             sort_by: SortBy::Normal,
+            revisions: RevisionsSettings::default(),
+            trash: TrashSettings::default(),
+            pictures: PicturesSettings::default(),
+            cache: CacheSettings::default(),
+        }
+    }
+}
+
+impl Default for RevisionsSettings {
+    fn default() -> Self {
+        Self {
+            max_per_document: 50,
+            max_age_days: 0,
+        }
+    }
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        Self {
+            purge_after_days: 30,
+        }
+    }
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            entity_cache_size: 512,
         }
     }
 }
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use crate::{Result, Error};
 use serde_diff::{Apply, Diff, SerdeDiff};
 use crate::prelude::f;
+use ts_gen::TS;
 
 pub fn apply_diff<T>(old: &mut T, new: &T) -> Result<()>
     where T: Serialize + for<'a> Deserialize<'a> + SerdeDiff
@@ -9,4 +10,226 @@ pub fn apply_diff<T>(old: &mut T, new: &T) -> Result<()>
     let json_diff_data = serde_json::to_string(&Diff::serializable(old, new))?;
     let mut deserializer = serde_json::Deserializer::from_str(&json_diff_data);
     Apply::apply(&mut deserializer, old).map_err(|ex| Error::Other(f!("serde_diff deserializer apply error: {ex}")))
-}
\ No newline at end of file
+}
+#[derive(TS, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[ts(export)]
+pub enum LineChangeKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(TS, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[ts(export)]
+pub struct LineChange {
+    pub kind: LineChangeKind,
+    pub line: String,
+}
+
+/// Edits searched for the middle snake of a range, the ranges that need more are split at the furthest
+/// point reached (the diff is then not always the shortest one)
+const MAX_SNAKE_EDITS: isize = 1024;
+
+/// Line based diff between two texts (Myers, in linear space).
+/// In each block of changes, the deleted lines come before the inserted ones.
+pub fn diff_lines(old: &str, new: &str) -> Vec<LineChange> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let mut changes = Vec::<LineChange>::with_capacity(old.len().max(new.len()));
+    diff_range(&old, &new, &mut changes);
+
+    // Move the deletions before the insertions of each block of changes
+    let mut start = 0;
+    while start < changes.len() {
+        let end = changes[start..]
+            .iter()
+            .position(|c| c.kind == LineChangeKind::Equal)
+            .map_or(changes.len(), |p| start + p);
+        changes[start..end].sort_by_key(|c| c.kind == LineChangeKind::Insert);
+        start = end + 1;
+    }
+
+    changes
+}
+
+fn diff_range(old: &[&str], new: &[&str], changes: &mut Vec<LineChange>) {
+    let change = |kind: LineChangeKind, line: &str| LineChange {
+        kind,
+        line: line.to_string(),
+    };
+
+    // Common prefix and suffix don't need to go through the search
+    let prefix = old.iter().zip(new.iter()).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+
+    changes.extend(old[..prefix].iter().map(|l| change(LineChangeKind::Equal, l)));
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    let snake = if old_mid.is_empty() || new_mid.is_empty() {
+        None
+    } else {
+        middle_snake(old_mid, new_mid)
+    };
+    match snake {
+        Some((x0, y0, x1, y1)) => {
+            diff_range(&old_mid[..x0], &new_mid[..y0], changes);
+            changes.extend(old_mid[x0..x1].iter().map(|l| change(LineChangeKind::Equal, l)));
+            diff_range(&old_mid[x1..], &new_mid[y1..], changes);
+        }
+        None => {
+            changes.extend(old_mid.iter().map(|l| change(LineChangeKind::Delete, l)));
+            changes.extend(new_mid.iter().map(|l| change(LineChangeKind::Insert, l)));
+        }
+    }
+
+    changes.extend(old[old.len() - suffix..].iter().map(|l| change(LineChangeKind::Equal, l)));
+}
+
+/// The middle snake of a shortest edit script from `old` to `new` (both not empty), as `(x0, y0, x1, y1)`:
+/// `old[x0..x1]` equals `new[y0..y1]`, and the script splits into the scripts of the ranges before and after it.
+/// If the script needs more than `2 * MAX_SNAKE_EDITS` edits, an empty snake at the furthest forward point.
+fn middle_snake(old: &[&str], new: &[&str]) -> Option<(usize, usize, usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let delta = n - m;
+    let max = ((n + m + 1) / 2).min(MAX_SNAKE_EDITS);
+    let offset = max + 1;
+    // Furthest x (forward) and furthest x from the end (backward) on each diagonal `k = x - y`
+    let mut forward = vec![0isize; 2 * offset as usize + 1];
+    let mut backward = vec![0isize; 2 * offset as usize + 1];
+    let at = |k: isize| (k + offset) as usize;
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+
+            // The backward diagonal `delta - k` has been searched with `d - 1` edits
+            if delta % 2 != 0 && (delta - k).abs() < d && x + backward[at(delta - k)] >= n {
+                return Some((x0 as usize, y0 as usize, x as usize, y as usize));
+            }
+        }
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+
+            // The forward diagonal `delta - k` has been searched with `d` edits
+            if delta % 2 == 0 && (delta - k).abs() <= d && x + forward[at(delta - k)] >= n {
+                return Some(((n - x) as usize, (m - y) as usize, (n - x0) as usize, (m - y0) as usize));
+            }
+        }
+    }
+
+    // The furthest point reached on the forward diagonals
+    (-max..=max)
+        .step_by(2)
+        .map(|k| (forward[at(k)], forward[at(k)] - k))
+        .filter(|&(x, y)| x <= n && (0..=m).contains(&y) && (1..n + m).contains(&(x + y)))
+        .max_by_key(|&(x, y)| x + y)
+        .map(|(x, y)| (x as usize, y as usize, x as usize, y as usize))
+}
+
+/// Compact representation of the changes, unified diff hunks without context lines
+pub fn to_compact_diff(changes: &[LineChange]) -> String {
+    let mut result = String::new();
+    let (mut old_line, mut new_line) = (1usize, 1usize);
+    let mut idx = 0;
+
+    while idx < changes.len() {
+        if changes[idx].kind == LineChangeKind::Equal {
+            old_line += 1;
+            new_line += 1;
+            idx += 1;
+            continue;
+        }
+
+        let hunk_end = changes[idx..]
+            .iter()
+            .position(|c| c.kind == LineChangeKind::Equal)
+            .map(|p| idx + p)
+            .unwrap_or(changes.len());
+        let hunk = &changes[idx..hunk_end];
+        let deleted = hunk.iter().filter(|c| c.kind == LineChangeKind::Delete).count();
+        let inserted = hunk.len() - deleted;
+
+        result.push_str(&f!("@@ -{old_line},{deleted} +{new_line},{inserted} @@\n"));
+        for c in hunk {
+            let sign = if c.kind == LineChangeKind::Delete { '-' } else { '+' };
+            result.push_str(&f!("{sign}{}\n", c.line));
+        }
+
+        old_line += deleted;
+        new_line += inserted;
+        idx = hunk_end;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "a\nb\nc\nd";
+        let new = "a\nc\nx\nd";
+
+        let changes = diff_lines(old, new);
+        let kinds: Vec<LineChangeKind> = changes.iter().map(|c| c.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                LineChangeKind::Equal,
+                LineChangeKind::Delete,
+                LineChangeKind::Equal,
+                LineChangeKind::Insert,
+                LineChangeKind::Equal,
+            ]
+        );
+        assert_eq!(to_compact_diff(&changes), "@@ -2,1 +2,0 @@\n-b\n@@ -4,0 +3,1 @@\n+x\n");
+    }
+
+    #[test]
+    fn test_diff_lines_rewrite() {
+        let old: String = (0..5000).map(|i| f!("line {i}\n")).collect();
+        let new: String = (0..5000)
+            .map(|i| if i % 3 == 0 { f!("line {i}\n") } else { f!("changed {i}\n") })
+            .collect();
+
+        let changes = diff_lines(&old, &new);
+        let side = |skipped: LineChangeKind| -> String {
+            changes.iter().filter(|c| c.kind != skipped).map(|c| f!("{}\n", c.line)).collect()
+        };
+        assert_eq!(side(LineChangeKind::Insert), old);
+        assert_eq!(side(LineChangeKind::Delete), new);
+        assert_eq!(changes.iter().filter(|c| c.kind == LineChangeKind::Equal).count(), 1667);
+    }
+}
@@ -17,7 +17,7 @@ const DocumentsIndex: React.FC<Props> = ({}) => {
     if (loading) return <Spinner message="Loading" />;
 
     useEffect(() => {
-        docFmc.list().then(content => setDocuments(content));
+        docFmc.list().then(content => setDocuments(content.items));
     }, []);
 
     // const createDocument = () => {
@@ -1,24 +1,28 @@
 import {
     CategoriesTree,
     Category,
+    CategoryNode,
     CategoryForCreate,
     CategoryForUpdate,
     Document,
     DocumentForCreate,
     DocumentForUpdate,
     DocumentsFolder,
+    DocumentsFolderChild,
     DocumentsFolderForCreate,
     DocumentsFolderForUpdate,
     DocumentsFolderTree,
     DocumentsTemplate,
     DocumentsTemplateForCreate,
     DocumentsTemplateForUpdate,
+    Page,
     Picture,
     PictureForCreate,
     PictureForUpdate,
     Tag,
     TagForCreate,
     TagForUpdate,
+    TreeDelta,
 } from '@/interface';
 import ipcInvoke from '@/ipc';
 
@@ -69,12 +73,12 @@ class BaseFmc<M, C, U, F extends FilterBase> {
         return ipcInvoke<M>(`delete_${this._cmdSuffix}`, { id }, true);
     }
 
-    async list(filter?: MakeFilter<F>, list_options?: IListOptions): Promise<M[]> {
+    async list(filter?: MakeFilter<F>, list_options?: IListOptions): Promise<Page<M>> {
         let suffix = this._cmdSuffix;
         if (suffix.endsWith('y')) {
             suffix = `${suffix.substring(0, suffix.length - 1)}ie`;
         }
-        return ipcInvoke<M[]>(`list_${suffix}s`, { filter, list_options });
+        return ipcInvoke<Page<M>>(`list_${suffix}s`, { filter, list_options });
     }
 }
 
@@ -101,8 +105,8 @@ class PictureFmc extends BaseFmc<Picture, PictureForCreate, PictureForUpdate, Pi
     async listWithUrls(
         filter?: MakeFilter<PictureFilter>,
         list_options?: IListOptions,
-    ): Promise<Picture[]> {
-        return ipcInvoke<Picture[]>('list_pictures_with_urls', { filter, list_options });
+    ): Promise<Page<Picture>> {
+        return ipcInvoke<Page<Picture>>('list_pictures_with_urls', { filter, list_options });
     }
 
     async loadFolder(path: string): Promise<Picture[]> {
@@ -147,15 +151,29 @@ class DocumentsFolderFmc extends BaseFmc<
     }
 
     async addItem(id: string, subId: string) {
-        return ipcInvoke<DocumentsFolderTree>('add_folder_or_document', { id, subId });
+        return ipcInvoke<TreeDelta<DocumentsFolderChild>>('add_folder_or_document', { id, subId });
     }
 
     async removeItem(id: string, subId: string) {
-        return ipcInvoke<DocumentsFolderTree>('remove_folder_or_document', { id, subId });
+        return ipcInvoke<TreeDelta<DocumentsFolderChild>>('remove_folder_or_document', {
+            id,
+            subId,
+        });
     }
 
     async moveItem(id: string, fromId: string | undefined, toId: string | undefined) {
-        return ipcInvoke<DocumentsFolderTree>('move_folder_or_document', { id, fromId, toId });
+        return ipcInvoke<TreeDelta<DocumentsFolderChild>>('move_folder_or_document', {
+            id,
+            fromId,
+            toId,
+        });
+    }
+
+    async bulkMoveItems(ids: string[], toId: string | undefined) {
+        return ipcInvoke<TreeDelta<DocumentsFolderChild>[]>('bulk_move_folder_or_document', {
+            ids,
+            toId,
+        });
     }
 
     async listFoldersTree() {
@@ -172,24 +190,21 @@ class CategoryFmc extends BaseFmc<Category, CategoryForCreate, CategoryForUpdate
         super('category');
     }
 
-    async list(): Promise<Category[]> {
-        return ipcInvoke<Category[]>(`list_categories`, {});
-    }
 
     async createNewCategory() {
         return ipcInvoke<Category>('create_new_category', {});
     }
 
     async attachSubcategory(id: string, subId: string) {
-        return ipcInvoke<CategoriesTree>('attach_subcategory', { id, subId });
+        return ipcInvoke<TreeDelta<CategoryNode>>('attach_subcategory', { id, subId });
     }
 
     async detachSubcategory(id: string, subId: string) {
-        return ipcInvoke<CategoriesTree>('detach_subcategory', { id, subId });
+        return ipcInvoke<TreeDelta<CategoryNode>>('detach_subcategory', { id, subId });
     }
 
     async reattachSubcategory(id: string, fromId: string | undefined, toId: string | undefined) {
-        return ipcInvoke<CategoriesTree>('reattach_subcategory', { id, fromId, toId });
+        return ipcInvoke<TreeDelta<CategoryNode>>('reattach_subcategory', { id, fromId, toId });
     }
 
     async listCategoriesTree() {
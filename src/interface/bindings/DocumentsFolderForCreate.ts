@@ -1,3 +1,3 @@
 // This file was generated by [ts-gen](https://github.com/VlaydDetect/ts-gen). Do not edit this file manually.
 
-export type DocumentsFolderForCreate = { name: string, };
\ No newline at end of file
+export type DocumentsFolderForCreate = { name: string, parent?: string, };
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-gen](https://github.com/VlaydDetect/ts-gen). Do not edit this file manually.
+
+export type Page<T> = { items: Array<T>, next_cursor: string | null, total: number | null, };
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-gen](https://github.com/VlaydDetect/ts-gen). Do not edit this file manually.
+
+export type TreeDelta<N> = { "kind": "Inserted", parent: string | null, position: number, node: N, } | { "kind": "Moved", id: string, from: string | null, to: string | null, position: number, } | { "kind": "Removed", id: string, parent: string | null, position: number, } | { "kind": "Updated", node: N, } | { "kind": "Reset" };
\ No newline at end of file
@@ -37,4 +37,6 @@ export * from "./SingleInstancePayload";
 export * from "./TagForUpdate";
 export * from "./DocumentsTemplateForUpdate";
 export * from "./PictureForUpdate";
-export * from "./DocumentsFolderNode";
\ No newline at end of file
+export * from "./DocumentsFolderNode";
+export * from "./TreeDelta";
+export * from "./Page";
\ No newline at end of file
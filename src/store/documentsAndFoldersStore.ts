@@ -8,10 +8,17 @@ import {
     DocumentsFolderForUpdate,
     DocumentsFolderNode,
     DocumentsFolderTree,
+    TreeDelta,
 } from '@/interface';
+import { applyTreeDelta, type TreeAccessors } from '@/utils';
 
 type ItemType = 'document' | 'documentsFolder';
 
+const treeAccessors: TreeAccessors<DocumentsFolderChild> = {
+    idOf: child => ('DocumentsFolder' in child ? child.DocumentsFolder.id : child.Document.id),
+    childrenOf: child => ('DocumentsFolder' in child ? child.DocumentsFolder.children : undefined),
+};
+
 class DocumentsAndFoldersStore {
     //#region -------------------------- Fields --------------------------
     tree: DocumentsFolderTree = { roots: [] };
@@ -62,12 +69,12 @@ class DocumentsAndFoldersStore {
 
     private listDocumentsAsync = async () => {
         const docs = await docFmc.list();
-        this.setDocuments(docs);
+        this.setDocuments(docs.items);
     };
 
     private listFoldersAsync = async () => {
         const folders = await docsFolderFmc.list();
-        this.setFolders(folders);
+        this.setFolders(folders.items);
     };
     //#endregion -------------------------- Privates --------------------------
 
@@ -88,12 +95,19 @@ class DocumentsAndFoldersStore {
         docsFolderFmc.listFoldersTree().then(tree => this.setTree(tree));
     };
 
+    /** Patch the loaded tree with the deltas of a tree mutation, or reload it on a `Reset` */
+    applyTreeDeltas = (deltas: TreeDelta<DocumentsFolderChild>[]) => {
+        if (!deltas.every(delta => applyTreeDelta(this.tree.roots, delta, treeAccessors))) {
+            this.listTree();
+        }
+    };
+
     listDocuments = () => {
-        docFmc.list().then(docs => this.setDocuments(docs));
+        docFmc.list().then(docs => this.setDocuments(docs.items));
     };
 
     listFolders = () => {
-        docsFolderFmc.list().then(folders => this.setFolders(folders));
+        docsFolderFmc.list().then(folders => this.setFolders(folders.items));
     };
 
     listDocumentsAndFolders = () => {
@@ -185,15 +199,19 @@ class DocumentsAndFoldersStore {
     };
 
     addItemToFolder = (itemId: string, folderId: string) => {
-        docsFolderFmc.addItem(folderId, itemId).then(tree => this.setTree(tree));
+        docsFolderFmc.addItem(folderId, itemId).then(delta => this.applyTreeDeltas([delta]));
     };
 
     removeItemFromFolder = (itemId: string, folderId: string) => {
-        docsFolderFmc.removeItem(folderId, itemId).then(tree => this.setTree(tree));
+        docsFolderFmc.removeItem(folderId, itemId).then(delta => this.applyTreeDeltas([delta]));
     };
 
     moveItemBetweenFolders = (itemId: string, from: string | undefined, to: string | undefined) => {
-        docsFolderFmc.moveItem(itemId, from, to).then(tree => this.setTree(tree));
+        docsFolderFmc.moveItem(itemId, from, to).then(delta => this.applyTreeDeltas([delta]));
+    };
+
+    moveItemsToFolder = (itemIds: string[], to: string | undefined) => {
+        docsFolderFmc.bulkMoveItems(itemIds, to).then(deltas => this.applyTreeDeltas(deltas));
     };
 
     isFolder = (id: string): boolean => this.getItemType(id) === 'documentsFolder';
@@ -31,7 +31,7 @@ class DocumentsTemplatesStore {
 
     private listTemplatesAsync = async () => {
         const temps = await docsTemplateFmc.list();
-        this.setTemplates(temps);
+        this.setTemplates(temps.items);
     };
 
     createTemplateAsync = async (data: Omit<DocumentsTemplateForCreate, 'data'>) => {
@@ -55,7 +55,7 @@ class DocumentsTemplatesStore {
     };
 
     listTemplates = () => {
-        docsTemplateFmc.list().then(temps => this.setTemplates(temps));
+        docsTemplateFmc.list().then(temps => this.setTemplates(temps.items));
     };
 
     //#region -------------------------- Getters --------------------------
@@ -20,8 +20,8 @@ class PicturesStore {
     private listAllPicturesAsync = async (filter?: any, list_options?: IListOptions) => {
         const pics = await picFmc.list(filter, list_options);
         const pwus = await picFmc.listWithUrls(filter, list_options);
-        this.setPictures(pics);
-        this.setPicturesWithUrls(pwus);
+        this.setPictures(pics.items);
+        this.setPicturesWithUrls(pwus.items);
     };
     //#endregion -------------------------- Privates --------------------------
 
@@ -35,11 +35,11 @@ class PicturesStore {
     };
 
     listPictures = (filter?: any, list_options?: IListOptions) => {
-        picFmc.list(filter, list_options).then(pics => this.setPictures(pics));
+        picFmc.list(filter, list_options).then(pics => this.setPictures(pics.items));
     };
 
     listPicturesWithUrls = (filter?: any, list_options?: IListOptions) => {
-        picFmc.listWithUrls(filter, list_options).then(pics => this.setPicturesWithUrls(pics));
+        picFmc.listWithUrls(filter, list_options).then(pics => this.setPicturesWithUrls(pics.items));
     };
 
     listAllPictures = (filter?: any, list_options?: IListOptions) => {
@@ -8,10 +8,17 @@ import {
     LabelValue,
     Tag,
     TagForCreate,
+    TreeDelta,
 } from '@/interface';
+import { applyTreeDelta, type TreeAccessors } from '@/utils';
 import { catFmc, tagFmc } from '@/db';
 import type { TreeOptionType } from '@/hook';
 
+const categoryTreeAccessors: TreeAccessors<CategoryNode> = {
+    idOf: node => node.id,
+    childrenOf: node => node.children,
+};
+
 class TagsAndCategoriesStore {
     categories: Category[] = [];
 
@@ -102,7 +109,7 @@ class TagsAndCategoriesStore {
 
     listCategoriesAsync = async () => {
         const categories = await catFmc.list();
-        this.setCategories(categories);
+        this.setCategories(categories.items);
     };
 
     listCategoriesTreeAsync = async () => {
@@ -111,13 +118,20 @@ class TagsAndCategoriesStore {
     };
 
     listCategories = () => {
-        catFmc.list().then(cat => this.setCategories(cat));
+        catFmc.list().then(cat => this.setCategories(cat.items));
     };
 
     listCategoriesTree = () => {
         catFmc.listCategoriesTree().then(tree => this.setCategoriesTree(tree));
     };
 
+    /** Patch the loaded tree with the delta of a tree mutation, or reload it on a `Reset` */
+    applyCategoriesTreeDelta = (delta: TreeDelta<CategoryNode>) => {
+        if (!applyTreeDelta(this.categoriesTree.nodes, delta, categoryTreeAccessors)) {
+            this.listCategoriesTree();
+        }
+    };
+
     createNewCategory = async () => {
         const category = await catFmc.createNewCategory();
         await this.listCategoriesTreeAsync();
@@ -134,7 +148,9 @@ class TagsAndCategoriesStore {
      * @param toId id of the category to which the category is attached
      */
     reattachCategory = (id: string, fromId: string | undefined, toId: string | undefined) => {
-        catFmc.reattachSubcategory(id, fromId, toId).then(tree => this.setCategoriesTree(tree));
+        catFmc
+            .reattachSubcategory(id, fromId, toId)
+            .then(delta => this.applyCategoriesTreeDelta(delta));
     };
 
     createCategory = (name: string) => {
@@ -148,15 +164,15 @@ class TagsAndCategoriesStore {
         catFmc.create({ name }).then(newCategory => {
             catFmc
                 .attachSubcategory(fromId, newCategory.id)
-                .then(tree => this.setCategoriesTree(tree))
+                .then(delta => this.applyCategoriesTreeDelta(delta))
                 .finally(() => this.listCategories());
         });
     };
 
     createAndAttachNewCategory = async (fromId: string) => {
         const newCategory = await catFmc.createNewCategory();
-        const tree = await catFmc.attachSubcategory(fromId, newCategory.id);
-        this.setCategoriesTree(tree);
+        const delta = await catFmc.attachSubcategory(fromId, newCategory.id);
+        this.applyCategoriesTreeDelta(delta);
         await this.listCategoriesAsync();
         return newCategory;
     };
@@ -244,12 +260,12 @@ class TagsAndCategoriesStore {
     };
 
     listTags = () => {
-        tagFmc.list().then(tags => this.setTags(tags));
+        tagFmc.list().then(tags => this.setTags(tags.items));
     };
 
     private listTagsAsync = async () => {
         const tags = await tagFmc.list();
-        this.setTags(tags);
+        this.setTags(tags.items);
     };
 
     createTag = async (data: TagForCreate) => {
@@ -9,6 +9,7 @@ export * from './classes-utils';
 export * from './utils';
 export * from './setRef';
 export * from './json';
+export * from './tree-utils';
 
 /**
  * Safe chained function.
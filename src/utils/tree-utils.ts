@@ -0,0 +1,85 @@
+import type { TreeDelta } from '@/interface';
+
+export interface TreeAccessors<N> {
+    idOf: (node: N) => string;
+    /** `undefined` for the leaves that can't have children (e.g. a document) */
+    childrenOf: (node: N) => N[] | undefined;
+}
+
+function findNode<N>(
+    nodes: N[],
+    id: string,
+    accessors: TreeAccessors<N>,
+): { siblings: N[]; index: number } | undefined {
+    for (let index = 0; index < nodes.length; index++) {
+        const node = nodes[index];
+        if (accessors.idOf(node) === id) return { siblings: nodes, index };
+
+        const children = accessors.childrenOf(node);
+        const found = children && findNode(children, id, accessors);
+        if (found) return found;
+    }
+
+    return undefined;
+}
+
+function findChildren<N>(
+    roots: N[],
+    parent: string | null,
+    accessors: TreeAccessors<N>,
+): N[] | undefined {
+    if (parent === null) return roots;
+
+    const found = findNode(roots, parent, accessors);
+    return found && accessors.childrenOf(found.siblings[found.index]);
+}
+
+/**
+ * Apply a `TreeDelta` of the backend (see `tree_index.rs`) to the loaded tree, in place.
+ * Returns `false` when the tree must be reloaded: a `Reset`, or a delta the loaded tree doesn't match.
+ */
+export function applyTreeDelta<N>(
+    roots: N[],
+    delta: TreeDelta<N>,
+    accessors: TreeAccessors<N>,
+): boolean {
+    switch (delta.kind) {
+        case 'Inserted': {
+            const children = findChildren(roots, delta.parent, accessors);
+            if (!children) return false;
+
+            children.splice(delta.position, 0, delta.node);
+            return true;
+        }
+        case 'Moved': {
+            const found = findNode(roots, delta.id, accessors);
+            if (!found) return false;
+
+            const [node] = found.siblings.splice(found.index, 1);
+            const children = findChildren(roots, delta.to, accessors);
+            if (!children) {
+                found.siblings.splice(found.index, 0, node);
+                return false;
+            }
+
+            children.splice(delta.position, 0, node);
+            return true;
+        }
+        case 'Removed': {
+            const found = findNode(roots, delta.id, accessors);
+            if (!found) return false;
+
+            found.siblings.splice(found.index, 1);
+            return true;
+        }
+        case 'Updated': {
+            const found = findNode(roots, accessors.idOf(delta.node), accessors);
+            if (!found) return false;
+
+            found.siblings[found.index] = delta.node;
+            return true;
+        }
+        default:
+            return false;
+    }
+}